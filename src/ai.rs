@@ -1,6 +1,9 @@
 use crate::board::*;
+use crate::book::*;
+use crate::invalidmoveerror::*;
 use crate::movement::*;
 use crate::*;
+use rand::{thread_rng, Rng};
 use std::io::*;
 
 /// return the best move for a greedy algorithm
@@ -19,25 +22,351 @@ pub fn greedy(b: &Board) -> String {
         .unwrap()
 }
 
-/// ask user for input from the terminal
+/// score of a checkmate, for the player who is checkmated (see alphabeta)
+pub const MATE: i32 = 1_000_000;
+
+/// Search the moves up to the given depth (minimax over Board::value, with alpha-beta pruning)
+/// and return the score for the current player together with the principal variation. Mates
+/// found sooner get a better score
+pub fn alphabeta(b: &Board, depth: u32) -> (i32, Vec<String>) {
+    search(b, depth, -MATE - 1, MATE + 1)
+}
+
+/// same as alphabeta, but a move of the opening book is played if the position is known. The
+/// search then only scores the position after the book move
+pub fn alphabeta_with_book(b: &Board, depth: u32, book: &Book) -> (i32, Vec<String>) {
+    match book.pick(b) {
+        Some(mv) if depth > 0 => {
+            let (score, pv) = alphabeta(&b.play_move_unchecked(&mv), depth - 1);
+            (parent_score(score), std::iter::once(mv).chain(pv).collect())
+        }
+        _ => alphabeta(b, depth),
+    }
+}
+
+///the score of a position for the player who moved into it
+fn parent_score(score: i32) -> i32 {
+    // a mate further away is less good for the winner
+    match -score {
+        score if score > MATE / 2 => score - 1,
+        score => score,
+    }
+}
+
+fn search(b: &Board, depth: u32, mut alpha: i32, beta: i32) -> (i32, Vec<String>) {
+    if depth == 0 {
+        return (b.value(), Vec::new());
+    }
+    // the other win conditions (a check in checkshogi...) end the game before a checkmate
+    if b.rules.win_condition != WinCondition::Checkmate && b.game_over() {
+        return (-MATE, Vec::new());
+    }
+    // without any legal move, the current player is checkmated. The captures come first, for
+    // more cutoffs
+    let mut best = (-MATE, Vec::new());
+    for (_, mv) in b.staged_moves() {
+        let (score, pv) = search(&b.play_move_unchecked(&mv), depth - 1, -beta, -alpha);
+        let score = parent_score(score);
+        if score > best.0 || best.1.is_empty() {
+            best = (score, std::iter::once(mv).chain(pv).collect());
+        }
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/// return a legal move chosen at random, mostly useful as a baseline opponent, or "withdraw"
+/// when there is no legal move
+pub fn random(b: &Board) -> String {
+    let moves: Vec<String> = b.iter_moves().collect();
+    if moves.is_empty() {
+        return String::from("withdraw");
+    }
+    moves[thread_rng().gen_range(0, moves.len())].clone()
+}
+
+/// return a move from the opening book if the position is known, else the greedy move
+pub fn greedy_with_book(b: &Board, book: &Book) -> String {
+    book.pick(b).unwrap_or_else(|| greedy(b))
+}
+
+/// ask user for input from the terminal, in the full or the short notation (see
+/// Board::resolve_move), and return the move in the full notation. The prompt also answers
+/// commands (see read_human_input): "withdraw" is returned if the user resigns, and "undo" if he
+/// wants to take back his last move
 pub fn get_move_from_human(b: &board::Board) -> String {
-    let mut input = String::new();
-    print!("Type in your move:");
-    let _ = stdout().flush();
-    stdin()
-        .read_line(&mut input)
-        .expect("error: unable to read user input");
-    if let Some('\n') = input.chars().next_back() {
-        input.pop();
-    }
-    if let Some('\r') = input.chars().next_back() {
-        input.pop();
-    }
-    if let Err(e) = b.check_move(&input) {
-        println!("Not a valid move: {}", e);
-        return get_move_from_human(&b);
-    } else {
-        return input;
-    }
-    // return input;
+    get_move_from_human_after(b, None)
+}
+
+/// same as get_move_from_human, but the japanese notation may also refer to the previous move
+/// (see Board::from_kanji)
+pub fn get_move_from_human_after(b: &board::Board, previous: Option<&str>) -> String {
+    match read_human_input(b, previous) {
+        HumanInput::Move(mv) => mv,
+        HumanInput::Resign => String::from("withdraw"),
+        HumanInput::Undo => String::from("undo"),
+    }
+}
+
+/// what the user decided at the prompt (see read_human_input)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HumanInput {
+    /// a legal move, in the full notation
+    Move(String),
+    /// take back the last move
+    Undo,
+    /// give up the game
+    Resign,
+}
+
+/// answer of the prompt to a line typed by the user
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Prompt {
+    Input(HumanInput),
+    Reply(String),
+}
+
+const PROMPT_HELP: &str = "Type a move (\"P7g-7f\", \"P-7f\", \"７六歩\") or a command:
+  moves         list the legal moves, by piece
+  moves SQUARE  list the legal moves of the piece on a square (\"moves 7g\")
+  hint          ask the bot for a move
+  undo          take back the last move
+  resign        resign the game
+  board         show the board
+  sfen          show the position in the SFEN notation
+  help          show this help";
+
+/// Ask user for input from the terminal until he types a move, resigns or asks to undo. The other
+/// commands (moves, hint, board, sfen, help) are answered directly, type "help" for the list.
+/// Once the input is closed (or unreadable), the user resigns
+pub fn read_human_input(b: &board::Board, previous: Option<&str>) -> HumanInput {
+    read_input(b, previous, true, &mut stdin().lock())
+}
+
+/// same as read_human_input, for the games where moves cannot be taken back: "undo" is neither
+/// listed nor accepted, the user types a move or resigns
+pub fn read_human_move(b: &board::Board, previous: Option<&str>) -> HumanInput {
+    read_input(b, previous, false, &mut stdin().lock())
+}
+
+fn read_input<R: BufRead>(
+    b: &Board,
+    previous: Option<&str>,
+    undo: bool,
+    reader: &mut R,
+) -> HumanInput {
+    loop {
+        let mut input = String::new();
+        print!("Type in your move:");
+        let _ = stdout().flush();
+        match reader.read_line(&mut input) {
+            Ok(0) | Err(_) => return HumanInput::Resign,
+            Ok(_) => {}
+        }
+        match prompt(b, previous, undo, &input) {
+            Prompt::Input(input) => return input,
+            Prompt::Reply(reply) => println!("{}", reply),
+        }
+    }
+}
+
+///the legal moves, one line per piece: "P7c: P7c-7d", drops being grouped by piece type
+fn list_moves(b: &Board, from: Option<position::Position>) -> String {
+    let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+    for mv in b.iter_moves() {
+        let movement: Movement = mv.parse().unwrap();
+        if from.is_some() && movement.start != from {
+            continue;
+        }
+        let key = match movement.start {
+            Some(start) => format!("{}{}", movement.piecetype, start.to_string()),
+            None => format!("{}*", movement.piecetype),
+        };
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, moves)) => moves.push(mv),
+            None => groups.push((key, vec![mv])),
+        }
+    }
+    if groups.is_empty() {
+        return String::from("No legal move");
+    }
+    groups
+        .iter()
+        .map(|(key, moves)| format!("{}: {}", key, moves.join(" ")))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+///answer a line typed by the user, "undo" being accepted if `undo` is true
+fn prompt(b: &Board, previous: Option<&str>, undo: bool, input: &str) -> Prompt {
+    let input = input.trim();
+    let mut words = input.split_whitespace();
+    let reply = match (words.next(), words.next(), words.next()) {
+        (Some("moves"), None, _) => list_moves(b, None),
+        (Some("moves"), Some(square), None) => match square.parse() {
+            Ok(square) => list_moves(b, Some(square)),
+            Err(_) => format!("Not a square: {}", square),
+        },
+        (Some("hint"), None, _) => {
+            let mv = greedy(b);
+            format!("The bot would play {}", mv)
+        }
+        (Some("undo"), None, _) if undo => return Prompt::Input(HumanInput::Undo),
+        (Some("undo"), None, _) => String::from("Moves cannot be taken back in this game"),
+        (Some("resign"), None, _) => return Prompt::Input(HumanInput::Resign),
+        (Some("board"), None, _) => b.to_string(),
+        (Some("sfen"), None, _) => b.to_sfen(1),
+        (Some("help"), None, _) => PROMPT_HELP
+            .lines()
+            .filter(|line| undo || !line.trim_start().starts_with("undo"))
+            .collect::<Vec<&str>>()
+            .join("\n"),
+        // the origin of the move may be omitted ("P-7f")
+        _ => match b.resolve_move(input) {
+            Ok(mv) => return Prompt::Input(HumanInput::Move(mv)),
+            Err(error) => {
+                if let Ok(mv) = b.from_kanji(input, previous) {
+                    return Prompt::Input(HumanInput::Move(mv));
+                }
+                let suggestions = match (&error, b.explain_illegal(input)) {
+                    (InvalidMoveError::AmbiguousMoveError(_), _) | (_, None) => vec![],
+                    (_, Some(explanation)) => explanation.suggestions,
+                };
+                let explanation = IllegalMoveExplanation { error, suggestions };
+                format!(
+                    "Not a valid move: {} (type \"help\" for the commands)",
+                    explanation
+                )
+            }
+        },
+    };
+    Prompt::Reply(reply)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ai::*;
+    use crate::piece::*;
+
+    fn piece(color: Color, piecetype: PieceType, square: &str) -> Piece {
+        Piece {
+            color,
+            piecetype,
+            promoted: false,
+            position: Some(square.parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn search() {
+        let mut b = Board::empty();
+        b.add_piece(piece(Color::White, PieceType::King, "9a"));
+        b.add_piece(piece(Color::White, PieceType::Rook, "5a"));
+        b.add_piece(piece(Color::Black, PieceType::King, "1i"));
+        b.add_piece(piece(Color::Black, PieceType::Gold, "5e"));
+        let (score, pv) = alphabeta(&b, 2);
+        assert_eq!(pv[0], "R5a-5e");
+        assert_eq!(pv.len(), 2);
+        assert!(score > 0);
+
+        // the black king is checkmated by the gold dropped in front of it
+        let mut b = Board::empty();
+        b.add_piece(piece(Color::White, PieceType::King, "9a"));
+        b.add_piece(piece(Color::White, PieceType::Pawn, "5g"));
+        b.add_piece(Piece {
+            position: None,
+            ..piece(Color::White, PieceType::Gold, "5a")
+        });
+        b.add_piece(piece(Color::Black, PieceType::King, "5i"));
+        let (score, pv) = alphabeta(&b, 2);
+        assert_eq!(pv, vec!["G*5h"]);
+        assert_eq!(score, MATE - 1);
+    }
+
+    #[test]
+    fn search_with_book() {
+        let b = Board::new();
+        let mut book = Book::new();
+        book.add_game(&["P9c-9d"], 1);
+        let (score, pv) = alphabeta_with_book(&b, 2, &book);
+        assert_eq!(pv[0], "P9c-9d");
+        assert_eq!(pv.len(), 2);
+        assert_eq!(score, -alphabeta(&b.play_move("P9c-9d"), 1).0);
+        // out of the book, the search decides
+        let (score, pv) = alphabeta_with_book(&b, 1, &Book::new());
+        assert_eq!(score, alphabeta(&b, 1).0);
+        assert_eq!(pv.len(), 1);
+    }
+
+    #[test]
+    fn random_without_moves() {
+        let b = Board::from_sfen("4k4/9/4P4/9/9/9/9/9/4K4 b G 1")
+            .unwrap()
+            .play_move("G*5h");
+        assert_eq!(b.iter_moves().count(), 0);
+        assert_eq!(random(&b), "withdraw");
+    }
+
+    #[test]
+    fn prompt_commands() {
+        let b = Board::new();
+        let reply = |input| match prompt(&b, None, true, input) {
+            Prompt::Reply(reply) => reply,
+            Prompt::Input(input) => panic!("unexpected {:?}", input),
+        };
+        assert_eq!(
+            prompt(&b, None, true, " P-7d "),
+            Prompt::Input(HumanInput::Move(String::from("P7c-7d")))
+        );
+        assert_eq!(
+            prompt(&b, None, true, "undo"),
+            Prompt::Input(HumanInput::Undo)
+        );
+        assert_eq!(
+            prompt(&b, None, true, "resign"),
+            Prompt::Input(HumanInput::Resign)
+        );
+
+        let moves = reply("moves");
+        assert_eq!(moves.lines().count(), 17);
+        assert!(moves.contains("P7c: P7c-7d\n"));
+        let king = moves
+            .lines()
+            .find(|line| line.starts_with("K5a: "))
+            .unwrap();
+        assert_eq!(king.split(' ').count(), 4);
+        assert_eq!(reply("moves 7c"), "P7c: P7c-7d");
+        assert_eq!(reply("moves 5e"), "No legal move");
+        assert!(reply("moves 0z").starts_with("Not a square"));
+        assert!(reply("hint").starts_with("The bot would play "));
+        assert_eq!(reply("sfen"), b.to_sfen(1));
+        assert!(reply("help").contains("moves SQUARE"));
+        assert!(reply("P7c-7e").starts_with("Not a valid move: "));
+
+        // when moves cannot be taken back, undo is neither listed nor accepted
+        assert_eq!(
+            prompt(&b, None, false, "undo"),
+            Prompt::Reply(String::from("Moves cannot be taken back in this game"))
+        );
+        match prompt(&b, None, false, "help") {
+            Prompt::Reply(help) => assert!(!help.contains("undo") && help.contains("resign")),
+            input => panic!("unexpected {:?}", input),
+        }
+    }
+
+    #[test]
+    fn closed_input() {
+        let b = Board::new();
+        assert_eq!(
+            read_input(&b, None, true, &mut "board\nP-7d\n".as_bytes()),
+            HumanInput::Move(String::from("P7c-7d"))
+        );
+        assert_eq!(
+            read_input(&b, None, true, &mut "board\n".as_bytes()),
+            HumanInput::Resign
+        );
+    }
 }
@@ -0,0 +1,93 @@
+use shogai::book::Book;
+use shogai::csa::*;
+use shogai::player::*;
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str =
+    "usage: csa [--port PORT] [--games N] [--kifu DIR] [--book BOOK] HOST NAME PASSWORD PLAYER
+connect PLAYER to a server speaking the CSA protocol (port 4081 by default) and play N games (1 by
+default), writing their records in DIR, and playing from the opening BOOK first
+players: greedy, random, human, usi:COMMAND (external USI engine)";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn number(value: Option<String>) -> usize {
+    value
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| fail("expected a number"))
+}
+
+fn main() {
+    let mut port = 4081;
+    let mut games = 1;
+    let mut kifu_dir: Option<PathBuf> = None;
+    let mut book = None;
+    let mut positional = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => port = number(args.next()) as u16,
+            "--games" => games = number(args.next()),
+            "--kifu" => {
+                kifu_dir = Some(PathBuf::from(
+                    args.next().unwrap_or_else(|| fail("expected a directory")),
+                ))
+            }
+            "--book" => {
+                let path = args.next().unwrap_or_else(|| fail("expected a file"));
+                book = Some(
+                    Book::load(&path)
+                        .unwrap_or_else(|e| fail(&format!("cannot read {}: {}", path, e))),
+                );
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            other => positional.push(other.to_string()),
+        }
+    }
+    let (host, name, password, player) = match positional.as_slice() {
+        [host, name, password, player] => (host, name, password, player),
+        _ => fail("expected a host, a name, a password and a player"),
+    };
+    let mut player = player_by_name(player, book)
+        .unwrap_or_else(|| fail(&format!("unknown player: {}", player)));
+
+    let result = (|| -> Result<(), CsaError> {
+        let mut client = CsaClient::connect((host.as_str(), port))?;
+        client.login(name, password)?;
+        for _ in 0..games {
+            let summary = client.wait_game()?;
+            println!(
+                "game {}: {} - {}",
+                summary.game_id, summary.white, summary.black
+            );
+            client.agree(&summary)?;
+            let outcome = client.play(&summary, player.as_mut())?;
+            println!(
+                "{:?} ({}) after {} moves",
+                outcome.result,
+                outcome.reason.as_deref().unwrap_or("no reason given"),
+                outcome.kifu.game.moves().len()
+            );
+            if let Some(dir) = &kifu_dir {
+                let path = dir.join(format!("{}.csa", summary.game_id.replace('/', "_")));
+                if let Err(e) = outcome.kifu.save(&path) {
+                    eprintln!("cannot write {}: {}", path.display(), e);
+                }
+            }
+        }
+        client.logout()
+    })();
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,65 @@
+use shogai::book::Book;
+use shogai::httpapi::*;
+
+use std::env;
+use std::net::TcpListener;
+use std::process;
+use std::sync::{Arc, Mutex};
+
+const USAGE: &str = "usage: httpapi [--address ADDRESS] [--port PORT] [--book BOOK]
+serve the JSON API on ADDRESS (127.0.0.1 by default) and PORT (8080 by default), the bots
+playing from the opening BOOK first if given, routes:
+  POST   /games                  create a game, from {\"sfen\": \"...\"} if given
+  GET    /games/ID               state of the game
+  DELETE /games/ID               forget the game
+  POST   /games/ID/moves         play {\"move\": \"P7g-7f\"}
+  POST   /games/ID/undo          take back the last move
+  POST   /games/ID/bot           let {\"bot\": \"greedy|random|alphabeta\", \"depth\": N} play
+  GET    /games/ID/analysis      best move and continuation, ?depth=N";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn main() {
+    let mut address = String::from("127.0.0.1");
+    let mut port: u16 = 8080;
+    let mut book = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--address" => address = args.next().unwrap_or_else(|| fail("expected an address")),
+            "--port" => {
+                port = args
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or_else(|| fail("expected a port"))
+            }
+            "--book" => {
+                let path = args.next().unwrap_or_else(|| fail("expected a book"));
+                book = Some(
+                    Book::load(&path)
+                        .unwrap_or_else(|e| fail(&format!("cannot read {}: {}", path, e))),
+                );
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            other => fail(&format!("unknown argument: {}", other)),
+        }
+    }
+
+    let listener = TcpListener::bind((address.as_str(), port))
+        .unwrap_or_else(|e| fail(&format!("cannot listen on {}:{}: {}", address, port, e)));
+    println!("listening on http://{}:{}", address, port);
+    let api = match book {
+        Some(book) => Api::with_book(book),
+        None => Api::new(),
+    };
+    if let Err(e) = serve(listener, Arc::new(Mutex::new(api))) {
+        eprintln!("server error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,87 @@
+use shogai::book::Book;
+use shogai::matchrunner::*;
+use shogai::player::*;
+
+use std::env;
+use std::fs;
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "usage: match [--games N] [--openings FILE] [--book BOOK] [--time SECONDS] [--increment SECONDS] [--max-moves N] PLAYER PLAYER
+players: greedy, random, human, usi:COMMAND (external USI engine)
+FILE contains one SFEN per line, the players (but humans) play from the opening BOOK first";
+
+fn player(name: &str, book: Option<Book>) -> Box<dyn Player> {
+    player_by_name(name, book).unwrap_or_else(|| fail(&format!("unknown player: {}", name)))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn number(value: Option<String>) -> f64 {
+    value
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| fail("expected a number"))
+}
+
+fn main() {
+    let mut settings = MatchSettings::default();
+    let mut players = Vec::new();
+    let mut book = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => settings.games = number(args.next()) as usize,
+            "--max-moves" => settings.max_moves = number(args.next()) as usize,
+            "--time" => settings.time_per_game = Some(Duration::from_secs_f64(number(args.next()))),
+            "--increment" => settings.increment = Duration::from_secs_f64(number(args.next())),
+            "--openings" => {
+                let path = args.next().unwrap_or_else(|| fail("expected a file"));
+                let content = fs::read_to_string(&path)
+                    .unwrap_or_else(|e| fail(&format!("cannot read {}: {}", path, e)));
+                settings.openings = content
+                    .lines()
+                    .map(|line| line.trim().to_string())
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .collect();
+            }
+            "--book" => {
+                let path = args.next().unwrap_or_else(|| fail("expected a file"));
+                book = Some(
+                    Book::load(&path)
+                        .unwrap_or_else(|e| fail(&format!("cannot read {}: {}", path, e))),
+                );
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            name => players.push(name.to_string()),
+        }
+    }
+    if players.len() != 2 {
+        fail("exactly two players are needed");
+    }
+
+    let mut first = player(&players[0], book.clone());
+    let mut second = player(&players[1], book);
+    let result = run_match(
+        first.as_mut(),
+        second.as_mut(),
+        &settings,
+        &mut |game, outcome| {
+            println!(
+                "game {}: {:?} after {} moves ({:?})",
+                game + 1,
+                outcome.result.winner,
+                outcome.moves,
+                outcome.result.reason
+            )
+        },
+    )
+    .unwrap_or_else(|e| fail(&e));
+
+    println!("{} vs {}: {}", first.name(), second.name(), result);
+}
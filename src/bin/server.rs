@@ -0,0 +1,73 @@
+use shogai::matchrunner::*;
+use shogai::server::*;
+
+use std::env;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+const USAGE: &str = "usage: server [--address ADDRESS] [--port PORT] [--time SECONDS] [--increment SECONDS] [--kifu DIR]
+pair the clients connecting on ADDRESS (0.0.0.0 by default) and PORT (4081 by default), two by two,
+and write their games in DIR. The time is per player and per game, 0 for unlimited (15 minutes by
+default). Clients start with \"NAME <name>\", then send \"MOVE <move>\" or \"RESIGN\"";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+fn number(value: Option<String>) -> f64 {
+    value
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| fail("expected a number"))
+}
+
+fn report(white: &str, black: &str, outcome: &GameOutcome) {
+    let winner = match outcome.result.winner {
+        Some(winner) => format!("{:?} wins", winner),
+        None => String::from("draw"),
+    };
+    println!(
+        "{} - {}: {} by {:?} after {} moves",
+        white, black, winner, outcome.result.reason, outcome.moves
+    );
+}
+
+fn main() {
+    let mut settings = ServerSettings::default();
+    let mut address = String::from("0.0.0.0");
+    let mut port = 4081;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--address" => address = args.next().unwrap_or_else(|| fail("expected an address")),
+            "--port" => port = number(args.next()) as u16,
+            "--time" => {
+                settings.time_per_game = match number(args.next()) {
+                    time if time > 0. => Some(Duration::from_secs_f64(time)),
+                    _ => None,
+                }
+            }
+            "--increment" => settings.increment = Duration::from_secs_f64(number(args.next())),
+            "--kifu" => {
+                settings.kifu_dir = Some(PathBuf::from(
+                    args.next().unwrap_or_else(|| fail("expected a directory")),
+                ))
+            }
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            other => fail(&format!("unknown argument: {}", other)),
+        }
+    }
+
+    let listener = TcpListener::bind((address.as_str(), port))
+        .unwrap_or_else(|e| fail(&format!("cannot listen on {}:{}: {}", address, port, e)));
+    println!("waiting for players on {}:{}", address, port);
+    if let Err(e) = serve(listener, settings, report) {
+        eprintln!("server error: {}", e);
+        process::exit(1);
+    }
+}
@@ -2,14 +2,35 @@ use crate::invalidmoveerror::*;
 use crate::movement::*;
 use crate::piece::*;
 use crate::position::*;
+use crate::variant::*;
 
 use arrayvec::ArrayVec;
-use std::iter::once;
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Rules {
     pub can_uncover_check: bool,
     pub can_restart: bool,
+    ///board, pieces and moves of the game (see the variant module)
+    pub variant: &'static Variant,
+    ///how the game is won
+    pub win_condition: WinCondition,
+}
+
+///how a player wins the game (see Rules)
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WinCondition {
+    ///the usual rule: the player whose king cannot escape (or who cannot move) has lost
+    Checkmate,
+    ///checkshogi: the first player giving a check wins (a checkmate still wins too)
+    FirstCheck,
+    ///the king has to be captured, and may be left in check
+    KingCapture,
+    ///try rule: a player also wins by bringing his king to the starting square of the
+    ///opponent's king
+    Try,
 }
 
 impl Default for Rules {
@@ -17,6 +38,8 @@ impl Default for Rules {
         Rules {
             can_uncover_check: false,
             can_restart: false,
+            variant: &Variant::STANDARD,
+            win_condition: WinCondition::Checkmate,
         }
     }
 }
@@ -33,13 +56,18 @@ pub struct Board {
 impl std::fmt::Display for Board {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let lines = ["a", "b", "c", "d", "e", "f", "g", "h", "i"];
+        let size = self.rules.variant.size;
+        let border = format!("+{}+\n", "-".repeat(2 * size as usize));
         let mut counter = 0;
-        write!(f, " 9 8 7 6 5 4 3 2 1 \n")?;
-        write!(f, "+------------------+\n")?;
-        for line in 0..9 {
+        for column in (1..=size).rev() {
+            write!(f, " {}", column)?;
+        }
+        write!(f, " \n")?;
+        write!(f, "{}", border)?;
+        for line in 0..size {
             write!(f, "|")?;
-            for column in 0..9 {
-                if let Some(p) = &self.is_occupied_by(Position(line * 9 + (8 - column))) {
+            for column in 0..size {
+                if let Some(p) = &self.is_occupied_by(Position(line * 9 + (size - 1 - column))) {
                     write!(f, "{}", p)?;
                 } else {
                     write!(f, "  ")?;
@@ -48,7 +76,7 @@ impl std::fmt::Display for Board {
             write!(f, "|{}\n", lines[counter])?;
             counter += 1;
         }
-        write!(f, "+------------------+\n")?;
+        write!(f, "{}", border)?;
         write!(f, "White reserve: ")?;
         for white_reserve in self
             .iter()
@@ -169,27 +197,432 @@ impl Board {
             .sum()
     }
 
-    /// return whether the current player is checkmated
+    /// return whether the current player has lost, according to the win condition of the rules
     pub fn game_over(&self) -> bool {
-        if self.rules.can_uncover_check {
-            return self.contains(PieceType::King, self.get_color());
-        }
-
-        for my_possible_move in self.iter_moves_partial_check() {
-            let board_before_next = self.play_move_unchecked(&my_possible_move);
-            let mut have_move_that_take_the_king = false;
-            for opponent_next_move in board_before_next.iter_normal_moves_only(false) {
-                let board_after_next_move =
-                    board_before_next.play_move_unchecked(&opponent_next_move);
-                if !board_after_next_move.contains(PieceType::King, self.get_color()) {
-                    have_move_that_take_the_king = true;
+        self.lost_by().is_some()
+    }
+
+    /// return how the current player has lost (a checkmate, a check in checkshogi...), or None
+    /// while the game goes on
+    pub fn lost_by(&self) -> Option<WinCondition> {
+        let color = self.get_color();
+        let condition = if self.rules.can_uncover_check {
+            WinCondition::KingCapture
+        } else {
+            self.rules.win_condition
+        };
+        let lost = match condition {
+            // the king being left in check, the game goes on until it is taken
+            WinCondition::KingCapture => {
+                return Some(condition).filter(|_| !self.contains(PieceType::King, color));
+            }
+            WinCondition::FirstCheck => self.is_check(),
+            WinCondition::Try => {
+                let start = self.king_start(color);
+                let mut opponent = color;
+                opponent.invert();
+                start.is_some()
+                    && self
+                        .iter_pieces(opponent)
+                        .any(|p| p.piecetype == PieceType::King && p.position == start)
+            }
+            WinCondition::Checkmate => false,
+        };
+        if lost {
+            Some(condition)
+        } else if self.checkmated() {
+            Some(WinCondition::Checkmate)
+        } else {
+            None
+        }
+    }
+
+    /// return whether the current player may declare the win by impasse (jishogi), with the
+    /// 27 points rule: his king is in the promotion zone and not in check, with 10 other pieces
+    /// there, and the pieces in the zone and in the reserve are worth 28 points for the first
+    /// player, 27 for the second (rooks and bishops are worth 5, the other pieces 1)
+    pub fn can_declare_impasse(&self) -> bool {
+        let color = self.get_color();
+        let variant = self.rules.variant;
+        let in_zone = |p: Position| {
+            let row = p.0 / 9;
+            if color == Color::White {
+                row >= variant.size - variant.promotion_rows
+            } else {
+                row < variant.promotion_rows
+            }
+        };
+        let points = |p: &Piece| match p.piecetype {
+            PieceType::Rook | PieceType::Bishop => 5,
+            _ => 1,
+        };
+        let mine = || self.iter().filter(|p| p.color == color);
+        if !mine().any(|p| p.piecetype == PieceType::King && p.position.is_some_and(in_zone))
+            || self.is_check()
+        {
+            return false;
+        }
+        let in_zone: Vec<&Piece> = mine()
+            .filter(|p| p.piecetype != PieceType::King && p.position.is_some_and(in_zone))
+            .collect();
+        let total: u32 = in_zone.iter().map(|p| points(p)).sum::<u32>()
+            + mine()
+                .filter(|p| p.position.is_none())
+                .map(points)
+                .sum::<u32>();
+        let needed = if color == Color::White { 28 } else { 27 };
+        in_zone.len() >= 10 && total >= needed
+    }
+
+    /// return the square where the king of that color starts, in the variant of the board
+    fn king_start(&self, color: Color) -> Option<Position> {
+        Board::start(self.rules.variant)
+            .iter_pieces(color)
+            .find(|p| p.piecetype == PieceType::King)
+            .and_then(|king| king.position)
+    }
+
+    /// return whether the current player is checkmated (or cannot move at all): in check, only
+    /// the evasions have to be looked at, else any move leaving the king safe will do
+    fn checkmated(&self) -> bool {
+        if self.is_check() {
+            self.evasions().is_empty()
+        } else {
+            !self
+                .iter_moves_partial_check()
+                .any(|mv| self.leaves_king_safe(&mv))
+        }
+    }
+
+    /// return whether the king of the current player is attacked
+    pub fn is_check(&self) -> bool {
+        self.king_attacked(self.get_color())
+    }
+
+    /// return whether the king of that color is attacked by a piece of the opponent
+    fn king_attacked(&self, color: Color) -> bool {
+        let mut opponent = color;
+        opponent.invert();
+        self.king_position(color)
+            .is_some_and(|king| !self.attackers(king, opponent).is_empty())
+    }
+
+    /// return the square of the king of that color, if it is on the board
+    pub(crate) fn king_position(&self, color: Color) -> Option<Position> {
+        self.iter_pieces(color)
+            .find(|p| p.piecetype == PieceType::King && p.position.is_some())
+            .and_then(|king| king.position)
+    }
+
+    /// return the pieces of that color which could move to the square (capturing what is
+    /// there), looking at the move tables backwards rather than generating every move
+    pub fn attackers(&self, square: Position, color: Color) -> Vec<Piece> {
+        self.iter_pawns(color)
+            .chain(self.iter_pieces(color))
+            .filter(|piece| match piece.position {
+                Some(start) => start != square && self.reaches(piece, start, square, None),
+                None => false,
+            })
+            .copied()
+            .collect()
+    }
+
+    /// return whether the piece, standing on start, could move to the square: with one of its
+    /// steps, or sliding over empty squares only (the vacated square, left by a moving piece,
+    /// counting as empty)
+    pub(crate) fn reaches(
+        &self,
+        piece: &Piece,
+        start: Position,
+        square: Position,
+        vacated: Option<Position>,
+    ) -> bool {
+        let variant = self.rules.variant;
+        let sign = if piece.color == Color::White { 1 } else { -1 };
+        let moves = variant
+            .piece_moves(piece.piecetype, piece.promoted)
+            .unwrap_or(variant.moves[piece.piecetype as usize]);
+        // as seen by the first player, like the move tables
+        let relative = (
+            sign * ((square.0 % 9) as i16 - (start.0 % 9) as i16),
+            sign * ((square.0 / 9) as i16 - (start.0 / 9) as i16),
+        );
+        moves.steps.contains(&relative)
+            || moves.slides.iter().any(|&(x, y)| {
+                let n = if x != 0 {
+                    relative.0 / x
+                } else {
+                    relative.1 / y
+                };
+                n >= 1
+                    && (n * x, n * y) == relative
+                    && (1..n).all(|i| {
+                        let between = Position((start.0 as i16 + sign * i * (x + 9 * y)) as u16);
+                        Some(between) == vacated || self.is_occupied_by(between).is_none()
+                    })
+            })
+    }
+
+    /// return whether the move (checked otherwise) does not leave the king of the current
+    /// player in check
+    fn leaves_king_safe(&self, mv: &str) -> bool {
+        !self.play_move_unchecked(mv).king_attacked(self.get_color())
+    }
+
+    /// return the moves getting the current player out of check: moving the king away,
+    /// capturing the checking piece or moving (or dropping) a piece in its way. Only these moves
+    /// are generated, rather than every move, and the list is empty when the king is not in check
+    /// (or checkmated). The pawn drop checkmate is not looked at, as in iter_moves_partial_check
+    pub fn evasions(&self) -> Vec<String> {
+        let color = self.get_color();
+        let mut opponent = color;
+        opponent.invert();
+        let king = match self.king_position(color) {
+            Some(king) => king,
+            None => return Vec::new(),
+        };
+        let checkers = self.attackers(king, opponent);
+        if checkers.is_empty() {
+            return Vec::new();
+        }
+        let mut sol: Vec<String> = vec![];
+        for piece in self.iter_pieces(color).filter(|p| p.position == Some(king)) {
+            for relative in piece.get_relative_moves(self.rules.variant) {
+                sol.extend(Movement::from_relative(piece, relative));
+            }
+        }
+        // with two checking pieces, only the king can move
+        if let [checker] = checkers[..] {
+            let target = checker.position.unwrap();
+            // the squares between a sliding checking piece and the king
+            let (column, row) = (
+                (king.0 % 9) as i16 - (target.0 % 9) as i16,
+                (king.0 / 9) as i16 - (target.0 / 9) as i16,
+            );
+            let mut between = vec![];
+            if column == 0 || row == 0 || column.abs() == row.abs() {
+                let direction = column.signum() + 9 * row.signum();
+                let mut square = target.0 as i16 + direction;
+                while square != king.0 as i16 {
+                    between.push(Position(square as u16));
+                    square += direction;
+                }
+            }
+            for piece in self
+                .iter_pawns(color)
+                .chain(self.iter_pieces(color))
+                .filter(|p| p.position.is_some() && p.position != Some(king))
+            {
+                let start = piece.position.unwrap().0 as i16;
+                for relative in piece.get_relative_moves(self.rules.variant) {
+                    let end = Position((start + relative.0 + 9 * relative.1) as u16);
+                    if end == target || between.contains(&end) {
+                        sol.extend(Movement::from_relative(piece, relative));
+                    }
+                }
+            }
+            let mut reserve: Vec<PieceType> = self
+                .iter()
+                .filter(|p| p.position.is_none() && p.color == color)
+                .map(|p| p.piecetype)
+                .collect();
+            reserve.sort_by_key(|&piecetype| piecetype as usize);
+            reserve.dedup();
+            for &square in &between {
+                for &piecetype in &reserve {
+                    let mv = Movement {
+                        piecetype,
+                        start: None,
+                        end: square,
+                        promotion: false,
+                        force_capture: false,
+                        withdraw: false,
+                        restart: false,
+                    };
+                    sol.push(mv.to_string());
+                }
+            }
+        }
+        sol.retain(|mv| self.check_move_general(mv, false).is_ok() && self.leaves_king_safe(mv));
+        sol
+    }
+
+    /// return the pieces of that color pinned to their king: each one with the squares it may
+    /// still move to, between the king and the pinning piece (which it may capture)
+    fn pins(&self, color: Color) -> Vec<(Position, Vec<Position>)> {
+        self.line_blockers(color, color)
+    }
+
+    /// return the pieces of the blocker color alone between the king of that color and a sliding
+    /// piece of the opponent aiming at it: each one with the squares from the king to the sliding
+    /// piece. These are the pinned pieces for the color of the king (see pins), and the pieces
+    /// giving a discovered check by moving off the line for the opponent
+    pub(crate) fn line_blockers(
+        &self,
+        color: Color,
+        blocker: Color,
+    ) -> Vec<(Position, Vec<Position>)> {
+        let king = match self.king_position(color) {
+            Some(king) => king,
+            None => return Vec::new(),
+        };
+        let size = self.rules.variant.size as i16;
+        let sign = if color == Color::White { -1 } else { 1 };
+        let mut pins = vec![];
+        for &(x, y) in &[
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (-1, 1),
+            (1, -1),
+            (-1, -1),
+        ] {
+            let (mut column, mut row) = ((king.0 % 9) as i16, (king.0 / 9) as i16);
+            let mut ray = vec![];
+            let mut pinned = None;
+            loop {
+                column += x;
+                row += y;
+                if !(0..size).contains(&column) || !(0..size).contains(&row) {
+                    break;
+                }
+                let square = Position((row * 9 + column) as u16);
+                ray.push(square);
+                match (self.is_occupied_by(square), pinned) {
+                    (None, _) => continue,
+                    (Some(piece), None) if piece.color == blocker => pinned = Some(square),
+                    (Some(piece), Some(pinned)) if piece.color != color => {
+                        // the opponent piece slides toward the king (as seen by the first player)
+                        let slides = self
+                            .rules
+                            .variant
+                            .piece_moves(piece.piecetype, piece.promoted)
+                            .unwrap_or(self.rules.variant.moves[piece.piecetype as usize])
+                            .slides;
+                        if slides.contains(&(sign * -x, sign * -y)) {
+                            pins.push((pinned, ray));
+                        }
+                        break;
+                    }
+                    _ => break,
                 }
             }
-            if !have_move_that_take_the_king {
-                return false;
+        }
+        pins
+    }
+
+    /// generate the legal moves: the evasions when in check, else the moves of the pieces
+    /// which are not pinned (or stay on the line of their pin), the drops, and the moves of the
+    /// king to the squares the opponent does not attack
+    fn legal_moves(&self) -> Vec<String> {
+        self.keep_legal(
+            self.drop_candidates()
+                .into_iter()
+                .chain(self.board_candidates()),
+        )
+    }
+
+    /// keep the legal moves among the candidates (see drop_candidates and board_candidates)
+    pub(crate) fn keep_legal(&self, candidates: impl Iterator<Item = String>) -> Vec<String> {
+        self.keep_legal_with(candidates, &mut None)
+    }
+
+    /// same as keep_legal, the evasions being computed only if they were not yet (see
+    /// StagedMoves, which keeps the legal moves stage by stage)
+    pub(crate) fn keep_legal_with(
+        &self,
+        candidates: impl Iterator<Item = String>,
+        evasions: &mut Option<Vec<String>>,
+    ) -> Vec<String> {
+        let color = self.get_color();
+        let mut opponent = color;
+        opponent.invert();
+        let candidates: Vec<String> = if self.rules.can_uncover_check
+            || self.rules.win_condition == WinCondition::KingCapture
+            || !self.contains(PieceType::King, opponent)
+        {
+            candidates
+                .filter(|mv| self.check_move_general(mv, false).is_ok())
+                .collect()
+        } else if self.is_check() {
+            let evasions = evasions.get_or_insert_with(|| self.evasions());
+            candidates.filter(|mv| evasions.contains(mv)).collect()
+        } else {
+            let king = self.king_position(color);
+            let pins = self.pins(color);
+            candidates
+                .filter(|mv| self.check_move_general(mv, false).is_ok())
+                .filter(|mv| {
+                    let movement: Movement = mv.parse().unwrap();
+                    match movement.start {
+                        None => true,
+                        start if start == king => self.leaves_king_safe(mv),
+                        Some(start) => pins
+                            .iter()
+                            .find(|(pinned, _)| *pinned == start)
+                            .is_none_or(|(_, ray)| ray.contains(&movement.end)),
+                    }
+                })
+                .collect()
+        };
+        candidates
+            .into_iter()
+            .filter(|mv| check_checkmate_by_pawn_drop(mv, self).is_ok())
+            .collect()
+    }
+
+    /// count the positions reached after depth moves (perft), to test the move generation
+    pub fn perft(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        self.iter_moves()
+            .map(|mv| {
+                if depth == 1 {
+                    1
+                } else {
+                    self.play_move_unchecked(&mv).perft(depth - 1)
+                }
+            })
+            .sum()
+    }
+
+    ///return a 64 bits key identifying the position (variant, pieces on the board, reserves and
+    ///turn). The key does not depend on the order in which pieces were added, and is stable
+    ///between runs, so it can be stored on disk (see the book module)
+    pub fn hash_key(&self) -> u64 {
+        let mut key = if self.turn == Color::White {
+            0
+        } else {
+            splitmix64(u64::MAX)
+        };
+        // the variants are told apart by their names, standard shogi keeping the keys it had
+        let name = self.rules.variant.name;
+        if name != Variant::STANDARD.name {
+            key ^= name
+                .bytes()
+                .fold(20_000, |hash, byte| splitmix64(hash ^ byte as u64));
+        }
+        let mut reserve = [[0u64; 8]; 2];
+        for piece in self.iter() {
+            let kind = piece.piecetype as u64 + 8 * piece.promoted as u64 + 16 * piece.color as u64;
+            if let Some(pos) = piece.position {
+                key ^= splitmix64(kind * 81 + pos.0 as u64);
+            } else {
+                reserve[piece.color as usize][piece.piecetype as usize] += 1;
             }
         }
-        return true;
+        for (color, counts) in reserve.iter().enumerate() {
+            for (piecetype, &count) in counts.iter().enumerate() {
+                if count > 0 {
+                    // keys above 32*81 are free for the reserves
+                    key ^= splitmix64(10_000 + (color as u64 * 8 + piecetype as u64) * 64 + count);
+                }
+            }
+        }
+        key
     }
 
     ///return whether the board (not the reserve) contains a piece of given type and color
@@ -250,20 +683,28 @@ impl Board {
     }
 
     /// Play a move (but check if it is legal beforehand, else panic with a nice error message) and return a new board containing pieces in their new position
+    /// Use try_play_move when the move comes from an untrusted source
     #[allow(dead_code)]
     pub fn play_move(&self, mv: &str) -> Board {
-        return self.play_move_general(mv, true);
+        match self.try_play_move(mv) {
+            Ok(b) => b,
+            Err(e) => panic!("Invalid movement : {}", e),
+        }
     }
+
+    /// Play a move if it is legal and return the new board, else return why it is not legal.
+    /// This never panics, whatever the given string
+    pub fn try_play_move(&self, mv: &str) -> Result<Board, InvalidMoveError> {
+        self.check_move(mv)?;
+        Ok(self.play_move_unchecked(mv))
+    }
+
     /// Play a move and return a new board, but do no check if the move is legal, or even
     /// syntaxically correct. If given a illegal/incorrect move, this function will probably panic
     pub fn play_move_unchecked(&self, mv: &str) -> Board {
-        return self.play_move_general(mv, false);
-    }
-
-    fn play_move_general(&self, mv: &str, check: bool) -> Board {
         let mut new_board = self.clone();
         if mv == "restart" && self.rules.can_restart {
-            return Self::new();
+            return Self::start(self.rules.variant);
         }
         if mv == "withdraw" {
             if self.get_color() == Color::White {
@@ -279,12 +720,6 @@ impl Board {
             }
         }
 
-        if check {
-            if let Err(e) = self.check_move(mv) {
-                //move not valid
-                panic!("Invalid movement : {}", e);
-            }
-        }
         let movement: Movement = mv.parse().unwrap();
 
         //movement was checked so it's ok to just play
@@ -330,7 +765,8 @@ impl Board {
         }
         Ok(mv)
             .and_then(check_syntax)
-            .and_then(check_in_board)
+            .and_then(|mv| check_in_board(mv, self))
+            .and_then(|mv| check_drop_allowed(mv, self))
             .and_then(|mv| check_start(mv, self))
             .and_then(|mv| check_destination(mv, self))
             .and_then(|mv| check_possible_move(mv, self))
@@ -341,6 +777,29 @@ impl Board {
             .and_then(|mv| check_checkmate_by_pawn_drop(mv, self))
     }
 
+    /// return None if the move is legal, else the reason why it is not and the legal moves that
+    /// are the closest to what was written (eg. the same piece to other destinations, or the same
+    /// move with the '+' suffix)
+    pub fn explain_illegal(&self, mv: &str) -> Option<IllegalMoveExplanation> {
+        let error = self.check_move(mv).err()?;
+        let mut candidates: Vec<(usize, String)> = self
+            .iter_moves()
+            .map(|legal| (edit_distance(mv, &legal), legal))
+            .filter(|(distance, _)| *distance <= 3)
+            .collect();
+        candidates.sort();
+        let closest = candidates.first().map(|(distance, _)| *distance);
+        Some(IllegalMoveExplanation {
+            error,
+            suggestions: candidates
+                .into_iter()
+                .filter(|(distance, _)| Some(*distance) == closest)
+                .map(|(_, legal)| legal)
+                .take(5)
+                .collect(),
+        })
+    }
+
     #[allow(dead_code)]
     /// Check move, but not whether the king is uncovered or if the king is checkmated by a pawn
     /// drop
@@ -362,7 +821,8 @@ impl Board {
             }
             return Ok(mv)
                 .and_then(check_syntax)
-                .and_then(check_in_board)
+                .and_then(|mv| check_in_board(mv, self))
+                .and_then(|mv| check_drop_allowed(mv, self))
                 .and_then(|mv| check_start(mv, self))
                 .and_then(|mv| check_destination(mv, self))
                 .and_then(|mv| check_possible_move(mv, self))
@@ -382,32 +842,44 @@ impl Board {
         None
     }
 
-    ///add a piece to the board
+    ///add a piece to the board, panics if the player already has 32 pawns or 32 other pieces
+    ///(see try_add_piece)
     pub fn add_piece(&mut self, piece: Piece) {
-        if piece.piecetype == PieceType::Pawn {
-            if piece.color == Color::White {
-                self.white_pawns.push(piece);
-            } else {
-                self.black_pawns.push(piece);
-            }
-        } else {
-            if piece.color == Color::White {
-                self.white_pieces.push(piece);
-            } else {
-                self.black_pieces.push(piece);
-            }
-        }
+        self.try_add_piece(piece).unwrap()
+    }
+
+    ///add a piece to the board, unless the player already has 32 pawns or 32 other pieces (as
+    ///may happen with positions read from outside)
+    pub fn try_add_piece(&mut self, piece: Piece) -> Result<(), String> {
+        let pieces = match (piece.piecetype == PieceType::Pawn, piece.color) {
+            (true, Color::White) => &mut self.white_pawns,
+            (true, Color::Black) => &mut self.black_pawns,
+            (false, Color::White) => &mut self.white_pieces,
+            (false, Color::Black) => &mut self.black_pieces,
+        };
+        pieces.try_push(piece).map_err(|_| {
+            format!(
+                "too many {} for {:?}",
+                if piece.piecetype == PieceType::Pawn {
+                    "pawns"
+                } else {
+                    "pieces"
+                },
+                piece.color
+            )
+        })
     }
 
     ///centrally rotate the board
     pub fn flip(&mut self) {
+        let last = self.rules.variant.size - 1;
         let mut tmp: Vec<(Piece, Piece)> = Vec::new();
         for piece in self.iter() {
             let pos = piece.position;
             if let Some(x) = pos {
                 let i = x.0 % 9;
                 let j = x.0 / 9;
-                let new_x = (8 - j) * 9 + (8 - i);
+                let new_x = (last - j) * 9 + (last - i);
                 let new_piece = Piece {
                     color: piece.color,
                     piecetype: piece.piecetype,
@@ -425,7 +897,9 @@ impl Board {
         }
     }
 
-    ///set the regular starting position for one player
+    ///set the regular starting position for one player on the rows a to c, whatever its color
+    ///and the variant: call `set(Black)`, `flip()` then `set(White)` for a full board (see
+    ///set_start for the variants)
     pub fn set(&mut self, col: Color) {
         for i in 18..27 {
             let p = Piece {
@@ -507,15 +981,30 @@ impl Board {
         });
     }
 
+    ///set the starting position of the board's variant (see Variant::start) for one player
+    pub fn set_start(&mut self, col: Color) {
+        let start = Board::start(self.rules.variant);
+        for piece in start.iter().filter(|p| p.color == col) {
+            self.add_piece(*piece);
+        }
+    }
+
     /// create new default board with the starting configuration
     pub fn new<'a>() -> Board {
-        let mut b = Board::empty();
-        //white is always 'up' ( in the a-b-c rows)
-        b.set(Color::Black);
-        b.flip();
-        b.set(Color::White);
+        Board::start(&Variant::STANDARD)
+    }
+
+    /// create a board of the variant, with its starting configuration
+    pub fn start(variant: &'static Variant) -> Board {
+        let mut b = Board::from_sfen(variant.start).unwrap();
+        b.rules.variant = variant;
         b
     }
+
+    /// create a 5x5 minishogi board with its starting configuration
+    pub fn minishogi() -> Board {
+        Board::start(&Variant::MINISHOGI)
+    }
     /// iter over all the pieces in the board (and reserve)
     pub fn iter(&self) -> impl Iterator<Item = &Piece> {
         self.white_pieces
@@ -544,7 +1033,7 @@ impl Board {
 
     /// iter over all possible moves
     pub fn iter_moves(&self) -> impl Iterator<Item = String> {
-        return self.iter_moves_general(true);
+        self.legal_moves().into_iter()
     }
     /// iter over all possible moves without considering uncovering the king of pawn-drop ceckmate
     pub fn iter_moves_partial_check(&self) -> impl Iterator<Item = String> {
@@ -552,32 +1041,36 @@ impl Board {
     }
     /// iter only over the normal (not drops)
     pub fn iter_normal_moves_only(&self, complete_check: bool) -> impl Iterator<Item = String> {
-        let mut sol: Vec<String> = vec![];
-        let c = self.turn;
-        for &piece_to_move in self.iter().filter(|&p| p.position != None && p.color == c) {
-            for relative in piece_to_move.get_relative_moves() {
-                sol.extend(Movement::from_relative(&piece_to_move, relative));
-            }
-        }
-
         let cloned_board = self.clone();
-        return sol
+        self.board_candidates()
             .into_iter()
-            .filter(move |mv| cloned_board.check_move_general(mv, complete_check).is_ok());
+            .filter(move |mv| cloned_board.check_move_general(mv, complete_check).is_ok())
     }
 
     fn iter_moves_general(&self, complete_check: bool) -> impl Iterator<Item = String> {
         //all drops chain all moves filter check_move
-        let mut sol: Vec<String> = vec![];
+        let cloned_board = self.clone();
+        self.drop_candidates()
+            .into_iter()
+            .chain(self.board_candidates())
+            .filter(move |mv| cloned_board.check_move_general(mv, complete_check).is_ok())
+    }
 
-        //drop moves
-        for i in 0..80 {
-            for piece_to_drop in self
-                .iter()
-                .filter(|p| p.position == None && p.color == self.turn)
-            {
+    /// return the drops of the current player on every empty or occupied square, each kind of
+    /// piece of the reserve once, to be checked afterwards
+    pub(crate) fn drop_candidates(&self) -> Vec<String> {
+        let mut reserve: Vec<PieceType> = self
+            .iter()
+            .filter(|p| p.position == None && p.color == self.turn)
+            .map(|p| p.piecetype)
+            .collect();
+        reserve.sort_by_key(|&piecetype| piecetype as usize);
+        reserve.dedup();
+        let mut sol: Vec<String> = vec![];
+        for i in (0..81).filter(|&i| Position(i).in_board(self.rules.variant.size)) {
+            for &piecetype in &reserve {
                 let mv = Movement {
-                    piecetype: piece_to_drop.piecetype,
+                    piecetype,
                     start: None,
                     end: Position(i),
                     promotion: false,
@@ -585,26 +1078,36 @@ impl Board {
                     withdraw: false,
                     restart: false,
                 };
-                sol.extend(once(mv.to_string()));
+                sol.push(mv.to_string());
             }
         }
+        sol
+    }
 
+    /// return the moves of the pieces of the current player on the board, with and without
+    /// promotion, to be checked afterwards
+    pub(crate) fn board_candidates(&self) -> Vec<String> {
+        let mut sol: Vec<String> = vec![];
         for piece_to_move in self
             .iter()
             .filter(|p| p.position != None && p.color == self.turn)
         {
-            for relative in piece_to_move.get_relative_moves() {
+            for relative in piece_to_move.get_relative_moves(self.rules.variant) {
                 sol.extend(Movement::from_relative(piece_to_move, relative));
             }
         }
-
-        let cloned_board = self.clone();
-        return sol
-            .into_iter()
-            .filter(move |mv| cloned_board.check_move_general(mv, complete_check).is_ok());
+        sol
     }
 }
 
+///pseudo-random (but deterministic) 64 bits value, used to build the hash keys
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
 #[cfg(test)]
 #[allow(unused_imports)]
 mod test {
@@ -660,4 +1163,300 @@ mod test {
 
         assert_eq!(b2, b3);
     }
+
+    #[test]
+    fn set() {
+        let mut b = Board::empty();
+        b.set(Color::Black);
+        b.flip();
+        b.set(Color::White);
+        assert_eq!(b.to_sfen(1), Board::new().to_sfen(1));
+
+        for variant in Variant::ALL {
+            let mut b = Board::empty();
+            b.rules.variant = variant;
+            b.set_start(Color::White);
+            b.set_start(Color::Black);
+            assert_eq!(b.to_sfen(1), Board::start(variant).to_sfen(1));
+        }
+    }
+
+    #[test]
+    fn hash_key_ignores_move_order() {
+        let b = Board::new();
+        let b1 = b
+            .play_move("P9c-9d")
+            .play_move("P1g-1f")
+            .play_move("P8c-8d");
+        let b2 = b
+            .play_move("P8c-8d")
+            .play_move("P1g-1f")
+            .play_move("P9c-9d");
+        assert_eq!(b1.hash_key(), b2.hash_key());
+        assert_ne!(b.hash_key(), b1.hash_key());
+        assert_ne!(b1.hash_key(), b.play_move("P9c-9d").hash_key());
+
+        // the same pieces in another variant make another position
+        let mut mini = b.clone();
+        mini.rules.variant = &Variant::MINISHOGI;
+        assert_ne!(mini.hash_key(), b.hash_key());
+    }
+
+    #[test]
+    fn perft() {
+        let b = Board::new();
+        assert_eq!(b.perft(0), 1);
+        assert_eq!(b.perft(1), 30);
+        assert_eq!(b.perft(2), 900);
+    }
+
+    #[test]
+    fn check() {
+        assert!(!Board::new().is_check());
+        let mut b = Board::empty();
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::King,
+            promoted: false,
+            position: Some("5a".parse().unwrap()),
+        });
+        b.add_piece(Piece {
+            color: Color::Black,
+            piecetype: PieceType::Rook,
+            promoted: false,
+            position: Some("5e".parse().unwrap()),
+        });
+        assert!(b.is_check());
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Pawn,
+            promoted: false,
+            position: Some("5c".parse().unwrap()),
+        });
+        assert!(!b.is_check());
+    }
+
+    #[test]
+    fn try_play_invalid_moves() {
+        let b = Board::new();
+        for mv in &[
+            "", "Pzz-1", "P9c-9e", "P*5e", "P9c-9d+", "restart", "K5a-5é",
+        ] {
+            assert!(b.try_play_move(mv).is_err());
+        }
+        assert_eq!(b.try_play_move("P9c-9d").unwrap(), b.play_move("P9c-9d"));
+    }
+
+    #[test]
+    fn explain() {
+        let b = Board::new();
+        assert_eq!(b.explain_illegal("P9c-9d"), None);
+        let explanation = b.explain_illegal("P9c9d").unwrap();
+        assert_eq!(explanation.suggestions, vec!["P9c-9d"]);
+        let explanation = b.explain_illegal("R8b-8c").unwrap();
+        assert_eq!(explanation.suggestions.len(), 5);
+        assert!(explanation
+            .suggestions
+            .iter()
+            .all(|mv| mv.starts_with("R8b-")));
+
+        let mut b = Board::empty();
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Pawn,
+            promoted: false,
+            position: Some("5h".parse().unwrap()),
+        });
+        let explanation = b.explain_illegal("P5h-5i").unwrap();
+        assert_eq!(
+            explanation.error,
+            InvalidMoveError::PromotionError {
+                piecetype: PieceType::Pawn,
+                destination: "5i".parse().unwrap(),
+                mandatory: true
+            }
+        );
+        assert_eq!(explanation.suggestions, vec!["P5h-5i+"]);
+    }
+
+    #[test]
+    fn minishogi() {
+        let b = Board::minishogi();
+        assert_eq!(b.iter().count(), 12);
+        assert_eq!(b.perft(1), 14);
+        assert!(b.check_move("P1b-1c").is_ok());
+        assert!(b.check_move("R5a-6a").is_err());
+        assert!(b.check_move("K1a-1f").is_err());
+
+        // the pawn promotes (and has to) on the last row only
+        let mut b = Board::empty();
+        b.rules.variant = &Variant::MINISHOGI;
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Pawn,
+            promoted: false,
+            position: Some("3c".parse().unwrap()),
+        });
+        assert!(b.check_move("P3c-3d+").is_err());
+        let mut b = b.play_move("P3c-3d");
+        b.turn = Color::White;
+        assert!(b.check_move("P3d-3e").is_err());
+        assert_eq!(b.check_move("P3d-3e+"), Ok("P3d-3e+"));
+    }
+
+    #[test]
+    fn win_conditions() {
+        let piece = |color, piecetype, square: &str| Piece {
+            color,
+            piecetype,
+            promoted: false,
+            position: Some(square.parse().unwrap()),
+        };
+        let mut b = Board::empty();
+        b.add_piece(piece(Color::White, PieceType::King, "5a"));
+        b.add_piece(piece(Color::Black, PieceType::King, "1i"));
+        b.add_piece(piece(Color::White, PieceType::Rook, "1e"));
+
+        // a check only wins in checkshogi
+        let checked = b.play_move("R1e-1h");
+        assert_eq!(checked.lost_by(), None);
+        b.rules.win_condition = WinCondition::FirstCheck;
+        assert_eq!(
+            b.play_move("R1e-1h").lost_by(),
+            Some(WinCondition::FirstCheck)
+        );
+
+        // the king may be left in check, and has to be taken
+        b.rules.win_condition = WinCondition::KingCapture;
+        let mut checked = b.play_move("R1e-1h");
+        assert!(checked.check_move("K1i-2i").is_ok());
+        checked.turn = Color::White;
+        assert_eq!(checked.lost_by(), None);
+        let taken = checked.play_move("R1h-1i");
+        assert_eq!(taken.lost_by(), Some(WinCondition::KingCapture));
+
+        // the white king reaches 5i, where the black king starts
+        b.rules.win_condition = WinCondition::Try;
+        b.add_piece(piece(Color::White, PieceType::King, "5h"));
+        assert!(!b.play_move("K5h-4h").game_over());
+        assert_eq!(b.play_move("K5h-5i").lost_by(), Some(WinCondition::Try));
+    }
+
+    #[test]
+    fn evasions() {
+        let piece = |color, piecetype, square: &str| Piece {
+            color,
+            piecetype,
+            promoted: false,
+            position: Some(square.parse().unwrap()),
+        };
+        // the gold, protected by the pawn, mates the king
+        let mut b = Board::empty();
+        b.add_piece(piece(Color::Black, PieceType::King, "5i"));
+        b.add_piece(piece(Color::White, PieceType::King, "5a"));
+        b.add_piece(piece(Color::White, PieceType::Gold, "5h"));
+        b.add_piece(piece(Color::White, PieceType::Pawn, "5g"));
+        b.turn = Color::Black;
+        assert!(b.evasions().is_empty());
+        assert_eq!(b.lost_by(), Some(WinCondition::Checkmate));
+        b.add_piece(piece(Color::Black, PieceType::Silver, "4i"));
+        assert_eq!(b.evasions(), ["S4i-5h"]);
+        assert_eq!(b.lost_by(), None);
+
+        // the evasions are the legal moves, when in check
+        let mut checks = 0;
+        for game in 0..10 {
+            let mut b = Board::new();
+            for ply in 0..120 {
+                let moves: Vec<String> = b
+                    .iter_moves_partial_check()
+                    .filter(|mv| b.leaves_king_safe(mv))
+                    .collect();
+                if moves.is_empty() {
+                    break;
+                }
+                if b.is_check() {
+                    checks += 1;
+                    let color = b.get_color();
+                    let mut expected: Vec<String> = b
+                        .iter_moves_partial_check()
+                        .filter(|mv| {
+                            let after = b.play_move_unchecked(mv);
+                            after.iter_normal_moves_only(false).all(|reply| {
+                                after
+                                    .play_move_unchecked(&reply)
+                                    .contains(PieceType::King, color)
+                            })
+                        })
+                        .collect();
+                    expected.sort();
+                    expected.dedup();
+                    let mut evasions = b.evasions();
+                    evasions.sort();
+                    assert_eq!(evasions, expected, "{}", b.to_sfen(1));
+                }
+                b = b.play_move_unchecked(&moves[(game * 31 + ply * 17) % moves.len()]);
+            }
+        }
+        assert!(checks > 0);
+    }
+
+    #[test]
+    fn pins() {
+        let piece = |color, piecetype, square: &str| Piece {
+            color,
+            piecetype,
+            promoted: false,
+            position: Some(square.parse().unwrap()),
+        };
+        // the silver is pinned by the bishop, and may only capture it
+        let mut b = Board::empty();
+        b.add_piece(piece(Color::White, PieceType::King, "5a"));
+        b.add_piece(piece(Color::White, PieceType::Silver, "4b"));
+        b.add_piece(piece(Color::Black, PieceType::Bishop, "3c"));
+        b.add_piece(piece(Color::Black, PieceType::King, "5i"));
+        assert_eq!(
+            b.pins(Color::White),
+            [(
+                "4b".parse().unwrap(),
+                vec!["4b".parse().unwrap(), "3c".parse().unwrap()]
+            )]
+        );
+        let silver: Vec<String> = b.iter_moves().filter(|mv| mv.starts_with('S')).collect();
+        assert_eq!(silver, ["S4b-3c"]);
+        assert_eq!(
+            b.check_move("S4b-4c"),
+            Err(InvalidMoveError::UncoverCheckError {
+                checking_piece: piece(Color::Black, PieceType::Bishop, "3c")
+            })
+        );
+        // once the silver is moved away, the king is in check
+        b.turn = Color::Black;
+        let b = b.play_move("B3c-2b");
+        assert_eq!(b.pins(Color::White), []);
+        assert!(b.play_move("S4b-4c").play_move("B2b-3c").is_check());
+    }
+
+    #[test]
+    fn legal_moves() {
+        assert_eq!(Board::new().perft(3), 25470);
+        // the generated moves are the ones passing all the checks of check_move
+        for game in 0..6 {
+            let mut b = Board::new();
+            for ply in 0..100 {
+                let mut moves: Vec<String> = b.iter_moves().collect();
+                let mut expected: Vec<String> = b
+                    .iter_moves_partial_check()
+                    .filter(|mv| b.check_move(mv).is_ok())
+                    .collect();
+                moves.sort();
+                expected.sort();
+                assert_eq!(moves, expected, "{}", b.to_sfen(1));
+                if moves.is_empty() {
+                    break;
+                }
+                b = b.play_move_unchecked(&moves[(game * 31 + ply * 17) % moves.len()]);
+            }
+        }
+    }
 }
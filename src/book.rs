@@ -0,0 +1,261 @@
+use crate::board::*;
+use crate::kifu::*;
+
+use rand::{thread_rng, Rng};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BookMove {
+    pub mv: String,
+    pub weight: u32,
+}
+
+/// Map positions (through Board::hash_key) to weighted candidate moves
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Book {
+    entries: HashMap<u64, Vec<BookMove>>,
+}
+
+impl Book {
+    ///return an empty book
+    pub fn new() -> Self {
+        Book::default()
+    }
+
+    ///return the number of positions known by the book
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    ///return whether the book does not know any position
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///add a move to the book, if the move is already known for this position its weight is
+    ///increased
+    pub fn add(&mut self, b: &Board, mv: &str, weight: u32) {
+        self.add_by_key(b.hash_key(), mv, weight);
+    }
+
+    fn add_by_key(&mut self, key: u64, mv: &str, weight: u32) {
+        let candidates = self.entries.entry(key).or_default();
+        if let Some(known) = candidates.iter_mut().find(|c| c.mv == mv) {
+            known.weight += weight;
+        } else {
+            candidates.push(BookMove {
+                mv: mv.to_string(),
+                weight,
+            });
+        }
+    }
+
+    ///return the candidate moves for this position (empty if the position is unknown)
+    pub fn get(&self, b: &Board) -> &[BookMove] {
+        match self.entries.get(&b.hash_key()) {
+            Some(candidates) => candidates,
+            None => &[],
+        }
+    }
+
+    ///choose a legal move at random among the candidates, according to their weights
+    pub fn pick(&self, b: &Board) -> Option<String> {
+        let candidates: Vec<&BookMove> = self
+            .get(b)
+            .iter()
+            .filter(|c| c.weight > 0 && b.check_move(&c.mv).is_ok())
+            .collect();
+        let total: u32 = candidates.iter().map(|c| c.weight).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut chosen = thread_rng().gen_range(0, total);
+        for candidate in candidates {
+            if chosen < candidate.weight {
+                return Some(candidate.mv.clone());
+            }
+            chosen -= candidate.weight;
+        }
+        None
+    }
+
+    ///replay a game from the starting position, adding each of its first `max_ply` moves.
+    ///Stop at the first move that is not legal, and return the number of moves added
+    pub fn add_game(&mut self, moves: &[&str], max_ply: usize) -> usize {
+        self.add_game_from(&Board::new(), moves, max_ply)
+    }
+
+    ///same as add_game, for a game started from another position (a variant, a handicap...)
+    pub fn add_game_from(&mut self, start: &Board, moves: &[&str], max_ply: usize) -> usize {
+        let mut b = start.clone();
+        let mut added = 0;
+        for &mv in moves.iter().take(max_ply) {
+            if mv == "withdraw" || mv == "restart" || b.check_move(mv).is_err() {
+                break;
+            }
+            self.add(&b, mv, 1);
+            b = b.play_move_unchecked(mv);
+            added += 1;
+        }
+        added
+    }
+
+    ///build a book from every file in a directory. Each file is a game record: KIF, CSA and USI
+    ///records are read with Kifu::load (see KifuFormat::from_path), the other files are moves in
+    ///the usual notation separated by whitespace, lines starting with '#' being ignored
+    pub fn from_directory<P: AsRef<Path>>(dir: P, max_ply: usize) -> io::Result<Book> {
+        let mut book = Book::new();
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+        for path in paths {
+            match KifuFormat::from_path(&path) {
+                Some(KifuFormat::Kif) | Some(KifuFormat::Csa) | Some(KifuFormat::Usi) => {
+                    let kifu = Kifu::load(&path).map_err(|e| match e {
+                        KifuError::IoError(e) => e,
+                        e => io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{}: {}", path.display(), e),
+                        ),
+                    })?;
+                    let moves: Vec<&str> = kifu.game.moves().iter().map(String::as_str).collect();
+                    book.add_game_from(kifu.game.start(), &moves, max_ply);
+                    continue;
+                }
+                _ => {}
+            }
+            let record = fs::read_to_string(path)?;
+            let moves: Vec<&str> = record
+                .lines()
+                .filter(|line| !line.trim_start().starts_with('#'))
+                .flat_map(|line| line.split_whitespace())
+                .collect();
+            book.add_game(&moves, max_ply);
+        }
+        Ok(book)
+    }
+
+    ///read a book in the text format written by `write_to`
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Book> {
+        let mut book = Book::new();
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 3 {
+                return Err(invalid_data(&line));
+            }
+            let key = u64::from_str_radix(fields[0], 16).map_err(|_| invalid_data(&line))?;
+            let weight = fields[2].parse().map_err(|_| invalid_data(&line))?;
+            book.add_by_key(key, fields[1], weight);
+        }
+        Ok(book)
+    }
+
+    ///write the book as text, one "<hash key in hex> <move> <weight>" line per candidate move
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut keys: Vec<&u64> = self.entries.keys().collect();
+        keys.sort();
+        for key in keys {
+            for candidate in &self.entries[key] {
+                writeln!(writer, "{:016x} {} {}", key, candidate.mv, candidate.weight)?;
+            }
+        }
+        Ok(())
+    }
+
+    ///load a book from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Book> {
+        Book::read_from(fs::File::open(path)?)
+    }
+
+    ///save the book into a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(io::BufWriter::new(fs::File::create(path)?))
+    }
+}
+
+fn invalid_data(line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid book line: {}", line),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::book::*;
+    use crate::game::*;
+
+    #[test]
+    fn build_and_pick() {
+        let mut book = Book::new();
+        assert_eq!(book.add_game(&["P9c-9d", "P1g-1f", "P8c-8d"], 2), 2);
+        assert_eq!(book.add_game(&["P9c-9d", "P9g-9f", "Pzz-1"], 10), 2);
+        assert_eq!(book.len(), 2);
+
+        let b = Board::new();
+        assert_eq!(
+            book.get(&b),
+            &[BookMove {
+                mv: "P9c-9d".to_string(),
+                weight: 2
+            }]
+        );
+        assert_eq!(book.pick(&b), Some("P9c-9d".to_string()));
+        let after = b.play_move("P9c-9d");
+        assert_eq!(book.get(&after).len(), 2);
+        assert_eq!(book.pick(&after.play_move("P1g-1f")), None);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut book = Book::new();
+        book.add_game(&["P9c-9d", "P1g-1f"], 10);
+        let mut text: Vec<u8> = Vec::new();
+        book.write_to(&mut text).unwrap();
+        assert_eq!(Book::read_from(&text[..]).unwrap(), book);
+        assert!(Book::read_from(&b"not a book"[..]).is_err());
+    }
+
+    #[test]
+    fn variants_and_records() {
+        let mut book = Book::new();
+        let minishogi = Board::minishogi();
+        assert_eq!(book.add_game_from(&minishogi, &["P1b-1c"], 10), 1);
+        assert_eq!(book.pick(&minishogi), Some("P1b-1c".to_string()));
+        assert_eq!(book.pick(&Board::new()), None);
+
+        let dir = std::env::temp_dir().join(format!("shogai-book-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut game = Game::from_board(Board::minishogi());
+        game.play("P1b-1c").unwrap();
+        Kifu::new(game).save(dir.join("mini.kif")).unwrap();
+        let mut game = Game::new();
+        game.play("P9c-9d").unwrap();
+        game.play("P9g-9f").unwrap();
+        Kifu::new(game).save(dir.join("standard.csa")).unwrap();
+        fs::write(dir.join("game.txt"), "# a comment\nP9c-9d P1g-1f\n").unwrap();
+        let book = Book::from_directory(&dir, 10).unwrap();
+        assert_eq!(book.len(), 3);
+        assert_eq!(book.pick(&minishogi), Some("P1b-1c".to_string()));
+        assert_eq!(book.pick(&Board::new()), Some("P9c-9d".to_string()));
+        assert_eq!(book.get(&Board::new().play_move("P9c-9d")).len(), 2);
+
+        fs::write(
+            dir.join("broken.kif"),
+            "手数----指手---------消費時間--\n   1 ９九王\n",
+        )
+        .unwrap();
+        assert!(Book::from_directory(&dir, 10).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
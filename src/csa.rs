@@ -0,0 +1,550 @@
+use crate::game::*;
+use crate::invalidmoveerror::*;
+use crate::kifu::*;
+use crate::piece::*;
+use crate::player::*;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum CsaError {
+    IoError(io::Error),
+    /// the server refused the name or the password
+    LoginError(String),
+    /// the game was rejected (by the opponent or the server)
+    RejectedError(String),
+    ProtocolError(String),
+    IllegalMoveError(String, InvalidMoveError),
+}
+
+impl fmt::Display for CsaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsaError::IoError(e) => write!(f, "Communication with the server failed: {}", e),
+            CsaError::LoginError(line) => write!(f, "Login refused: {}", line),
+            CsaError::RejectedError(line) => write!(f, "Game rejected: {}", line),
+            CsaError::ProtocolError(line) => {
+                write!(f, "Unexpected message from the server: {}", line)
+            }
+            CsaError::IllegalMoveError(mv, e) => write!(f, "The server sent {}: {}", mv, e),
+        }
+    }
+}
+
+impl error::Error for CsaError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CsaError::IoError(e) => Some(e),
+            CsaError::IllegalMoveError(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CsaError {
+    fn from(e: io::Error) -> Self {
+        CsaError::IoError(e)
+    }
+}
+
+/// the game proposed by the server (its Game_Summary)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSummary {
+    pub game_id: String,
+    /// name of the first player ("+", White here)
+    pub white: String,
+    pub black: String,
+    /// the color we play
+    pub color: Color,
+    /// the starting position, with the moves already played if the game is resumed
+    pub game: Game,
+    /// unit of the times below, and of the times sent with the moves
+    pub time_unit: Duration,
+    /// time for the whole game, per player
+    pub total_time: Duration,
+    /// time per move once the total time is spent
+    pub byoyomi: Duration,
+    /// time added after each move
+    pub increment: Duration,
+    pub max_moves: Option<usize>,
+}
+
+impl GameSummary {
+    /// read the lines of a summary, from "BEGIN Game_Summary" to "END Game_Summary" included
+    pub fn parse(lines: &[String]) -> Result<GameSummary, CsaError> {
+        let error = |line: &str| CsaError::ProtocolError(line.to_string());
+        let mut fields: Vec<(&str, &str)> = Vec::new();
+        let mut position = String::new();
+        let mut section = "";
+        for line in lines {
+            match line.as_str() {
+                "BEGIN Game_Summary" | "END Game_Summary" | "END Time" | "END Position" => {
+                    section = ""
+                }
+                "BEGIN Time" => section = "Time",
+                "BEGIN Position" => section = "Position",
+                line if section == "Position" => {
+                    position.push_str(line);
+                    position.push('\n');
+                }
+                line => match line.split_once(':') {
+                    Some(field) => fields.push(field),
+                    None if line.is_empty() => {}
+                    None => return Err(error(line)),
+                },
+            }
+        }
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| *value)
+        };
+        let unit = match field("Time_Unit").unwrap_or("1sec") {
+            unit if unit.ends_with("msec") => (unit.trim_end_matches("msec"), 1),
+            unit if unit.ends_with("sec") => (unit.trim_end_matches("sec"), 1000),
+            unit if unit.ends_with("min") => (unit.trim_end_matches("min"), 60_000),
+            unit => return Err(error(unit)),
+        };
+        let unit = Duration::from_millis(unit.0.parse::<u64>().unwrap_or(1) * unit.1);
+        let time = |name: &str| -> Result<Duration, CsaError> {
+            match field(name) {
+                None => Ok(Duration::from_secs(0)),
+                Some(value) => value
+                    .parse::<u32>()
+                    .map(|n| unit * n)
+                    .map_err(|_| error(value)),
+            }
+        };
+
+        let kifu = Kifu::parse(&position, KifuFormat::Csa).map_err(|e| error(&e.to_string()))?;
+        Ok(GameSummary {
+            game_id: field("Game_ID").unwrap_or_default().to_string(),
+            white: field("Name+").unwrap_or_default().to_string(),
+            black: field("Name-").unwrap_or_default().to_string(),
+            color: match field("Your_Turn") {
+                Some("+") => Color::White,
+                Some("-") => Color::Black,
+                _ => return Err(error("Your_Turn is missing")),
+            },
+            game: kifu.game,
+            time_unit: unit,
+            total_time: time("Total_Time")?,
+            byoyomi: time("Byoyomi")?,
+            increment: time("Increment")?,
+            max_moves: field("Max_Moves").and_then(|n| n.parse().ok()),
+        })
+    }
+}
+
+/// how the game ended for us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsaResult {
+    Win,
+    Lose,
+    Draw,
+    /// the game was interrupted without a result
+    Censored,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsaOutcome {
+    pub result: CsaResult,
+    /// why the game ended, as sent by the server ("RESIGN", "TIME_UP", "ILLEGAL_MOVE",
+    /// "SENNICHITE"...)
+    pub reason: Option<String>,
+    /// the record of the game
+    pub kifu: Kifu,
+    /// time consumed by each player, as counted by the server (indexed by color)
+    pub times: [Duration; 2],
+}
+
+/// the result of the game for the record, from ours and the reason sent by the server
+fn game_result(us: Color, result: CsaResult, reason: Option<&str>) -> Option<GameResult> {
+    let reason = match reason? {
+        "RESIGN" => Termination::Resignation,
+        "CHECKMATE" => Termination::Checkmate,
+        "TIME_UP" => Termination::TimeForfeit,
+        "ILLEGAL_MOVE" => Termination::IllegalMove,
+        "SENNICHITE" => Termination::Repetition,
+        "OUTE_SENNICHITE" => Termination::PerpetualCheck,
+        "JISHOGI" => Termination::Impasse,
+        "MAX_MOVES" => Termination::MaxMoves,
+        // a player left, or the server stopped the game
+        "ABNORMAL" => Termination::Disconnection,
+        _ => return None,
+    };
+    match result {
+        CsaResult::Win => Some(GameResult::win(us, reason)),
+        CsaResult::Lose => Some(GameResult::loss(us, reason)),
+        CsaResult::Draw => Some(GameResult::draw(reason)),
+        CsaResult::Censored => None,
+    }
+}
+
+/// A connection to a server speaking the CSA protocol (such as floodgate): log in, wait for a
+/// game, agree to it and play it with any Player
+pub struct CsaClient {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl CsaClient {
+    /// connect to a server, 4081 being the usual port
+    pub fn connect<A: ToSocketAddrs>(address: A) -> Result<Self, CsaError> {
+        let stream = TcpStream::connect(address)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(CsaClient { stream, reader })
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), CsaError> {
+        writeln!(self.stream, "{}", line)?;
+        Ok(())
+    }
+
+    ///the next line, skipping the empty ones the servers send to keep the connection alive
+    fn read(&mut self) -> Result<String, CsaError> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(CsaError::IoError(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the server closed the connection",
+                )));
+            }
+            let line = line.trim();
+            if !line.is_empty() {
+                return Ok(line.to_string());
+            }
+        }
+    }
+
+    pub fn login(&mut self, name: &str, password: &str) -> Result<(), CsaError> {
+        self.send(&format!("LOGIN {} {}", name, password))?;
+        let line = self.read()?;
+        if line == format!("LOGIN:{} OK", name) {
+            Ok(())
+        } else if line.starts_with("LOGIN:") {
+            Err(CsaError::LoginError(line))
+        } else {
+            Err(CsaError::ProtocolError(line))
+        }
+    }
+
+    /// wait until the server proposes a game, and return its summary
+    pub fn wait_game(&mut self) -> Result<GameSummary, CsaError> {
+        let line = self.read()?;
+        if line != "BEGIN Game_Summary" {
+            return Err(CsaError::ProtocolError(line));
+        }
+        let mut lines = vec![line];
+        while lines.last().unwrap() != "END Game_Summary" {
+            lines.push(self.read()?);
+        }
+        GameSummary::parse(&lines)
+    }
+
+    /// accept the game, and wait until it starts
+    pub fn agree(&mut self, summary: &GameSummary) -> Result<(), CsaError> {
+        self.send(&format!("AGREE {}", summary.game_id))?;
+        let line = self.read()?;
+        if line.starts_with("START:") {
+            Ok(())
+        } else if line.starts_with("REJECT:") {
+            Err(CsaError::RejectedError(line))
+        } else {
+            Err(CsaError::ProtocolError(line))
+        }
+    }
+
+    /// Play the game, the player choosing our moves. A player offering a draw is asked again (the
+    /// protocol has no draw offers), and one choosing an illegal move resigns instead
+    pub fn play(
+        &mut self,
+        summary: &GameSummary,
+        player: &mut dyn Player,
+    ) -> Result<CsaOutcome, CsaError> {
+        let mut kifu = Kifu::new(summary.game.clone());
+        kifu.white = Some(summary.white.clone());
+        kifu.black = Some(summary.black.clone());
+        let mut times = [Duration::from_secs(0); 2];
+        let mut reason = None;
+        // whether we have a move waiting for its confirmation by the server
+        let mut sent = false;
+
+        loop {
+            // once the game is over (we are checkmated...), only the result is left to read
+            if kifu.game.board().get_color() == summary.color
+                && !sent
+                && reason.is_none()
+                && kifu.game.result().is_none()
+            {
+                // the protocol has no draw offers: the player is asked again once, and resigns if
+                // it offers a draw again
+                let mut offered = false;
+                let statement = loop {
+                    match player.choose_move(&kifu.game) {
+                        PlayerAction::OfferDraw if !offered => offered = true,
+                        PlayerAction::Move(mv) if kifu.game.board().check_move(&mv).is_ok() => {
+                            break csa_move(kifu.game.board(), &mv)
+                        }
+                        PlayerAction::DeclareImpasse => break String::from("%KACHI"),
+                        _ => break String::from("%TORYO"),
+                    }
+                };
+                self.send(&statement)?;
+                sent = true;
+            }
+
+            let line = self.read()?;
+            let (statement, time) = match line.split_once(",T") {
+                Some((statement, time)) => (statement, time.parse::<u64>().ok()),
+                None => (line.as_str(), None),
+            };
+            match statement.chars().next() {
+                Some(sign @ ('+' | '-')) => {
+                    let color = if sign == '+' {
+                        Color::White
+                    } else {
+                        Color::Black
+                    };
+                    let b = kifu.game.board();
+                    if color != b.get_color() {
+                        return Err(CsaError::ProtocolError(line));
+                    }
+                    let mv = parse_csa_move(b, &statement[1..])
+                        .ok_or_else(|| CsaError::ProtocolError(line.clone()))?;
+                    kifu.game
+                        .play(&mv)
+                        .map_err(|e| CsaError::IllegalMoveError(line.clone(), e))?;
+                    if let Some(time) = time {
+                        times[color as usize] += summary.time_unit * time as u32;
+                    }
+                    sent = false;
+                }
+                // a resignation or a win declaration (the reason comes with the result)
+                Some('%') => {}
+                Some('#') => match &statement[1..] {
+                    "WIN" | "LOSE" | "DRAW" | "CENSORED" => {
+                        let result = match &statement[1..] {
+                            "WIN" => CsaResult::Win,
+                            "LOSE" => CsaResult::Lose,
+                            "DRAW" => CsaResult::Draw,
+                            _ => CsaResult::Censored,
+                        };
+                        kifu.result = game_result(summary.color, result, reason.as_deref());
+                        return Ok(CsaOutcome {
+                            result,
+                            reason,
+                            kifu,
+                            times,
+                        });
+                    }
+                    other => reason = Some(other.to_string()),
+                },
+                _ => return Err(CsaError::ProtocolError(line)),
+            }
+        }
+    }
+
+    pub fn logout(mut self) -> Result<(), CsaError> {
+        self.send("LOGOUT")?;
+        // the server may close the connection without answering
+        match self.read() {
+            Ok(line) if line != "LOGOUT:completed" => Err(CsaError::ProtocolError(line)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::*;
+    use crate::csa::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const SUMMARY: &str = "BEGIN Game_Summary
+Protocol_Version:1.2
+Protocol_Mode:Server
+Format:Shogi 1.0
+Game_ID:test-1
+Name+:bot
+Name-:opponent
+Your_Turn:+
+To_Move:+
+BEGIN Time
+Time_Unit:1sec
+Total_Time:600
+Byoyomi:10
+END Time
+BEGIN Position
+PI
+P+
+P-
++
+END Position
+END Game_Summary
+";
+
+    ///a server playing a scripted game: each line it expects, and the lines it answers
+    fn mock_server(script: &[(&str, &str)]) -> std::net::SocketAddr {
+        let script: Vec<(String, String)> = script
+            .iter()
+            .map(|(expected, answer)| (expected.to_string(), answer.to_string()))
+            .collect();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            for (expected, answer) in &script {
+                if !expected.is_empty() {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    assert_eq!(line.trim(), expected);
+                }
+                stream.write_all(answer.as_bytes()).unwrap();
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn scripted_game() {
+        let address = mock_server(&[
+            ("LOGIN bot secret", "LOGIN:bot OK\n"),
+            ("", SUMMARY),
+            ("AGREE test-1", "START:test-1\n"),
+            ("+7776FU", "+7776FU,T3\n\n-3334FU,T5\n"),
+            ("+8822UM", "+8822UM,T1\n%TORYO,T12\n#RESIGN\n#WIN\n"),
+            ("LOGOUT", "LOGOUT:completed\n"),
+        ]);
+        let mut client = CsaClient::connect(address).unwrap();
+        client.login("bot", "secret").unwrap();
+        let summary = client.wait_game().unwrap();
+        assert_eq!(summary.white, "bot");
+        assert_eq!(summary.color, Color::White);
+        assert_eq!(summary.total_time, Duration::from_secs(600));
+        assert_eq!(summary.byoyomi, Duration::from_secs(10));
+        assert_eq!(*summary.game.board(), Board::new());
+        client.agree(&summary).unwrap();
+
+        let mut moves = vec!["B2b-8h+", "P3c-3d"];
+        let mut player = |_: &Board| moves.pop().unwrap().to_string();
+        let outcome = client.play(&summary, &mut player).unwrap();
+        assert_eq!(outcome.result, CsaResult::Win);
+        assert_eq!(outcome.reason.as_deref(), Some("RESIGN"));
+        assert_eq!(outcome.kifu.game.moves(), ["P3c-3d", "P7g-7f", "B2b-8h+"]);
+        assert_eq!(
+            outcome.kifu.result,
+            Some(GameResult::win(Color::White, Termination::Resignation))
+        );
+        assert_eq!(outcome.times[Color::White as usize], Duration::from_secs(4));
+        assert_eq!(outcome.times[Color::Black as usize], Duration::from_secs(5));
+        client.logout().unwrap();
+    }
+
+    #[test]
+    fn refusals() {
+        let address = mock_server(&[("LOGIN bot wrong", "LOGIN:incorrect\n")]);
+        let mut client = CsaClient::connect(address).unwrap();
+        match client.login("bot", "wrong") {
+            Err(CsaError::LoginError(line)) => assert_eq!(line, "LOGIN:incorrect"),
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // a resumed game where we play second, and our player resigns
+        let summary = SUMMARY
+            .replace("Your_Turn:+", "Your_Turn:-")
+            .replace("+\nEND Position", "+\n+7776FU,T2\nEND Position");
+        let address = mock_server(&[
+            ("LOGIN bot secret", "LOGIN:bot OK\n"),
+            ("", &summary),
+            ("AGREE test-1", "START:test-1\n"),
+            ("%TORYO", "%TORYO,T0\n#RESIGN\n#LOSE\n"),
+        ]);
+        let mut client = CsaClient::connect(address).unwrap();
+        client.login("bot", "secret").unwrap();
+        let summary = client.wait_game().unwrap();
+        assert_eq!(summary.color, Color::Black);
+        assert_eq!(summary.game.moves(), ["P3c-3d"]);
+        client.agree(&summary).unwrap();
+        let outcome = client
+            .play(&summary, &mut |_: &Board| String::from("withdraw"))
+            .unwrap();
+        assert_eq!(outcome.result, CsaResult::Lose);
+        assert_eq!(outcome.kifu.game.moves().len(), 1);
+
+        // a player offering draws over and over ends up resigning
+        struct Drawish(usize);
+        impl Player for Drawish {
+            fn choose_move(&mut self, _game: &Game) -> PlayerAction {
+                self.0 += 1;
+                PlayerAction::OfferDraw
+            }
+        }
+        let address = mock_server(&[
+            ("LOGIN bot secret", "LOGIN:bot OK\n"),
+            ("", SUMMARY),
+            ("AGREE test-1", "START:test-1\n"),
+            ("%TORYO", "%TORYO,T0\n#RESIGN\n#LOSE\n"),
+        ]);
+        let mut client = CsaClient::connect(address).unwrap();
+        client.login("bot", "secret").unwrap();
+        let summary = client.wait_game().unwrap();
+        client.agree(&summary).unwrap();
+        let mut player = Drawish(0);
+        let outcome = client.play(&summary, &mut player).unwrap();
+        assert_eq!(outcome.result, CsaResult::Lose);
+        assert_eq!(player.0, 2);
+
+        // a position the board cannot hold is refused
+        let lines: Vec<String> = SUMMARY
+            .replace("P-\n", &format!("P-{}\n", "00FU".repeat(40)))
+            .lines()
+            .map(String::from)
+            .collect();
+        match GameSummary::parse(&lines) {
+            Err(CsaError::ProtocolError(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn checkmated() {
+        // the opponent drops a gold in front of our king, protected by a pawn
+        let summary = SUMMARY
+            .replace("To_Move:+", "To_Move:-")
+            .replace("PI\nP+\nP-\n+\n", "P+59OU\nP-51OU57FU00KI\n-\n");
+        let address = mock_server(&[
+            ("LOGIN bot secret", "LOGIN:bot OK\n"),
+            ("", &summary),
+            (
+                "AGREE test-1",
+                "START:test-1\n-0058KI,T1\n#CHECKMATE\n#LOSE\n",
+            ),
+        ]);
+        let mut client = CsaClient::connect(address).unwrap();
+        client.login("bot", "secret").unwrap();
+        let summary = client.wait_game().unwrap();
+        client.agree(&summary).unwrap();
+        let outcome = client
+            .play(&summary, &mut |_: &Board| -> String {
+                panic!("no move to choose")
+            })
+            .unwrap();
+        assert_eq!(outcome.result, CsaResult::Lose);
+        assert_eq!(outcome.reason.as_deref(), Some("CHECKMATE"));
+        assert_eq!(outcome.kifu.game.moves(), ["G*5b"]);
+        assert_eq!(
+            outcome.kifu.result,
+            Some(GameResult::loss(Color::White, Termination::Checkmate))
+        );
+    }
+}
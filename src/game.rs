@@ -0,0 +1,293 @@
+use crate::board::*;
+use crate::invalidmoveerror::*;
+use crate::piece::*;
+use std::fmt;
+
+/// why a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Termination {
+    Checkmate,
+    /// the first check of a checkshogi game (see WinCondition)
+    Check,
+    KingCapture,
+    /// a king reached the starting square of the opponent's king
+    Try,
+    /// the "withdraw" move, or a player giving up
+    Resignation,
+    DrawAgreement,
+    IllegalMove,
+    TimeForfeit,
+    /// sennichite: the same position occurred four times
+    Repetition,
+    /// the position was repeated by a player checking at every move, who loses
+    PerpetualCheck,
+    /// a player declared the win with their king in the opponent's camp (see
+    /// Board::can_declare_impasse)
+    Impasse,
+    MaxMoves,
+    /// the player left the game server (see server)
+    Disconnection,
+}
+
+impl From<WinCondition> for Termination {
+    fn from(condition: WinCondition) -> Self {
+        match condition {
+            WinCondition::Checkmate => Termination::Checkmate,
+            WinCondition::FirstCheck => Termination::Check,
+            WinCondition::KingCapture => Termination::KingCapture,
+            WinCondition::Try => Termination::Try,
+        }
+    }
+}
+
+/// how a game ended: the winner (None for a draw) and why
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GameResult {
+    pub winner: Option<Color>,
+    pub reason: Termination,
+}
+
+impl GameResult {
+    /// the game was won by the given player
+    pub fn win(winner: Color, reason: Termination) -> Self {
+        GameResult {
+            winner: Some(winner),
+            reason,
+        }
+    }
+
+    /// the game was lost by the given player
+    pub fn loss(loser: Color, reason: Termination) -> Self {
+        let mut winner = loser;
+        winner.invert();
+        GameResult::win(winner, reason)
+    }
+
+    /// the game was drawn
+    pub fn draw(reason: Termination) -> Self {
+        GameResult {
+            winner: None,
+            reason,
+        }
+    }
+}
+
+/// "White wins by Checkmate", "Draw by Repetition"
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.winner {
+            Some(winner) => write!(f, "{:?} wins by {:?}", winner, self.reason),
+            None => write!(f, "Draw by {:?}", self.reason),
+        }
+    }
+}
+
+/// A game: the starting board and every move played since, which allows undoing moves and
+/// detecting repetitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    boards: Vec<Board>,
+    moves: Vec<String>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+impl Game {
+    /// create a game from the regular starting position
+    pub fn new() -> Self {
+        Game::from_board(Board::new())
+    }
+
+    /// create a game from any board
+    pub fn from_board(b: Board) -> Self {
+        Game {
+            boards: vec![b],
+            moves: Vec::new(),
+        }
+    }
+
+    /// return the current board
+    pub fn board(&self) -> &Board {
+        self.boards.last().unwrap()
+    }
+
+    /// return the board the game started from
+    pub fn start(&self) -> &Board {
+        &self.boards[0]
+    }
+
+    /// return the moves played so far
+    pub fn moves(&self) -> &[String] {
+        &self.moves
+    }
+
+    /// return the last move played, if any
+    pub fn last_move(&self) -> Option<&str> {
+        self.moves.last().map(|mv| mv.as_str())
+    }
+
+    /// check the move, and play it if it is legal
+    pub fn play(&mut self, mv: &str) -> Result<(), InvalidMoveError> {
+        let next = self.board().try_play_move(mv)?;
+        self.boards.push(next);
+        self.moves.push(mv.to_string());
+        Ok(())
+    }
+
+    /// play a move without checking it (see Board::play_move_unchecked)
+    pub fn play_unchecked(&mut self, mv: &str) {
+        let next = self.board().play_move_unchecked(mv);
+        self.boards.push(next);
+        self.moves.push(mv.to_string());
+    }
+
+    /// take back the last move, and return it
+    pub fn undo(&mut self) -> Option<String> {
+        let mv = self.moves.pop()?;
+        self.boards.pop();
+        Some(mv)
+    }
+
+    /// return how many times the current position has occurred in the game (at least 1)
+    pub fn repetitions(&self) -> usize {
+        let key = self.board().hash_key();
+        self.boards.iter().filter(|b| b.hash_key() == key).count()
+    }
+
+    /// return the result of the game if the moves played so far ended it: a resignation, a
+    /// checkmate (or the other win conditions of the rules), or a fourth repetition
+    pub fn result(&self) -> Option<GameResult> {
+        let b = self.board();
+        if self.last_move() == Some("withdraw") {
+            // the player who withdrew keeps the turn
+            return Some(GameResult::loss(b.get_color(), Termination::Resignation));
+        }
+        if let Some(condition) = b.lost_by() {
+            return Some(GameResult::loss(b.get_color(), condition.into()));
+        }
+        if self.repetitions() >= 4 {
+            return Some(self.sennichite());
+        }
+        None
+    }
+
+    /// return the result of the repetition of the current position: a draw, unless all the
+    /// moves of a player since its first occurrence were checks (that player loses)
+    pub fn sennichite(&self) -> GameResult {
+        let key = self.board().hash_key();
+        let first = self
+            .boards
+            .iter()
+            .position(|b| b.hash_key() == key)
+            .unwrap();
+        for &color in &[Color::White, Color::Black] {
+            // whether the opponent was in check after each move of the player
+            let checks: Vec<bool> = (first..self.moves.len())
+                .filter(|&i| self.boards[i].get_color() == color)
+                .map(|i| self.boards[i + 1].is_check())
+                .collect();
+            if !checks.is_empty() && checks.iter().all(|&check| check) {
+                return GameResult::loss(color, Termination::PerpetualCheck);
+            }
+        }
+        GameResult::draw(Termination::Repetition)
+    }
+
+    /// the player to move declares the win by impasse: they win if the board allows it (see
+    /// Board::can_declare_impasse), else loses
+    pub fn declare_impasse(&self) -> GameResult {
+        let b = self.board();
+        if b.can_declare_impasse() {
+            GameResult::win(b.get_color(), Termination::Impasse)
+        } else {
+            GameResult::loss(b.get_color(), Termination::IllegalMove)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::game::*;
+
+    #[test]
+    fn play_and_undo() {
+        let mut g = Game::new();
+        assert!(g.play("P9c-9d").is_ok());
+        assert!(g.play("P9c-9d").is_err());
+        assert_eq!(g.moves(), ["P9c-9d"]);
+        assert_eq!(g.last_move(), Some("P9c-9d"));
+        assert_eq!(g.undo(), Some("P9c-9d".to_string()));
+        assert_eq!(g.board(), &Board::new());
+        assert_eq!(g.undo(), None);
+    }
+
+    #[test]
+    fn repetitions() {
+        let mut g = Game::new();
+        for mv in &["K5a-5b", "K5i-5h", "K5b-5a", "K5h-5i"] {
+            g.play(mv).unwrap();
+        }
+        assert_eq!(g.repetitions(), 2);
+        g.play("K5a-5b").unwrap();
+        assert_eq!(g.repetitions(), 2);
+    }
+
+    #[test]
+    fn results() {
+        let mut g = Game::new();
+        assert_eq!(g.result(), None);
+        g.play("withdraw").unwrap();
+        assert_eq!(
+            g.result(),
+            Some(GameResult::loss(Color::White, Termination::Resignation))
+        );
+        assert_eq!(g.result().unwrap().to_string(), "Black wins by Resignation");
+
+        let mut g = Game::new();
+        for _ in 0..3 {
+            for mv in &["K5a-5b", "K5i-5h", "K5b-5a", "K5h-5i"] {
+                g.play(mv).unwrap();
+            }
+        }
+        assert_eq!(g.result(), Some(GameResult::draw(Termination::Repetition)));
+    }
+
+    #[test]
+    fn perpetual_check() {
+        let mut g = Game::from_board(Board::from_sfen("8k/9/9/9/9/9/9/9/K6R1 b - 1").unwrap());
+        // the rook checks the king at every move
+        for _ in 0..3 {
+            for mv in &["R8a-9a", "K9i-8i", "R9a-8a", "K8i-9i"] {
+                assert_eq!(g.result(), None);
+                g.play(mv).unwrap();
+            }
+        }
+        assert_eq!(
+            g.result(),
+            Some(GameResult::loss(Color::White, Termination::PerpetualCheck))
+        );
+    }
+
+    #[test]
+    fn impasse() {
+        let g = Game::new();
+        assert_eq!(
+            g.declare_impasse(),
+            GameResult::loss(Color::White, Termination::IllegalMove)
+        );
+        // the king and 12 pieces in the opponent's camp, 20 points, and the reserve
+        let b = Board::from_sfen("4K4/1RB3G2/PPPPPPPPP/9/9/9/9/9/4k4 b 2G6P 1").unwrap();
+        assert!(b.can_declare_impasse());
+        assert_eq!(
+            Game::from_board(b).declare_impasse(),
+            GameResult::win(Color::White, Termination::Impasse)
+        );
+        // the first player needs 28 points, one more than the second one
+        let b = Board::from_sfen("4K4/1RB3G2/PPPPPPPPP/9/9/9/9/9/4k4 b 2G5P 1").unwrap();
+        assert!(!b.can_declare_impasse());
+    }
+}
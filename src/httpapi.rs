@@ -0,0 +1,744 @@
+use crate::ai;
+use crate::board::*;
+use crate::book::*;
+use crate::game::*;
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// deepest search allowed for the bots and the analysis, the others would take minutes
+pub const MAX_DEPTH: u32 = 4;
+
+/// largest body accepted in a request
+const MAX_BODY: usize = 64 * 1024;
+
+/// longest wait for the rest of a request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// an HTTP request, as far as the API is concerned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// path, with its query string ("/games/1/analysis?depth=3")
+    pub path: String,
+    pub body: String,
+}
+
+impl Request {
+    pub fn new(method: &str, path: &str, body: &str) -> Self {
+        Request {
+            method: method.to_string(),
+            path: path.to_string(),
+            body: body.to_string(),
+        }
+    }
+}
+
+/// the answer of the API: a status code and a JSON body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Response { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response {
+            status,
+            body: json!({ "error": message }),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    }
+}
+
+/// The games in progress, and the routes to play them:
+///
+/// - POST /games, with an optional {"sfen": "..."}: create a game
+/// - GET /games/ID: the state of a game (SFEN, moves played, legal moves, check...)
+/// - DELETE /games/ID: forget a game
+/// - POST /games/ID/moves, with {"move": "P7g-7f"}: play a move, in any notation Board::resolve_move
+///   or Board::from_kanji understand
+/// - POST /games/ID/undo: take back the last move
+/// - POST /games/ID/bot, with an optional {"bot": "greedy", "depth": 2}: let a bot (greedy, random
+///   or alphabeta) play the next move, from the opening book first if the API has one
+/// - GET /games/ID/analysis?depth=N: the best move and the expected continuation (ai::alphabeta)
+#[derive(Debug, Clone, Default)]
+pub struct Api {
+    games: HashMap<u64, Game>,
+    next_id: u64,
+    book: Option<Arc<Book>>,
+}
+
+/// a search asked by a request, run on a copy of the board so that the API is not held meanwhile
+struct Search {
+    id: u64,
+    board: Board,
+    depth: u32,
+    /// whether the best move is played (the alphabeta bot), else it is only reported
+    play: bool,
+    /// the book of the bot, if it plays
+    book: Option<Arc<Book>>,
+}
+
+impl Search {
+    fn run(&self) -> (i32, Vec<String>) {
+        match &self.book {
+            Some(book) => ai::alphabeta_with_book(&self.board, self.depth, book),
+            None => ai::alphabeta(&self.board, self.depth),
+        }
+    }
+}
+
+/// what a request needs: an answer, or a search first (see Api::finish)
+enum Prepared {
+    Done(Response),
+    Search(Box<Search>),
+}
+
+impl Api {
+    pub fn new() -> Self {
+        Api::default()
+    }
+
+    /// an API whose bots play from the opening book as long as the position is known
+    pub fn with_book(book: Book) -> Self {
+        Api {
+            book: Some(Arc::new(book)),
+            ..Api::default()
+        }
+    }
+
+    /// answer a request, the API being reachable without any HTTP server (see serve)
+    pub fn handle(&mut self, request: &Request) -> Response {
+        match self.prepare(request) {
+            Prepared::Done(response) => response,
+            Prepared::Search(search) => {
+                let result = search.run();
+                self.finish(search, result)
+            }
+        }
+    }
+
+    /// answer a request, unless it needs a search
+    fn prepare(&mut self, request: &Request) -> Prepared {
+        let (path, query) = match request.path.find('?') {
+            Some(i) => (&request.path[..i], &request.path[i + 1..]),
+            None => (request.path.as_str(), ""),
+        };
+        let body: Value = if request.body.trim().is_empty() {
+            json!({})
+        } else {
+            match serde_json::from_str(&request.body) {
+                Ok(body) => body,
+                Err(e) => {
+                    return Prepared::Done(Response::error(400, &format!("invalid JSON: {}", e)))
+                }
+            }
+        };
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let method = request.method.as_str();
+
+        let response = match segments.as_slice() {
+            ["games"] => match method {
+                "POST" => self.create(&body),
+                _ => Response::error(405, "use POST to create a game"),
+            },
+            ["games", id, rest @ ..] => {
+                let id: u64 = match id.parse() {
+                    Ok(id) if self.games.contains_key(&id) => id,
+                    _ => return Prepared::Done(Response::error(404, &format!("no game {}", id))),
+                };
+                match (method, rest) {
+                    ("GET", []) => Response::ok(self.state(id)),
+                    ("DELETE", []) => {
+                        self.games.remove(&id);
+                        Response {
+                            status: 204,
+                            body: Value::Null,
+                        }
+                    }
+                    ("POST", ["moves"]) => self.play(id, &body),
+                    ("POST", ["undo"]) => self.undo(id),
+                    ("POST", ["bot"]) => return self.bot(id, &body),
+                    ("GET", ["analysis"]) => return self.analyse(id, query),
+                    (_, []) | (_, ["moves"]) | (_, ["undo"]) | (_, ["bot"]) | (_, ["analysis"]) => {
+                        Response::error(405, "method not allowed on this route")
+                    }
+                    _ => Response::error(404, "no such route"),
+                }
+            }
+            _ => Response::error(404, "no such route"),
+        };
+        Prepared::Done(response)
+    }
+
+    /// answer a request once its search is over
+    fn finish(&mut self, search: Box<Search>, (score, pv): (i32, Vec<String>)) -> Response {
+        if !search.play {
+            // mate scores decrease by one per move played before the mate
+            let mate = if score.abs() > ai::MATE / 2 {
+                Some(((ai::MATE - score.abs() + 1) / 2) * score.signum())
+            } else {
+                None
+            };
+            return Response::ok(json!({
+                "depth": search.depth,
+                "score": score,
+                "mate": mate,
+                "best_move": pv.first(),
+                "pv": pv,
+            }));
+        }
+        // the game may have been played on, or deleted, during the search
+        match (self.games.get(&search.id), pv.first()) {
+            (Some(game), Some(mv)) if *game.board() == search.board => {
+                let mv = mv.clone();
+                self.played(search.id, mv)
+            }
+            _ => Response::error(409, "the game changed during the search"),
+        }
+    }
+
+    fn create(&mut self, body: &Value) -> Response {
+        let board = match body.get("sfen").and_then(Value::as_str) {
+            None | Some("startpos") => Board::new(),
+            Some(sfen) => match Board::from_sfen(sfen) {
+                Ok(board) => board,
+                Err(e) => return Response::error(400, &format!("invalid sfen: {}", e)),
+            },
+        };
+        self.next_id += 1;
+        self.games.insert(self.next_id, Game::from_board(board));
+        Response {
+            status: 201,
+            body: self.state(self.next_id),
+        }
+    }
+
+    fn state(&self, id: u64) -> Value {
+        let game = &self.games[&id];
+        let b = game.board();
+        let result = game.result();
+        let legal_moves: Vec<String> = match result {
+            Some(_) => vec![],
+            None => b.iter_moves().collect(),
+        };
+        json!({
+            "id": id,
+            "sfen": b.to_sfen(game.moves().len() as u32 + 1),
+            "turn": format!("{:?}", b.get_color()),
+            "moves": game.moves(),
+            "legal_moves": legal_moves,
+            "check": b.is_check(),
+            "game_over": result.is_some(),
+            "result": result.map(|result| json!({
+                "winner": result.winner.map(|color| format!("{:?}", color)),
+                "reason": format!("{:?}", result.reason),
+            })),
+        })
+    }
+
+    ///the state of the game after a move, with the move played
+    fn played(&mut self, id: u64, mv: String) -> Response {
+        self.games.get_mut(&id).unwrap().play_unchecked(&mv);
+        let mut state = self.state(id);
+        state["move"] = json!(mv);
+        Response::ok(state)
+    }
+
+    fn play(&mut self, id: u64, body: &Value) -> Response {
+        let mv = match body.get("move").and_then(Value::as_str) {
+            Some(mv) => mv.trim(),
+            None => return Response::error(400, "expected {\"move\": \"...\"}"),
+        };
+        let game = &self.games[&id];
+        if game.result().is_some() {
+            return Response::error(409, "the game is over");
+        }
+        // a withdraw is a resignation (see Game::result), taking back the game is not allowed
+        if mv == "restart" {
+            return Response::error(422, "the game cannot be restarted");
+        }
+        let b = game.board();
+        match b.resolve_move(mv) {
+            Ok(mv) => self.played(id, mv),
+            Err(error) => match b.from_kanji(mv, game.last_move()) {
+                Ok(mv) => self.played(id, mv),
+                Err(_) => {
+                    let explanation = b.explain_illegal(mv);
+                    let suggestions = explanation.map_or(vec![], |e| e.suggestions);
+                    Response {
+                        status: 422,
+                        body: json!({
+                            "error": error.to_string(),
+                            "invalid_move": error,
+                            "suggestions": suggestions,
+                        }),
+                    }
+                }
+            },
+        }
+    }
+
+    fn undo(&mut self, id: u64) -> Response {
+        match self.games.get_mut(&id).unwrap().undo() {
+            Some(mv) => {
+                let mut state = self.state(id);
+                state["undone"] = json!(mv);
+                Response::ok(state)
+            }
+            None => Response::error(409, "no move to undo"),
+        }
+    }
+
+    fn bot(&mut self, id: u64, body: &Value) -> Prepared {
+        let depth = match search_depth(body.get("depth").and_then(Value::as_u64)) {
+            Ok(depth) => depth,
+            Err(response) => return Prepared::Done(response),
+        };
+        let game = &self.games[&id];
+        if game.result().is_some() {
+            return Prepared::Done(Response::error(409, "the game is over"));
+        }
+        let b = game.board();
+        let book_move = || self.book.as_ref().and_then(|book| book.pick(b));
+        let mv = match body.get("bot").and_then(Value::as_str).unwrap_or("greedy") {
+            "greedy" => book_move().unwrap_or_else(|| ai::greedy(b)),
+            "random" => book_move().unwrap_or_else(|| ai::random(b)),
+            "alphabeta" => {
+                return Prepared::Search(Box::new(Search {
+                    id,
+                    board: b.clone(),
+                    depth,
+                    play: true,
+                    book: self.book.clone(),
+                }))
+            }
+            bot => {
+                return Prepared::Done(Response::error(
+                    400,
+                    &format!("unknown bot {} (choose greedy, random or alphabeta)", bot),
+                ))
+            }
+        };
+        Prepared::Done(self.played(id, mv))
+    }
+
+    fn analyse(&self, id: u64, query: &str) -> Prepared {
+        let requested = match query
+            .split('&')
+            .filter_map(|pair| pair.strip_prefix("depth="))
+            .next_back()
+        {
+            Some(depth) => match depth.parse() {
+                Ok(depth) => Some(depth),
+                Err(_) => {
+                    return Prepared::Done(Response::error(400, "the depth must be a number"))
+                }
+            },
+            None => None,
+        };
+        match search_depth(requested) {
+            Ok(depth) => Prepared::Search(Box::new(Search {
+                id,
+                board: self.games[&id].board().clone(),
+                depth,
+                play: false,
+                book: None,
+            })),
+            Err(response) => Prepared::Done(response),
+        }
+    }
+}
+
+///the search depth asked for, 2 by default
+fn search_depth(requested: Option<u64>) -> Result<u32, Response> {
+    match requested.unwrap_or(2) {
+        depth if (1..=MAX_DEPTH as u64).contains(&depth) => Ok(depth as u32),
+        _ => Err(Response::error(
+            400,
+            &format!("the depth must be between 1 and {}", MAX_DEPTH),
+        )),
+    }
+}
+
+/// read a request from a connection, None if the client closed it
+pub fn read_request<R: BufRead>(reader: &mut R) -> io::Result<Option<Request>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut words = line.split_whitespace();
+    let (method, path) = match (words.next(), words.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid request line",
+            ))
+        }
+    };
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid content length")
+                })?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "request body too large",
+        ));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8(body)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "body is not UTF-8"))?;
+    Ok(Some(Request { method, path, body }))
+}
+
+/// write the response, allowing any origin so that web pages can call the API
+pub fn write_response<W: Write>(writer: &mut W, response: &Response) -> io::Result<()> {
+    let body = if response.body.is_null() {
+        String::new()
+    } else {
+        response.body.to_string()
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, POST, DELETE, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+///the API, even if a request panicked while holding it
+fn lock(api: &Mutex<Api>) -> MutexGuard<'_, Api> {
+    api.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+///answer a request, the searches running without holding the API so that the other games go on
+fn answer(api: &Mutex<Api>, request: &Request) -> Response {
+    let answered = panic::catch_unwind(AssertUnwindSafe(|| {
+        let prepared = lock(api).prepare(request);
+        match prepared {
+            Prepared::Done(response) => response,
+            Prepared::Search(search) => {
+                let result = search.run();
+                lock(api).finish(search, result)
+            }
+        }
+    }));
+    answered.unwrap_or_else(|_| Response::error(500, "internal error"))
+}
+
+fn connection(stream: TcpStream, api: &Mutex<Api>, timeout: Duration) -> io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut stream = stream;
+    let response = match read_request(&mut reader) {
+        Ok(None) => return Ok(()),
+        // preflight requests of the browsers
+        Ok(Some(request)) if request.method == "OPTIONS" => Response {
+            status: 204,
+            body: Value::Null,
+        },
+        Ok(Some(request)) => answer(api, &request),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            let status = if e.to_string().contains("too large") {
+                413
+            } else {
+                400
+            };
+            Response::error(status, &e.to_string())
+        }
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ) =>
+        {
+            Response::error(408, "the request was not received in time")
+        }
+        Err(e) => return Err(e),
+    };
+    write_response(&mut stream, &response)
+}
+
+/// Answer the connections of the listener, one request per connection, until it fails
+pub fn serve(listener: TcpListener, api: Arc<Mutex<Api>>) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let api = Arc::clone(&api);
+        thread::spawn(move || {
+            if let Err(e) = connection(stream, &api, READ_TIMEOUT) {
+                eprintln!("connection error: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::httpapi::*;
+    use std::io::Read;
+
+    /// in-process client: the requests go straight to the API
+    struct Client(Api);
+
+    impl Client {
+        fn send(&mut self, method: &str, path: &str, body: Value) -> Response {
+            let body = if body.is_null() {
+                String::new()
+            } else {
+                body.to_string()
+            };
+            self.0.handle(&Request::new(method, path, &body))
+        }
+    }
+
+    #[test]
+    fn play_a_game() {
+        let mut client = Client(Api::new());
+        let created = client.send("POST", "/games", Value::Null);
+        assert_eq!(created.status, 201);
+        assert_eq!(created.body["id"], 1);
+        assert_eq!(created.body["turn"], "White");
+        assert_eq!(created.body["legal_moves"].as_array().unwrap().len(), 30);
+
+        let played = client.send("POST", "/games/1/moves", json!({"move": "P-3d"}));
+        assert_eq!(played.status, 200);
+        assert_eq!(played.body["move"], "P3c-3d");
+        assert_eq!(played.body["moves"], json!(["P3c-3d"]));
+        assert_eq!(played.body["turn"], "Black");
+
+        let illegal = client.send("POST", "/games/1/moves", json!({"move": "P7g-7e"}));
+        assert_eq!(illegal.status, 422);
+        assert!(illegal.body["invalid_move"].is_object());
+
+        let bot = client.send(
+            "POST",
+            "/games/1/bot",
+            json!({"bot": "alphabeta", "depth": 1}),
+        );
+        assert_eq!(bot.status, 200);
+        assert_eq!(bot.body["moves"].as_array().unwrap().len(), 2);
+
+        let undone = client.send("POST", "/games/1/undo", Value::Null);
+        assert_eq!(undone.body["moves"], json!(["P3c-3d"]));
+        client.send("POST", "/games/1/undo", Value::Null);
+        assert_eq!(
+            client.send("POST", "/games/1/undo", Value::Null).status,
+            409
+        );
+
+        assert_eq!(client.send("DELETE", "/games/1", Value::Null).status, 204);
+        assert_eq!(client.send("GET", "/games/1", Value::Null).status, 404);
+        assert_eq!(client.send("GET", "/games", Value::Null).status, 405);
+        assert_eq!(
+            client
+                .send("POST", "/games", json!({"sfen": "9/9 b -"}))
+                .status,
+            400
+        );
+        assert_eq!(
+            client
+                .send(
+                    "POST",
+                    "/games",
+                    json!({"sfen": "9/9/9/9/9/9/9/9/9 b 40P 1"})
+                )
+                .status,
+            400
+        );
+    }
+
+    #[test]
+    fn bots_with_a_book() {
+        let mut book = Book::new();
+        book.add_game(&["P9c-9d", "P1g-1f", "P1c-1d"], 10);
+        let mut client = Client(Api::with_book(book));
+        client.send("POST", "/games", Value::Null);
+        for (bot, mv) in &[
+            ("alphabeta", "P9c-9d"),
+            ("random", "P1g-1f"),
+            ("greedy", "P1c-1d"),
+        ] {
+            let played = client.send("POST", "/games/1/bot", json!({ "bot": bot, "depth": 1 }));
+            assert_eq!(played.body["move"], *mv);
+        }
+    }
+
+    #[test]
+    fn searches_without_the_lock() {
+        let api = Mutex::new(Api::new());
+        answer(&api, &Request::new("POST", "/games", ""));
+        let bot = Request::new(
+            "POST",
+            "/games/1/bot",
+            "{\"bot\": \"alphabeta\", \"depth\": 1}",
+        );
+        let search = match lock(&api).prepare(&bot) {
+            Prepared::Search(search) => search,
+            Prepared::Done(response) => panic!("unexpected {:?}", response),
+        };
+        // the API is free during the search, and the game may go on meanwhile
+        let moved = answer(
+            &api,
+            &Request::new("POST", "/games/1/moves", "{\"move\": \"P-7d\"}"),
+        );
+        assert_eq!(moved.status, 200);
+        let result = search.run();
+        assert_eq!(lock(&api).finish(search, result).status, 409);
+        assert_eq!(answer(&api, &bot).status, 200);
+
+        // a request panicking while holding the API does not take it down
+        let api = Arc::new(api);
+        let poisoner = Arc::clone(&api);
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poisoned");
+        })
+        .join();
+        assert!(api.is_poisoned());
+        let state = answer(&api, &Request::new("GET", "/games/1", ""));
+        assert_eq!(state.status, 200);
+        assert_eq!(state.body["moves"].as_array().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn incomplete_request() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        // the body announced never comes
+        write!(
+            client,
+            "POST /games HTTP/1.1\r\nContent-Length: 100\r\n\r\n{{}}"
+        )
+        .unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let api = Mutex::new(Api::new());
+        connection(stream, &api, Duration::from_millis(100)).unwrap();
+        let mut answer = String::new();
+        client.read_to_string(&mut answer).unwrap();
+        assert!(answer.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    }
+
+    #[test]
+    fn analysis() {
+        let mut client = Client(Api::new());
+        let sfen = "4k4/9/4P4/9/9/9/9/9/4K4 b G 1";
+        assert_eq!(
+            client
+                .send("POST", "/games", json!({ "sfen": sfen }))
+                .status,
+            201
+        );
+        let analysis = client.send("GET", "/games/1/analysis?depth=2", Value::Null);
+        assert_eq!(analysis.status, 200);
+        assert_eq!(analysis.body["best_move"], "G*5h");
+        assert_eq!(analysis.body["mate"], 1);
+        let too_deep = client.send("GET", "/games/1/analysis?depth=9", Value::Null);
+        assert_eq!(too_deep.status, 400);
+
+        client.send("POST", "/games/1/moves", json!({"move": "G*5h"}));
+        let state = client.send("GET", "/games/1", Value::Null);
+        assert_eq!(state.body["game_over"], true);
+        assert_eq!(
+            state.body["result"],
+            json!({"winner": "White", "reason": "Checkmate"})
+        );
+        assert_eq!(state.body["check"], true);
+        assert_eq!(client.send("POST", "/games/1/bot", Value::Null).status, 409);
+    }
+
+    #[test]
+    fn special_moves() {
+        let mut client = Client(Api::new());
+        client.send("POST", "/games", Value::Null);
+        let restart = client.send("POST", "/games/1/moves", json!({"move": "restart"}));
+        assert_eq!(restart.status, 422);
+        assert_eq!(restart.body["error"], "the game cannot be restarted");
+
+        let withdraw = client.send("POST", "/games/1/moves", json!({"move": "withdraw"}));
+        assert_eq!(withdraw.status, 200);
+        assert_eq!(withdraw.body["game_over"], true);
+        assert_eq!(
+            withdraw.body["result"],
+            json!({"winner": "Black", "reason": "Resignation"})
+        );
+        assert_eq!(
+            client
+                .send("POST", "/games/1/moves", json!({"move": "P-7f"}))
+                .status,
+            409
+        );
+        assert_eq!(client.send("POST", "/games/1/bot", Value::Null).status, 409);
+    }
+
+    #[test]
+    fn over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, Arc::new(Mutex::new(Api::new()))));
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        let body = "{\"sfen\": \"startpos\"}";
+        write!(
+            stream,
+            "POST /games HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer).unwrap();
+        assert!(answer.starts_with("HTTP/1.1 201 Created\r\n"));
+        let (_, body) = answer.split_once("\r\n\r\n").unwrap();
+        let state: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(state["id"], 1);
+    }
+}
@@ -5,36 +5,191 @@ use crate::position::*;
 use std::error;
 use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InvalidMoveError {
-    MoveSyntaxError,
-    OutOfBoardMoveError,
-    DestinationOccupiedError,
-    NoPieceAtPositionError,
-    NoPieceCapturedError,
-    PieceHasNoSuchMoveError,
-    NifuViolationError,
-    NoMovePossibleAfterDropError,
-    PromotionError,
-    UncoverCheckError,
-    CheckmateByPawnDropError,
+    /// the move as it was given
+    MoveSyntaxError(String),
+    /// the square (as it was written) that is not on the board
+    OutOfBoardMoveError(String),
+    DestinationOccupiedError {
+        square: Position,
+        found: Piece,
+    },
+    NoPieceAtPositionError {
+        /// None for a drop: there was no such piece in the reserve
+        square: Option<Position>,
+        /// what is at the square instead, if anything
+        found: Option<Piece>,
+    },
+    NoPieceCapturedError {
+        square: Position,
+    },
+    PieceHasNoSuchMoveError {
+        piece: Piece,
+        destination: Position,
+        /// the square of the piece standing in the way, if the move is only blocked
+        blocked_at: Option<Position>,
+    },
+    NifuViolationError {
+        /// column (1 to 9) already containing a pawn
+        column: u16,
+    },
+    NoMovePossibleAfterDropError {
+        piecetype: PieceType,
+        square: Position,
+    },
+    PromotionError {
+        piecetype: PieceType,
+        destination: Position,
+        /// true if the promotion was mandatory but not requested, false if it was impossible
+        mandatory: bool,
+    },
+    UncoverCheckError {
+        /// the opponent piece that could take the king after the move
+        checking_piece: Piece,
+    },
+    CheckmateByPawnDropError {
+        square: Position,
+    },
+    /// the variant of the board does not allow drops
+    DropError {
+        piecetype: PieceType,
+    },
+    /// the move (written without its origin) could be played by several pieces, these moves
+    AmbiguousMoveError(Vec<String>),
 }
+
+///describe a piece with words, unlike its Display (which is made for the board)
+fn describe(piece: &Piece) -> String {
+    format!(
+        "{:?} {}{:?}",
+        piece.color,
+        if piece.promoted { "promoted " } else { "" },
+        piece.piecetype
+    )
+}
+
 #[allow(non_snake_case)]
 #[allow(unused_variables)]
 impl fmt::Display for InvalidMoveError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            InvalidMoveError::MoveSyntaxError => write!(f,"Move has an incorrect syntax"),
-            InvalidMoveError::OutOfBoardMoveError => write!(f,"The move uses squares outside of the board"),
-            InvalidMoveError::DestinationOccupiedError => write!(f,"The destination square is occupied"),
-            InvalidMoveError::NoPieceAtPositionError => write!(f,"No (such) piece was found at the start location"),
-            InvalidMoveError::NoPieceCapturedError => write!(f,"Capture was indicated but no piece was captured"),
-            InvalidMoveError::PieceHasNoSuchMoveError => write!(f,"The piece cannot move in such a way"),
-            InvalidMoveError::NifuViolationError => write!(f,"A pawn was dropped in a column already occupied by a non-promoted pawn"),
-            InvalidMoveError::NoMovePossibleAfterDropError => write!(f,"The piece was dropped in a position but will never be able to move afterwards"),
-            InvalidMoveError::PromotionError => write!(f,"The promotion of the piece is mandatory or impossible at this position but move do not provide the correct instruction"),
-            InvalidMoveError::UncoverCheckError => write!(f,"The king cannot be left in check with the current rules"),
-            InvalidMoveError::CheckmateByPawnDropError => write!(f,"A checkmate cannot be given by dropping a pawn")
+            InvalidMoveError::MoveSyntaxError(mv) => {
+                write!(f, "Move {} has an incorrect syntax", mv)
+            }
+            InvalidMoveError::OutOfBoardMoveError(square) => write!(
+                f,
+                "The move uses the square {}, outside of the board",
+                square
+            ),
+            InvalidMoveError::DestinationOccupiedError { square, found } => write!(
+                f,
+                "The destination square {} is occupied by a {}",
+                square.to_string(),
+                describe(found)
+            ),
+            InvalidMoveError::NoPieceAtPositionError { square: None, .. } => {
+                write!(f, "No such piece was found (in the reserve, for a drop)")
+            }
+            InvalidMoveError::NoPieceAtPositionError {
+                square: Some(square),
+                found: None,
+            } => write!(
+                f,
+                "No piece was found at the start location {}",
+                square.to_string()
+            ),
+            InvalidMoveError::NoPieceAtPositionError {
+                square: Some(square),
+                found: Some(found),
+            } => write!(
+                f,
+                "No such piece was found at the start location {}, but a {}",
+                square.to_string(),
+                describe(found)
+            ),
+            InvalidMoveError::NoPieceCapturedError { square } => write!(
+                f,
+                "Capture was indicated but no piece was captured at {}",
+                square.to_string()
+            ),
+            InvalidMoveError::PieceHasNoSuchMoveError {
+                piece,
+                destination,
+                blocked_at: None,
+            } => write!(
+                f,
+                "The {} cannot move to {}",
+                describe(piece),
+                destination.to_string()
+            ),
+            InvalidMoveError::PieceHasNoSuchMoveError {
+                piece,
+                destination,
+                blocked_at: Some(blocked_at),
+            } => write!(
+                f,
+                "The {} cannot move to {}, the way is blocked at {}",
+                describe(piece),
+                destination.to_string(),
+                blocked_at.to_string()
+            ),
+            InvalidMoveError::NifuViolationError { column } => write!(
+                f,
+                "A pawn was dropped in column {}, already occupied by a non-promoted pawn",
+                column
+            ),
+            InvalidMoveError::NoMovePossibleAfterDropError { piecetype, square } => write!(
+                f,
+                "The {:?} was dropped in {} but will never be able to move afterwards",
+                piecetype,
+                square.to_string()
+            ),
+            InvalidMoveError::PromotionError {
+                piecetype,
+                destination,
+                mandatory: true,
+            } => write!(
+                f,
+                "The promotion of the {:?} is mandatory at {} but was not requested (add a '+')",
+                piecetype,
+                destination.to_string()
+            ),
+            InvalidMoveError::PromotionError {
+                piecetype,
+                destination,
+                mandatory: false,
+            } => write!(
+                f,
+                "The {:?} cannot be promoted at {}",
+                piecetype,
+                destination.to_string()
+            ),
+            InvalidMoveError::UncoverCheckError { checking_piece } => write!(
+                f,
+                "The king cannot be left in check with the current rules (by the {} in {})",
+                describe(checking_piece),
+                checking_piece
+                    .position
+                    .map(|p| p.to_string())
+                    .unwrap_or_default()
+            ),
+            InvalidMoveError::CheckmateByPawnDropError { square } => write!(
+                f,
+                "A checkmate cannot be given by dropping a pawn (in {})",
+                square.to_string()
+            ),
+            InvalidMoveError::DropError { piecetype } => write!(
+                f,
+                "The {:?} cannot be dropped, captured pieces stay in the reserve in this variant",
+                piecetype
+            ),
+            InvalidMoveError::AmbiguousMoveError(candidates) => write!(
+                f,
+                "The move is ambiguous, write its origin: {}",
+                candidates.join(" or ")
+            ),
         }
     }
 }
@@ -47,6 +202,38 @@ impl error::Error for InvalidMoveError {
     }
 }
 
+/// Why a move is illegal, and the legal moves closest to it (see Board::explain_illegal)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IllegalMoveExplanation {
+    pub error: InvalidMoveError,
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for IllegalMoveExplanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if !self.suggestions.is_empty() {
+            write!(f, "\nDid you mean: {}?", self.suggestions.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+///number of characters to insert, remove or replace to go from one string to the other
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
 fn maybe_drop(mv: &str) -> bool {
     if mv.len() == 4 && mv.as_bytes()[1] == b'*' {
         true
@@ -68,7 +255,7 @@ fn maybe_normal_move(mv: &str) -> bool {
 /// check if move is syntaxically correct
 pub fn check_syntax(mv: &str) -> Result<&str, InvalidMoveError> {
     if !(maybe_drop(mv) || maybe_normal_move(mv)) {
-        return Err(InvalidMoveError::MoveSyntaxError);
+        return Err(InvalidMoveError::MoveSyntaxError(mv.to_string()));
     }
 
     let first_char = mv.chars().next().unwrap();
@@ -81,33 +268,41 @@ pub fn check_syntax(mv: &str) -> Result<&str, InvalidMoveError> {
         && first_char != 'N'
         && first_char != 'L'
     {
-        return Err(InvalidMoveError::MoveSyntaxError);
+        return Err(InvalidMoveError::MoveSyntaxError(mv.to_string()));
     }
 
     return Ok(mv);
 }
 
 /// Check if all the squares invloved fit into the shogiban
-pub fn check_in_board(mv: &str) -> Result<&str, InvalidMoveError> {
-    let mut in_board = true;
-    if maybe_drop(mv) {
-        in_board &= mv.as_bytes()[2] as char >= '1' && mv.as_bytes()[2] as char <= '9';
-        in_board &= mv.as_bytes()[3] as char >= 'a' && mv.as_bytes()[3] as char <= 'i';
-    }
-
-    if maybe_normal_move(mv) {
-        in_board &= mv.as_bytes()[1] as char >= '1' && mv.as_bytes()[1] as char <= '9';
-        in_board &= mv.as_bytes()[2] as char >= 'a' && mv.as_bytes()[2] as char <= 'i';
+pub fn check_in_board<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, InvalidMoveError> {
+    let size = b.rules.variant.size as u8;
+    let bytes = mv.as_bytes();
+    let squares = if maybe_drop(mv) {
+        vec![&bytes[2..4]]
+    } else {
+        vec![&bytes[1..3], &bytes[4..6]]
+    };
 
-        in_board &= mv.as_bytes()[4] as char >= '1' && mv.as_bytes()[4] as char <= '9';
-        in_board &= mv.as_bytes()[5] as char >= 'a' && mv.as_bytes()[5] as char <= 'i';
+    for square in squares {
+        if !(b'1'..b'1' + size).contains(&square[0]) || !(b'a'..b'a' + size).contains(&square[1]) {
+            return Err(InvalidMoveError::OutOfBoardMoveError(
+                String::from_utf8_lossy(square).to_string(),
+            ));
+        }
     }
+    Ok(mv)
+}
 
-    if in_board {
-        Ok(mv)
-    } else {
-        Err(InvalidMoveError::OutOfBoardMoveError)
+///check if the move is not a drop, when the variant does not allow them
+pub fn check_drop_allowed<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, InvalidMoveError> {
+    if b.rules.variant.drops || !maybe_drop(mv) {
+        return Ok(mv);
     }
+    let full_move: Movement = mv.parse().unwrap();
+    Err(InvalidMoveError::DropError {
+        piecetype: full_move.piecetype,
+    })
 }
 
 ///check if destination is not occupied (or occupied by opponent)
@@ -118,11 +313,14 @@ pub fn check_destination<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, Inval
 
     if full_move.start == None {
         //the move is a drop
-        if None == b.is_occupied_by(destination) {
+        if let Some(found) = b.is_occupied_by(destination) {
+            return Err(InvalidMoveError::DestinationOccupiedError {
+                square: destination,
+                found,
+            });
+        } else {
             //ok the destination is empty
             return Ok(mv);
-        } else {
-            return Err(InvalidMoveError::DestinationOccupiedError);
         }
     } else {
         //the move is a normal move
@@ -134,12 +332,17 @@ pub fn check_destination<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, Inval
                     return Ok(mv);
                 }
             }
-            return Err(InvalidMoveError::NoPieceCapturedError);
+            return Err(InvalidMoveError::NoPieceCapturedError {
+                square: destination,
+            });
         } else {
             //check if there is not one's own piece already there
             if let Some(p) = b.is_occupied_by(destination) {
                 if p.color == current_player_color {
-                    return Err(InvalidMoveError::DestinationOccupiedError);
+                    return Err(InvalidMoveError::DestinationOccupiedError {
+                        square: destination,
+                        found: p,
+                    });
                 }
             }
             return Ok(mv);
@@ -159,19 +362,15 @@ pub fn check_start<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, InvalidMove
             == None
     {
         //no such piece at given start position
-        return Err(InvalidMoveError::NoPieceAtPositionError);
+        return Err(InvalidMoveError::NoPieceAtPositionError {
+            square: full_move.start,
+            found: full_move.start.and_then(|start| b.is_occupied_by(start)),
+        });
     }
 
     return Ok(mv);
 }
 
-///the move only moves the piece from/to squares next to each other, no need to check path
-fn small_move(start: Position, end: Position) -> bool {
-    let x = start.row() as i32 - end.row() as i32;
-    let y = start.column() as i32 - end.column() as i32;
-    return x.abs() <= 1 && y.abs() <= 1;
-}
-
 ///check if the piece is allowed to move in such a way, according to its type/promotion
 pub fn check_possible_move<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, InvalidMoveError> {
     if maybe_drop(mv) {
@@ -181,91 +380,56 @@ pub fn check_possible_move<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, Inv
     let full_move: Movement = mv.parse().unwrap();
     let start = full_move.start.unwrap();
     let piece = b.is_occupied_by(full_move.start.unwrap()).unwrap();
-    if !piece.get_relative_moves().into_iter().any(|relative_move| {
-        (relative_move.0 as i32, relative_move.1 as i32)
-            == (
-                (full_move.end.0 as i32 % 9 - start.0 as i32 % 9),
-                (full_move.end.0 as i32 / 9 - start.0 as i32 / 9),
-            )
-    }) {
-        return Err(InvalidMoveError::PieceHasNoSuchMoveError);
-    }
-    if full_move.piecetype == PieceType::Rook
-        && !small_move(start, full_move.end)
-        && !check_rook_path(start, full_move.end, b.clone())
+    let no_such_move = |blocked_at| {
+        Err(InvalidMoveError::PieceHasNoSuchMoveError {
+            piece,
+            destination: full_move.end,
+            blocked_at,
+        })
+    };
+    let relative = (
+        (full_move.end.0 % 9) as i16 - (start.0 % 9) as i16,
+        (full_move.end.0 / 9) as i16 - (start.0 / 9) as i16,
+    );
+    if !piece
+        .get_relative_moves(b.rules.variant)
+        .contains(&relative)
     {
-        return Err(InvalidMoveError::PieceHasNoSuchMoveError);
-    }
-    if full_move.piecetype == PieceType::Bishop
-        && !small_move(start, full_move.end)
-        && !check_bishop_path(start, full_move.end, b.clone())
+        return no_such_move(None);
+    }
+    //steps jump to their destination, only slides have to check their path
+    let sign = if piece.color == Color::White { 1 } else { -1 };
+    let moves = b
+        .rules
+        .variant
+        .piece_moves(piece.piecetype, piece.promoted)
+        .unwrap_or(b.rules.variant.moves[piece.piecetype as usize]);
+    if moves
+        .steps
+        .contains(&(sign * relative.0, sign * relative.1))
     {
-        return Err(InvalidMoveError::PieceHasNoSuchMoveError);
+        return Ok(mv);
     }
-    if full_move.piecetype == PieceType::Lance
-        && !small_move(start, full_move.end)
-        && !check_lance_path(start, full_move.end, b.clone())
-    {
-        return Err(InvalidMoveError::PieceHasNoSuchMoveError);
+    if let Some(blocked_at) = check_path(start, full_move.end, b) {
+        return no_such_move(Some(blocked_at));
     }
     return Ok(mv);
 }
 
-///return true if the path is clear, false if a piece is blocking the way
-fn check_bishop_path(start: Position, end: Position, b: Board) -> bool {
-    let direction = if (end.row() as u8 as i16 - start.row() as u8 as i16) > 0 {
-        if (end.column() as u8 as i16 - start.column() as u8 as i16) > 0 {
-            10
-        } else {
-            8
-        }
-    } else {
-        if (end.column() as u8 as i16 - start.column() as u8 as i16) > 0 {
-            -8
-        } else {
-            -10
-        }
-    };
-
-    let mut counter = start.0 as i32 + direction;
-    while counter != end.0 as i32 {
-        if !(None == b.is_occupied_by(Position(counter as u16))) {
-            return false;
-        }
-        counter += direction;
-    }
-    return true;
-}
-///return true if the path is clear, false if a piece is blocking the way
-fn check_rook_path(start: Position, end: Position, b: Board) -> bool {
-    let direction;
-    if start.column() == end.column() {
-        //vertical move
-        direction = if end.0 > start.0 { 9 } else { -9 };
-    } else {
-        //horizontal move
-        direction = if end.0 > start.0 { 1 } else { -1 };
-    }
-    let mut counter = start.0 as i32 + direction;
-    while counter != end.0 as i32 {
-        if !(None == b.is_occupied_by(Position(counter as u16))) {
-            return false;
-        }
-        counter += direction;
-    }
-    return true;
-}
-///return true if the path is clear, false if a piece is blocking the way
-fn check_lance_path(start: Position, end: Position, b: Board) -> bool {
-    let direction = if end.0 > start.0 { 9 } else { -9 };
+///return None if the path of a sliding piece is clear, else the square of the first piece
+///blocking the way
+pub(crate) fn check_path(start: Position, end: Position, b: &Board) -> Option<Position> {
+    let column = ((end.0 % 9) as i32 - (start.0 % 9) as i32).signum();
+    let row = ((end.0 / 9) as i32 - (start.0 / 9) as i32).signum();
+    let direction = column + 9 * row;
     let mut counter = start.0 as i32 + direction;
     while counter != end.0 as i32 {
         if !(None == b.is_occupied_by(Position(counter as u16))) {
-            return false;
+            return Some(Position(counter as u16));
         }
         counter += direction;
     }
-    return true;
+    return None;
 }
 
 /// check if the nifu rule is respected
@@ -281,7 +445,9 @@ pub fn check_nifu<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, InvalidMoveE
         .find(|p| p.position.unwrap().0 % 9 == full_move.end.0 % 9)
     {
         //two pawn on same column
-        return Err(InvalidMoveError::NifuViolationError);
+        return Err(InvalidMoveError::NifuViolationError {
+            column: full_move.end.0 % 9 + 1,
+        });
     }
     return Ok(mv);
 }
@@ -296,85 +462,77 @@ pub fn check_move_possible_after_drop<'a>(
         return Ok(mv);
     }
 
-    let last_row;
-    let before_last_row;
-    if b.get_color() == Color::White {
-        last_row = 'i';
-        before_last_row = 'h';
-    } else {
-        last_row = 'a';
-        before_last_row = 'b';
-    }
     let full_move: Movement = mv.parse().unwrap();
+    if stuck(full_move.piecetype, full_move.end, b) {
+        return Err(InvalidMoveError::NoMovePossibleAfterDropError {
+            piecetype: full_move.piecetype,
+            square: full_move.end,
+        });
+    }
+    return Ok(mv);
+}
 
-    if full_move.piecetype == PieceType::Pawn && full_move.end.row() == last_row {
-        return Err(InvalidMoveError::NoMovePossibleAfterDropError);
-    } else if full_move.piecetype == PieceType::Lance && full_move.end.row() == last_row {
-        return Err(InvalidMoveError::NoMovePossibleAfterDropError);
-    } else if full_move.piecetype == PieceType::Knight
-        && (full_move.end.row() == last_row || full_move.end.row() == before_last_row)
-    {
-        return Err(InvalidMoveError::NoMovePossibleAfterDropError);
+///number of rows between the square and the last row of the board, for the current player
+fn rows_left(square: Position, b: &Board) -> u16 {
+    let row = square.0 / 9;
+    if b.get_color() == Color::White {
+        b.rules.variant.size - 1 - row
     } else {
-        return Ok(mv);
+        row
     }
 }
 
+///whether an unpromoted piece of the current player could not move anymore from the square
+///(such as a pawn on the last row), whatever the other pieces
+fn stuck(piecetype: PieceType, square: Position, b: &Board) -> bool {
+    let moves = b.rules.variant.moves[piecetype as usize];
+    let sign = if b.get_color() == Color::White { 1 } else { -1 };
+    let size = b.rules.variant.size as i16;
+    let (column, row) = ((square.0 % 9) as i16, (square.0 / 9) as i16);
+    !moves.steps.iter().chain(moves.slides).any(|&(x, y)| {
+        (0..size).contains(&(column + sign * x)) && (0..size).contains(&(row + sign * y))
+    })
+}
+
 ///check if promotion (or absence of promotion) is allowed
 pub fn check_promotion<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, InvalidMoveError> {
     let full_move: Movement = mv.parse().unwrap();
+    let error = |mandatory| {
+        Err(InvalidMoveError::PromotionError {
+            piecetype: full_move.piecetype,
+            destination: full_move.end,
+            mandatory,
+        })
+    };
     if !maybe_normal_move(mv) {
         //it's a drop
         if full_move.promotion {
-            return Err(InvalidMoveError::PromotionError);
+            return error(false);
         } else {
             return Ok(mv);
         }
     }
-    let last_row;
-    let before_last_row;
-    let third_row;
-    if b.get_color() == Color::White {
-        last_row = 'i';
-        before_last_row = 'h';
-        third_row = 'g';
-    } else {
-        last_row = 'a';
-        before_last_row = 'b';
-        third_row = 'c';
-    }
+    let in_zone = |square| rows_left(square, b) < b.rules.variant.promotion_rows;
 
-    //cannot promote gold or king
-    if full_move.promotion
-        && (full_move.piecetype == PieceType::Gold || full_move.piecetype == PieceType::King)
-    {
-        return Err(InvalidMoveError::PromotionError);
+    //cannot promote gold or king (in the usual shogi)
+    if full_move.promotion && !b.rules.variant.can_promote(full_move.piecetype) {
+        return error(false);
     }
 
     if full_move.promotion {
         //promotion is asked
-        if (full_move.end.row() != last_row
-            && full_move.end.row() != before_last_row
-            && full_move.end.row() != third_row)
-            && (full_move.start.unwrap().row() != last_row
-                && full_move.start.unwrap().row() != before_last_row
-                && full_move.start.unwrap().row() != third_row)
-        {
-            return Err(InvalidMoveError::PromotionError);
+        if !in_zone(full_move.end) && !in_zone(full_move.start.unwrap()) {
+            return error(false);
         }
         return Ok(mv);
     } else {
         //promotion not asked
         if let Some(piece) = b.is_occupied_by(full_move.start.unwrap()) {
-            if !piece.promoted {
-                if (full_move.piecetype == PieceType::Pawn && full_move.end.row() == last_row)
-                    || (full_move.piecetype == PieceType::Lance && full_move.end.row() == last_row)
-                    || (full_move.piecetype == PieceType::Knight
-                        && (full_move.end.row() == last_row
-                            || full_move.end.row() == before_last_row))
-                {
-                    return Err(InvalidMoveError::PromotionError);
-                }
+            if !piece.promoted
+                && b.rules.variant.can_promote(full_move.piecetype)
+                && stuck(full_move.piecetype, full_move.end, b)
+            {
+                return error(true);
             }
         }
         return Ok(mv);
@@ -384,7 +542,7 @@ pub fn check_promotion<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, Invalid
 ///allow to uncover check, else consider the move invalid if it uncovers a check and do not take
 ///the opponent king
 pub fn check_uncover_check<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, InvalidMoveError> {
-    if b.rules.can_uncover_check {
+    if b.rules.can_uncover_check || b.rules.win_condition == WinCondition::KingCapture {
         return Ok(mv);
     }
 
@@ -400,12 +558,13 @@ pub fn check_uncover_check<'a>(mv: &'a str, b: &'a Board) -> Result<&'a str, Inv
     }
 
     let board_after_my_move = b.play_move_unchecked(mv);
-    for opponent_move in board_after_my_move.iter_normal_moves_only(false) {
-        let future_board = board_after_my_move.play_move_unchecked(&opponent_move);
-        if !future_board.contains(PieceType::King, my_color) {
-            //opponent has taken our king
-            return Err(InvalidMoveError::UncoverCheckError);
-        }
+    let king = match board_after_my_move.king_position(my_color) {
+        Some(king) => king,
+        None => return Ok(mv),
+    };
+    if let Some(&checking_piece) = board_after_my_move.attackers(king, opponent_color).first() {
+        //the opponent could take our king
+        return Err(InvalidMoveError::UncoverCheckError { checking_piece });
     }
 
     return Ok(mv);
@@ -436,9 +595,60 @@ pub fn check_checkmate_by_pawn_drop<'a>(
 
     //expensive, but unevitable check
     let board_after_my_move = b.play_move_unchecked(mv);
-    if board_after_my_move.game_over() {
-        return Err(InvalidMoveError::CheckmateByPawnDropError);
+    if board_after_my_move.lost_by() == Some(WinCondition::Checkmate) {
+        return Err(InvalidMoveError::CheckmateByPawnDropError {
+            square: full_move.end,
+        });
     } else {
         Ok(mv)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::invalidmoveerror::*;
+
+    #[test]
+    fn errors_have_context() {
+        let b = Board::new();
+        assert_eq!(
+            b.check_move("R8b-8h"),
+            Err(InvalidMoveError::PieceHasNoSuchMoveError {
+                piece: b.is_occupied_by("8b".parse().unwrap()).unwrap(),
+                destination: "8h".parse().unwrap(),
+                blocked_at: Some("8c".parse().unwrap()),
+            })
+        );
+        assert_eq!(
+            b.check_move("P1a-1b"),
+            Err(InvalidMoveError::NoPieceAtPositionError {
+                square: Some("1a".parse().unwrap()),
+                found: b.is_occupied_by("1a".parse().unwrap()),
+            })
+        );
+        assert_eq!(
+            b.check_move("P1c-1j"),
+            Err(InvalidMoveError::OutOfBoardMoveError(String::from("1j")))
+        );
+        let after = b
+            .play_move("P9c-9d")
+            .play_move("P9g-9f")
+            .play_move("P9d-9e");
+        let after = after
+            .play_move("P9f-9e")
+            .play_move("L9a-9e")
+            .play_move("P1g-1f");
+        assert_eq!(
+            after.check_move("P*1d"),
+            Err(InvalidMoveError::NifuViolationError { column: 1 })
+        );
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("P9c-9d", "P9c-9d"), 0);
+        assert_eq!(edit_distance("P9c9d", "P9c-9d"), 1);
+        assert_eq!(edit_distance("P9c-9d+", "P9c-9d"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+}
@@ -0,0 +1,1085 @@
+use crate::board::*;
+use crate::game::*;
+use crate::invalidmoveerror::*;
+use crate::movement::*;
+use crate::notation::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::render::*;
+use crate::usi::*;
+use crate::variant::*;
+
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// formats of game records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KifuFormat {
+    /// japanese KIF files (moves in kanji, with their origin: "７六歩(77)")
+    Kif,
+    /// files of the CSA protocol ("+7776FU")
+    Csa,
+    /// the USI "position" command ("position startpos moves 7g7f 3c3d")
+    Usi,
+    /// one move per line in our notation, after an optional "sfen ..." line
+    Moves,
+}
+
+impl KifuFormat {
+    /// guess the format from the extension of the file: .kif/.kifu, .csa, .usi, or .txt/.moves
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<KifuFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "kif" | "kifu" => Some(KifuFormat::Kif),
+            "csa" => Some(KifuFormat::Csa),
+            "usi" => Some(KifuFormat::Usi),
+            "txt" | "moves" => Some(KifuFormat::Moves),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KifuError {
+    IoError(io::Error),
+    UnknownFormat(String),
+    /// line (starting at 1) that could not be understood
+    SyntaxError {
+        line: usize,
+        text: String,
+    },
+    /// move (starting at 1) that is not legal in its position
+    IllegalMove {
+        number: usize,
+        text: String,
+        error: InvalidMoveError,
+    },
+}
+
+impl fmt::Display for KifuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KifuError::IoError(e) => write!(f, "{}", e),
+            KifuError::UnknownFormat(path) => write!(
+                f,
+                "Unknown format for {} (expected .kif, .csa, .usi or .txt)",
+                path
+            ),
+            KifuError::SyntaxError { line, text } => {
+                write!(f, "Cannot read line {}: {}", line, text)
+            }
+            KifuError::IllegalMove {
+                number,
+                text,
+                error,
+            } => write!(f, "Move {} ({}) is not valid: {}", number, text, error),
+        }
+    }
+}
+
+impl error::Error for KifuError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            KifuError::IoError(e) => Some(e),
+            KifuError::IllegalMove { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for KifuError {
+    fn from(e: io::Error) -> Self {
+        KifuError::IoError(e)
+    }
+}
+
+/// A game record: the game with the names of the players, and how it ended (None if the record
+/// does not tell)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Kifu {
+    pub game: Game,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub result: Option<GameResult>,
+}
+
+/// the ends of games written in KIF and CSA files, and whether the player to move wins (Some(true)),
+/// loses or draws (None)
+const RESULTS: [(Termination, Option<bool>, &str, &str); 7] = [
+    (Termination::Resignation, Some(false), "投了", "%TORYO"),
+    (Termination::Checkmate, Some(false), "詰み", "%TSUMI"),
+    (
+        Termination::TimeForfeit,
+        Some(false),
+        "切れ負け",
+        "%TIME_UP",
+    ),
+    (
+        Termination::IllegalMove,
+        Some(false),
+        "反則負け",
+        "%ILLEGAL_MOVE",
+    ),
+    (Termination::Repetition, None, "千日手", "%SENNICHITE"),
+    (
+        Termination::PerpetualCheck,
+        Some(true),
+        "連続王手の千日手",
+        "%OUTE_SENNICHITE",
+    ),
+    (Termination::Impasse, Some(true), "入玉勝ち", "%KACHI"),
+];
+
+/// read the end of a game written in a record, `kif` telling whether it is a KIF or CSA one
+fn parse_result(b: &Board, text: &str, kif: bool) -> Option<GameResult> {
+    let &(reason, wins, _, _) = RESULTS
+        .iter()
+        .find(|(_, _, kif_text, csa_text)| text == if kif { *kif_text } else { *csa_text })?;
+    Some(match wins {
+        Some(true) => GameResult::win(b.get_color(), reason),
+        Some(false) => GameResult::loss(b.get_color(), reason),
+        None => GameResult::draw(reason),
+    })
+}
+
+/// write the end of the game, if it can be written in KIF (or CSA) files
+fn write_result(result: &Option<GameResult>, kif: bool) -> Option<&'static str> {
+    let reason = result.as_ref()?.reason;
+    let &(_, _, kif_text, csa_text) = RESULTS.iter().find(|entry| entry.0 == reason)?;
+    Some(if kif { kif_text } else { csa_text })
+}
+
+const CSA_PIECES: [(PieceType, &str, &str); 8] = [
+    (PieceType::Pawn, "FU", "TO"),
+    (PieceType::Lance, "KY", "NY"),
+    (PieceType::Knight, "KE", "NK"),
+    (PieceType::Silver, "GI", "NG"),
+    (PieceType::Gold, "KI", "KI"),
+    (PieceType::Bishop, "KA", "UM"),
+    (PieceType::Rook, "HI", "RY"),
+    (PieceType::King, "OU", "OU"),
+];
+
+const HAND_ORDER: [PieceType; 7] = [
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+];
+
+fn csa_piece(piece: &Piece) -> &'static str {
+    let &(_, normal, promoted) = CSA_PIECES
+        .iter()
+        .find(|(t, _, _)| *t == piece.piecetype)
+        .unwrap();
+    if piece.promoted {
+        promoted
+    } else {
+        normal
+    }
+}
+
+fn parse_csa_piece(s: &str) -> Option<(PieceType, bool)> {
+    CSA_PIECES.iter().find_map(|&(t, normal, promoted)| {
+        if s == normal {
+            Some((t, false))
+        } else if s == promoted {
+            Some((t, true))
+        } else {
+            None
+        }
+    })
+}
+
+///squares of a board of the given size are written with digits as seen by the first player
+///("77"), ours are rotated
+fn digits_square(p: Position, size: u16) -> String {
+    format!("{}{}", size - p.0 % 9, size - p.0 / 9)
+}
+
+fn parse_digits_square(s: &str, size: u16) -> Option<Position> {
+    let mut chars = s.chars();
+    let square = kanji_position(chars.next()?, chars.next()?, size)?;
+    if chars.next().is_some() || !s.is_ascii() {
+        return None;
+    }
+    Some(square)
+}
+/// write a legal move in the CSA notation ("+7776FU"), the piece being written as it is after
+/// the move
+pub(crate) fn csa_move(b: &Board, mv: &str) -> String {
+    let movement: Movement = mv.parse().unwrap();
+    let sign = if b.get_color() == Color::White {
+        '+'
+    } else {
+        '-'
+    };
+    let piece = b
+        .play_move_unchecked(mv)
+        .is_occupied_by(movement.end)
+        .unwrap();
+    let size = b.rules.variant.size;
+    let start = movement
+        .start
+        .map_or(String::from("00"), |start| digits_square(start, size));
+    format!(
+        "{}{}{}{}",
+        sign,
+        start,
+        digits_square(movement.end, size),
+        csa_piece(&piece)
+    )
+}
+
+/// read a move in the CSA notation, without its sign ("7776FU"), and return it in the full
+/// notation (whether it is legal is not checked)
+pub(crate) fn parse_csa_move(b: &Board, s: &str) -> Option<String> {
+    if s.len() != 6 || !s.is_ascii() {
+        return None;
+    }
+    let (piecetype, promoted) = parse_csa_piece(&s[4..])?;
+    let size = b.rules.variant.size;
+    let end = parse_digits_square(&s[2..4], size)?;
+    if &s[..2] == "00" {
+        return Some(format!("{}*{}", piecetype, end.to_string()));
+    }
+    let start = parse_digits_square(&s[..2], size)?;
+    let promotion = b
+        .is_occupied_by(start)
+        .is_some_and(|piece| promoted && !piece.promoted);
+    Some(format!(
+        "{}{}-{}{}",
+        piecetype,
+        start.to_string(),
+        end.to_string(),
+        if promotion { "+" } else { "" }
+    ))
+}
+
+///counts of pieces in hand in KIF diagrams ("十二")
+fn parse_kanji_number(s: &str) -> Option<usize> {
+    let digit = |c: char| {
+        "一二三四五六七八九"
+            .chars()
+            .position(|k| k == c)
+            .map(|i| i + 1)
+    };
+    let mut chars = s.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (None, _, _) => Some(1),
+        (Some('十'), None, _) => Some(10),
+        (Some('十'), Some(c), None) => Some(10 + digit(c)?),
+        (Some(c), None, _) => digit(c),
+        _ => None,
+    }
+}
+
+///the board without the piece on the square
+fn without(b: &Board, square: Position) -> Board {
+    let mut new = Board::empty();
+    for piece in b.iter().filter(|p| p.position != Some(square)) {
+        new.add_piece(*piece);
+    }
+    new.turn = b.turn;
+    new.rules = b.rules;
+    new
+}
+
+///add pieces to the hand of a player, unless they would have more pieces than a board holds
+fn add_to_hand(
+    b: &mut Board,
+    color: Color,
+    piecetype: PieceType,
+    count: usize,
+) -> Result<(), String> {
+    for _ in 0..count {
+        b.try_add_piece(Piece {
+            color,
+            piecetype,
+            promoted: false,
+            position: None,
+        })?;
+    }
+    Ok(())
+}
+
+impl Kifu {
+    /// create the record of a game
+    pub fn new(game: Game) -> Self {
+        Kifu {
+            game,
+            ..Kifu::default()
+        }
+    }
+
+    /// read a game record, checking every move
+    pub fn parse(s: &str, format: KifuFormat) -> Result<Kifu, KifuError> {
+        match format {
+            KifuFormat::Kif => Kifu::parse_kif(s),
+            KifuFormat::Csa => Kifu::parse_csa(s),
+            KifuFormat::Usi => Kifu::parse_usi(s),
+            KifuFormat::Moves => Kifu::parse_moves(s),
+        }
+    }
+
+    /// write the game record
+    pub fn write(&self, format: KifuFormat) -> String {
+        let (game, result) = self.recorded();
+        match format {
+            KifuFormat::Kif => self.write_kif(&game, &result),
+            KifuFormat::Csa => self.write_csa(&game, &result),
+            KifuFormat::Usi => format!("{}\n", position_command(&game)),
+            KifuFormat::Moves => Kifu::write_moves(&game, &result),
+        }
+    }
+
+    /// return the game to write, stopped before a "withdraw" or a "restart" (which the formats
+    /// cannot write as moves), and its result, the withdraw being a resignation
+    fn recorded(&self) -> (Game, Option<GameResult>) {
+        let mut game = Game::from_board(self.game.start().clone());
+        for mv in self.game.moves() {
+            if mv == "withdraw" {
+                let resigned = GameResult::loss(game.board().get_color(), Termination::Resignation);
+                return (game, self.result.or(Some(resigned)));
+            }
+            if mv == "restart" {
+                break;
+            }
+            game.play_unchecked(mv);
+        }
+        (game, self.result)
+    }
+
+    /// read a game record from a file, whose format is given by its extension
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Kifu, KifuError> {
+        let format = KifuFormat::from_path(&path)
+            .ok_or_else(|| KifuError::UnknownFormat(path.as_ref().display().to_string()))?;
+        Kifu::parse(&fs::read_to_string(path)?, format)
+    }
+
+    /// write a game record in a file, whose format is given by its extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), KifuError> {
+        let format = KifuFormat::from_path(&path)
+            .ok_or_else(|| KifuError::UnknownFormat(path.as_ref().display().to_string()))?;
+        fs::write(path, self.write(format))?;
+        Ok(())
+    }
+
+    ///play the next move of the record
+    fn play(&mut self, mv: Result<String, InvalidMoveError>, text: &str) -> Result<(), KifuError> {
+        let number = self.game.moves().len() + 1;
+        let illegal = |error| KifuError::IllegalMove {
+            number,
+            text: text.to_string(),
+            error,
+        };
+        let mv = mv.map_err(illegal)?;
+        self.game.play(&mv).map_err(illegal)
+    }
+
+    fn parse_kif(s: &str) -> Result<Kifu, KifuError> {
+        let mut kifu = Kifu::default();
+        let mut diagram: Option<Board> = None;
+        let mut rank = 0;
+        for (i, line) in s.lines().enumerate() {
+            let syntax_error = || KifuError::SyntaxError {
+                line: i + 1,
+                text: line.to_string(),
+            };
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('*') {
+                continue;
+            }
+
+            // board diagram of the starting position
+            if let Some(b) = diagram.as_mut() {
+                if let Some(row) = line.strip_prefix('|') {
+                    let cells: Vec<char> = row.chars().collect();
+                    // two characters per square, the first row telling the variant
+                    let size = cells.iter().position(|&c| c == '|').unwrap_or(0) / 2;
+                    if rank == 0 {
+                        b.rules.variant = Variant::ALL
+                            .iter()
+                            .find(|v| v.size as usize == size)
+                            .ok_or_else(syntax_error)?;
+                    }
+                    let size = b.rules.variant.size;
+                    if rank >= size || cells.len() < 2 * size as usize {
+                        return Err(syntax_error());
+                    }
+                    for column in 0..size as usize {
+                        let color = match cells[2 * column] {
+                            ' ' => Color::White,
+                            'v' => Color::Black,
+                            _ => return Err(syntax_error()),
+                        };
+                        if cells[2 * column + 1] == '・' {
+                            continue;
+                        }
+                        let (piecetype, promoted) =
+                            Piece::from_kanji(&cells[2 * column + 1].to_string())
+                                .ok_or_else(syntax_error)?;
+                        b.try_add_piece(Piece {
+                            color,
+                            piecetype,
+                            promoted,
+                            position: Some(Position((size - 1 - rank) * 9 + column as u16)),
+                        })
+                        .map_err(|_| syntax_error())?;
+                    }
+                    rank += 1;
+                    continue;
+                }
+                // the borders, and the files above the board
+                if line.starts_with('+')
+                    || line
+                        .trim_start()
+                        .starts_with(|c| "１２３４５６７８９".contains(c))
+                {
+                    continue;
+                }
+                if line.starts_with("後手番") || line.starts_with("上手番") {
+                    b.turn = Color::Black;
+                    continue;
+                }
+            }
+
+            if let Some((key, value)) = line.split_once('：') {
+                let color = match key {
+                    "先手" | "下手" => Color::White,
+                    "後手" | "上手" => Color::Black,
+                    "先手の持駒" | "下手の持駒" | "後手の持駒" | "上手の持駒" =>
+                    {
+                        let color = if key.starts_with('先') || key.starts_with('下') {
+                            Color::White
+                        } else {
+                            Color::Black
+                        };
+                        let b = diagram.get_or_insert_with(Board::empty);
+                        for name in value.split(['　', ' ']) {
+                            if name.is_empty() || name == "なし" {
+                                continue;
+                            }
+                            let (first, count) =
+                                name.split_at(name.chars().next().unwrap().len_utf8());
+                            match (Piece::from_kanji(first), parse_kanji_number(count)) {
+                                (Some((piecetype, false)), Some(count)) => {
+                                    add_to_hand(b, color, piecetype, count)
+                                        .map_err(|_| syntax_error())?
+                                }
+                                _ => return Err(syntax_error()),
+                            }
+                        }
+                        continue;
+                    }
+                    _ => continue, // other headers (date, event, handicap...) are not kept
+                };
+                let name = Some(value.trim().to_string()).filter(|name| !name.is_empty());
+                if color == Color::White {
+                    kifu.white = name;
+                } else {
+                    kifu.black = name;
+                }
+                continue;
+            }
+            if line.starts_with("手数") {
+                if let Some(b) = diagram.take() {
+                    kifu.game = Game::from_board(b);
+                }
+                continue;
+            }
+
+            // move line: "   1 ７六歩(77)   ( 0:01/00:00:01)"
+            if line.starts_with("まで") {
+                continue; // summary of the result
+            }
+            if line.starts_with("変化") {
+                break; // variations are not kept
+            }
+            let rest = line.trim_start();
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if digits == 0 {
+                return Err(syntax_error());
+            }
+            if let Some(b) = diagram.take() {
+                kifu.game = Game::from_board(b);
+            }
+            let rest = rest[digits..].trim_start();
+            let text = match rest.strip_prefix('同') {
+                Some(after) => {
+                    let after = after.trim_start_matches(['　', ' ']);
+                    format!("同{}", after.split(' ').next().unwrap_or(""))
+                }
+                None => rest.split(' ').next().unwrap_or("").to_string(),
+            };
+            if let Some(result) = parse_result(kifu.game.board(), &text, true) {
+                kifu.result = Some(result);
+                break;
+            }
+            // the other ways to end a game (中断, 持将棋...) are not kept
+            if text
+                .chars()
+                .next()
+                .is_none_or(|c| kanji_coordinate(c).is_none())
+                && !text.starts_with('同')
+            {
+                break;
+            }
+            let mv = kifu.game.board().from_kanji(&text, kifu.game.last_move());
+            kifu.play(mv, &text)?;
+        }
+        if let Some(b) = diagram.take() {
+            kifu.game = Game::from_board(b);
+        }
+        Ok(kifu)
+    }
+
+    fn write_kif(&self, game: &Game, result: &Option<GameResult>) -> String {
+        let mut s = String::new();
+        let start = game.start();
+        if *start == Board::new() {
+            s.push_str("手合割：平手\n");
+        } else {
+            s.push_str(&format!(
+                "後手の持駒：{}\n",
+                kanji_hand(start, Color::Black)
+            ));
+            let size = start.rules.variant.size;
+            let files: Vec<char> = "１２３４５６７８９".chars().take(size as usize).collect();
+            let border = format!("+{}+\n", "-".repeat(3 * size as usize));
+            s.push(' ');
+            for file in files.iter().rev() {
+                s.push_str(&format!(" {}", file));
+            }
+            s.push('\n');
+            s.push_str(&border);
+            for rank in 1..=size {
+                s.push('|');
+                for column in 0..size {
+                    match start.is_occupied_by(Position((size - rank) * 9 + column)) {
+                        None => s.push_str(" ・"),
+                        Some(piece) => {
+                            s.push(if piece.color == Color::White {
+                                ' '
+                            } else {
+                                'v'
+                            });
+                            s.push_str(kanji_symbol(&piece));
+                        }
+                    }
+                }
+                s.push_str(&format!("|{}\n", kanji_number(rank as usize)));
+            }
+            s.push_str(&border);
+            s.push_str(&format!(
+                "先手の持駒：{}\n",
+                kanji_hand(start, Color::White)
+            ));
+            if start.turn == Color::Black {
+                s.push_str("後手番\n");
+            }
+        }
+        s.push_str(&format!(
+            "先手：{}\n後手：{}\n",
+            self.white.as_deref().unwrap_or(""),
+            self.black.as_deref().unwrap_or("")
+        ));
+        s.push_str("手数----指手---------消費時間--\n");
+
+        let size = start.rules.variant.size;
+        let mut b = start.clone();
+        let mut previous: Option<&str> = None;
+        for (i, mv) in game.moves().iter().enumerate() {
+            let movement: Movement = mv.parse().unwrap();
+            let same_square = previous
+                .and_then(|previous| previous.parse::<Movement>().ok())
+                .is_some_and(|previous| previous.end == movement.end);
+            let (name, suffix) = match movement.start {
+                None => (
+                    Piece {
+                        color: b.get_color(),
+                        piecetype: movement.piecetype,
+                        promoted: false,
+                        position: None,
+                    }
+                    .kanji(),
+                    String::from("打"),
+                ),
+                Some(start) => {
+                    let piece = b.is_occupied_by(start).unwrap();
+                    let declined = !movement.promotion
+                        && !piece.promoted
+                        && b.check_move(&format!("{}+", mv)).is_ok();
+                    let promotion = if movement.promotion {
+                        "成"
+                    } else if declined {
+                        "不成"
+                    } else {
+                        ""
+                    };
+                    (
+                        piece.kanji(),
+                        format!("{}({})", promotion, digits_square(start, size)),
+                    )
+                }
+            };
+            let destination = if !same_square {
+                kanji_square(movement.end, size)
+            } else if name.chars().count() == 1 {
+                String::from("同　")
+            } else {
+                String::from("同")
+            };
+            s.push_str(&format!("{:>4} {}{}{}\n", i + 1, destination, name, suffix));
+            b = b.play_move_unchecked(mv);
+            previous = Some(mv);
+        }
+        if let Some(text) = write_result(result, true) {
+            s.push_str(&format!("{:>4} {}\n", game.moves().len() + 1, text));
+        }
+        s
+    }
+
+    fn parse_csa(s: &str) -> Result<Kifu, KifuError> {
+        let mut kifu = Kifu::default();
+        let mut b = Board::empty();
+        let mut started = false;
+        // CSA allows several statements on a line, separated by commas
+        let statements = s
+            .lines()
+            .enumerate()
+            .flat_map(|(i, line)| line.split(',').map(move |statement| (i, statement)));
+        for (i, statement) in statements {
+            let syntax_error = || KifuError::SyntaxError {
+                line: i + 1,
+                text: statement.to_string(),
+            };
+            let statement = statement.trim();
+            let (first, rest) = match statement.chars().next() {
+                Some(first) => (first, &statement[first.len_utf8()..]),
+                None => continue,
+            };
+            match first {
+                'N' if rest.starts_with('+') && rest.len() > 1 => {
+                    kifu.white = Some(rest[1..].to_string())
+                }
+                'N' if rest.starts_with('-') && rest.len() > 1 => {
+                    kifu.black = Some(rest[1..].to_string())
+                }
+                'N' => {}
+                'P' if rest.starts_with('I') => {
+                    b = Board::new();
+                    // handicaps: pieces removed from the starting position ("PI82HI22KA")
+                    if rest.len() % 4 != 1 || !rest.is_ascii() {
+                        return Err(syntax_error());
+                    }
+                    for chunk in rest.as_bytes()[1..].chunks(4) {
+                        let square =
+                            parse_digits_square(std::str::from_utf8(&chunk[..2]).unwrap(), 9)
+                                .ok_or_else(syntax_error)?;
+                        b.is_occupied_by(square).ok_or_else(syntax_error)?;
+                        b = without(&b, square);
+                    }
+                }
+                'P' if rest.starts_with('+') || rest.starts_with('-') => {
+                    let color = if rest.starts_with('+') {
+                        Color::White
+                    } else {
+                        Color::Black
+                    };
+                    let pieces = &rest.as_bytes()[1..];
+                    if pieces.len() % 4 != 0 || !rest.is_ascii() {
+                        return Err(syntax_error());
+                    }
+                    for chunk in rest.as_bytes()[1..].chunks(4) {
+                        let chunk = std::str::from_utf8(chunk).unwrap();
+                        let (piecetype, promoted) =
+                            parse_csa_piece(&chunk[2..]).ok_or_else(syntax_error)?;
+                        if &chunk[..2] == "00" {
+                            add_to_hand(&mut b, color, piecetype, 1).map_err(|_| syntax_error())?;
+                        } else {
+                            b.try_add_piece(Piece {
+                                color,
+                                piecetype,
+                                promoted,
+                                position: Some(
+                                    parse_digits_square(&chunk[..2], 9).ok_or_else(syntax_error)?,
+                                ),
+                            })
+                            .map_err(|_| syntax_error())?;
+                        }
+                    }
+                }
+                'P' => {
+                    // a rank of the board: "P1-KY-KE-GI-KI-OU-KI-GI-KE-KY"
+                    let rank = rest
+                        .get(0..1)
+                        .and_then(|r| r.parse::<u16>().ok())
+                        .filter(|r| (1..=9).contains(r))
+                        .ok_or_else(syntax_error)?;
+                    // the trailing spaces of an empty square may have been trimmed
+                    let cells = format!("{:<27}", &rest[1..]);
+                    let cells = cells.as_bytes();
+                    if !rest.is_ascii() || cells.len() != 27 {
+                        return Err(syntax_error());
+                    }
+                    for (column, cell) in cells.chunks(3).enumerate() {
+                        let cell = std::str::from_utf8(cell).unwrap();
+                        let color = match &cell[..1] {
+                            "+" => Color::White,
+                            "-" => Color::Black,
+                            _ if cell == " * " => continue,
+                            _ => return Err(syntax_error()),
+                        };
+                        let (piecetype, promoted) =
+                            parse_csa_piece(&cell[1..]).ok_or_else(syntax_error)?;
+                        b.try_add_piece(Piece {
+                            color,
+                            piecetype,
+                            promoted,
+                            position: Some(Position((9 - rank) * 9 + column as u16)),
+                        })
+                        .map_err(|_| syntax_error())?;
+                    }
+                }
+                '+' | '-' if rest.is_empty() && !started => {
+                    b.turn = if first == '+' {
+                        Color::White
+                    } else {
+                        Color::Black
+                    };
+                    kifu.game = Game::from_board(b.clone());
+                    started = true;
+                }
+                '+' | '-' => {
+                    if !started {
+                        kifu.game = Game::from_board(b.clone());
+                        started = true;
+                    }
+                    let mv = parse_csa_move(kifu.game.board(), rest).ok_or_else(syntax_error)?;
+                    kifu.play(Ok(mv), statement)?;
+                }
+                '%' => {
+                    kifu.result = parse_result(kifu.game.board(), statement, false);
+                    break;
+                }
+                // version, comments, times and other informations
+                'V' | '\'' | '$' | 'T' => {}
+                _ => return Err(syntax_error()),
+            }
+        }
+        if !started {
+            kifu.game = Game::from_board(b);
+        }
+        Ok(kifu)
+    }
+
+    fn write_csa(&self, game: &Game, result: &Option<GameResult>) -> String {
+        let mut s = String::from("V2.2\n");
+        s.push_str(&format!(
+            "N+{}\nN-{}\n",
+            self.white.as_deref().unwrap_or(""),
+            self.black.as_deref().unwrap_or("")
+        ));
+        let start = game.start();
+        if *start == Board::new() {
+            s.push_str("PI\n");
+        } else {
+            for rank in 1..10 {
+                s.push_str(&format!("P{}", rank));
+                for column in 0..9 {
+                    match start.is_occupied_by(Position((9 - rank) * 9 + column)) {
+                        None => s.push_str(" * "),
+                        Some(piece) => {
+                            s.push(if piece.color == Color::White {
+                                '+'
+                            } else {
+                                '-'
+                            });
+                            s.push_str(csa_piece(&piece));
+                        }
+                    }
+                }
+                s.push('\n');
+            }
+            for &(color, sign) in &[(Color::White, '+'), (Color::Black, '-')] {
+                let mut hand = String::new();
+                for piecetype in &HAND_ORDER {
+                    for piece in start.iter().filter(|p| {
+                        p.position.is_none() && p.color == color && p.piecetype == *piecetype
+                    }) {
+                        hand.push_str(&format!("00{}", csa_piece(piece)));
+                    }
+                }
+                if !hand.is_empty() {
+                    s.push_str(&format!("P{}{}\n", sign, hand));
+                }
+            }
+        }
+        s.push_str(if start.turn == Color::White {
+            "+\n"
+        } else {
+            "-\n"
+        });
+
+        let mut b = start.clone();
+        for mv in game.moves() {
+            s.push_str(&format!("{}\n", csa_move(&b, mv)));
+            b = b.play_move_unchecked(mv);
+        }
+        if let Some(text) = write_result(result, false) {
+            s.push_str(text);
+            s.push('\n');
+        }
+        s
+    }
+
+    fn parse_usi(s: &str) -> Result<Kifu, KifuError> {
+        let line = s.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+        let syntax_error = || KifuError::SyntaxError {
+            line: 1,
+            text: line.to_string(),
+        };
+        let mut words = line.split_whitespace().peekable();
+        if words.peek() == Some(&"position") {
+            words.next();
+        }
+        let board = match words.next() {
+            Some("startpos") => Board::new(),
+            Some("sfen") => {
+                let sfen: Vec<&str> = words.by_ref().take_while(|w| *w != "moves").collect();
+                let board = Board::from_sfen(&sfen.join(" ")).map_err(|_| syntax_error())?;
+                return Kifu::usi_moves(board, words);
+            }
+            _ => return Err(syntax_error()),
+        };
+        match words.next() {
+            None => Kifu::usi_moves(board, words),
+            Some("moves") => Kifu::usi_moves(board, words),
+            Some(_) => Err(syntax_error()),
+        }
+    }
+
+    fn usi_moves<'a>(
+        board: Board,
+        moves: impl Iterator<Item = &'a str>,
+    ) -> Result<Kifu, KifuError> {
+        let mut kifu = Kifu::new(Game::from_board(board));
+        for usi in moves {
+            let mv = match from_usi(kifu.game.board(), usi) {
+                Ok(mv) => Ok(mv),
+                Err(UsiError::IllegalMoveError(_, e)) => Err(e),
+                Err(_) => Err(InvalidMoveError::MoveSyntaxError(usi.to_string())),
+            };
+            kifu.play(mv, usi)?;
+        }
+        Ok(kifu)
+    }
+
+    fn parse_moves(s: &str) -> Result<Kifu, KifuError> {
+        let mut kifu = Kifu::default();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(sfen) = line.strip_prefix("sfen ") {
+                if !kifu.game.moves().is_empty() {
+                    return Err(KifuError::SyntaxError {
+                        line: i + 1,
+                        text: line.to_string(),
+                    });
+                }
+                let board = Board::from_sfen(sfen).map_err(|_| KifuError::SyntaxError {
+                    line: i + 1,
+                    text: line.to_string(),
+                })?;
+                kifu.game = Game::from_board(board);
+                continue;
+            }
+            if line == "withdraw" {
+                kifu.result = Some(GameResult::loss(
+                    kifu.game.board().get_color(),
+                    Termination::Resignation,
+                ));
+                break;
+            }
+            let mv = kifu.game.board().resolve_move(line);
+            kifu.play(mv, line)?;
+        }
+        Ok(kifu)
+    }
+
+    fn write_moves(game: &Game, result: &Option<GameResult>) -> String {
+        let mut s = String::new();
+        if *game.start() != Board::new() {
+            s.push_str(&format!("sfen {}\n", game.start().to_sfen(1)));
+        }
+        for mv in game.moves() {
+            s.push_str(mv);
+            s.push('\n');
+        }
+        if result.is_some_and(|result| result.reason == Termination::Resignation) {
+            s.push_str("withdraw\n");
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::kifu::*;
+
+    const KIF: &str = "# ---- Kifu for Windows ----
+開始日時：2020/01/01
+手合割：平手
+先手：sente
+後手：gote
+手数----指手---------消費時間--
+   1 ７六歩(77)   ( 0:01/00:00:01)
+   2 ３四歩(33)   ( 0:02/00:00:02)
+   3 ２二角成(88)   ( 0:01/00:00:02)
+   4 同　銀(31)   ( 0:01/00:00:03)
+   5 ４五角打   ( 0:01/00:00:03)
+   6 投了
+";
+
+    fn sample() -> Kifu {
+        Kifu::parse(KIF, KifuFormat::Kif).unwrap()
+    }
+
+    #[test]
+    fn kif() {
+        let kifu = sample();
+        assert_eq!(
+            kifu.game.moves(),
+            ["P3c-3d", "P7g-7f", "B2b-8h+", "S7i-8h", "B*6e"]
+        );
+        assert_eq!(kifu.white.as_deref(), Some("sente"));
+        assert_eq!(kifu.black.as_deref(), Some("gote"));
+        assert_eq!(
+            kifu.result,
+            Some(GameResult::win(Color::White, Termination::Resignation))
+        );
+        let written = kifu.write(KifuFormat::Kif);
+        assert!(written.contains("   4 同　銀(31)\n"));
+        assert_eq!(Kifu::parse(&written, KifuFormat::Kif).unwrap(), kifu);
+
+        let illegal = KIF.replace("３四歩(33)", "３五歩(33)");
+        match Kifu::parse(&illegal, KifuFormat::Kif) {
+            Err(KifuError::IllegalMove { number, .. }) => assert_eq!(number, 2),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(Kifu::parse("   1 ７六歩(77", KifuFormat::Kif).is_err());
+    }
+
+    #[test]
+    fn conversions() {
+        let kifu = sample();
+        for &format in &[KifuFormat::Csa, KifuFormat::Usi, KifuFormat::Moves] {
+            let mut converted = Kifu::parse(&kifu.write(format), format).unwrap();
+            if format == KifuFormat::Usi || format == KifuFormat::Moves {
+                // names (and the result, for USI) are not written in these formats
+                converted.white = kifu.white.clone();
+                converted.black = kifu.black.clone();
+                converted.result = kifu.result;
+            }
+            assert_eq!(converted, kifu, "{:?}", format);
+        }
+        assert!(kifu
+            .write(KifuFormat::Csa)
+            .contains("\n+7776FU\n-3334FU\n+8822UM\n"));
+        assert_eq!(
+            kifu.write(KifuFormat::Usi),
+            "position startpos moves 7g7f 3c3d 8h2b+ 3a2b B*4e\n"
+        );
+        assert_eq!(KifuFormat::from_path("game.KIF"), Some(KifuFormat::Kif));
+        assert_eq!(KifuFormat::from_path("game"), None);
+    }
+
+    #[test]
+    fn withdraw() {
+        let mut kifu = Kifu::new(Game::new());
+        for mv in &["P3c-3d", "P7g-7f", "withdraw"] {
+            kifu.game.play(mv).unwrap();
+        }
+        for &format in &[
+            KifuFormat::Kif,
+            KifuFormat::Csa,
+            KifuFormat::Usi,
+            KifuFormat::Moves,
+        ] {
+            let read = Kifu::parse(&kifu.write(format), format).unwrap();
+            assert_eq!(read.game.moves(), ["P3c-3d", "P7g-7f"], "{:?}", format);
+            if format != KifuFormat::Usi {
+                assert_eq!(
+                    read.result,
+                    Some(GameResult::loss(Color::White, Termination::Resignation)),
+                    "{:?}",
+                    format
+                );
+            }
+        }
+        assert!(kifu.write(KifuFormat::Kif).ends_with("   3 投了\n"));
+    }
+
+    #[test]
+    fn custom_start() {
+        let b = Board::from_sfen("4k4/9/4P4/9/9/9/9/9/4K4 b GS2p 1").unwrap();
+        let mut kifu = Kifu::new(Game::from_board(b));
+        kifu.game.play("G*5h").unwrap();
+        for &format in &[
+            KifuFormat::Kif,
+            KifuFormat::Csa,
+            KifuFormat::Usi,
+            KifuFormat::Moves,
+        ] {
+            assert_eq!(
+                Kifu::parse(&kifu.write(format), format).unwrap(),
+                kifu,
+                "{:?}",
+                format
+            );
+        }
+
+        // the squares of minishogi go from 1一 to 5五
+        let mut kifu = Kifu::new(Game::from_board(Board::minishogi()));
+        kifu.game
+            .play(&kifu.game.board().resolve_move("P-1c").unwrap())
+            .unwrap();
+        let written = kifu.write(KifuFormat::Kif);
+        assert!(written.contains("\n  ５ ４ ３ ２ １\n+---------------+\n"));
+        assert!(written.contains("|五\n"));
+        assert!(written.contains("   1 ５三歩(54)\n"));
+        for &format in &[KifuFormat::Kif, KifuFormat::Usi, KifuFormat::Moves] {
+            assert_eq!(
+                Kifu::parse(&kifu.write(format), format).unwrap(),
+                kifu,
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn too_many_pieces() {
+        let csa = format!("V2.2\nPI\nP+{}\n+\n", "00FU".repeat(40));
+        assert!(matches!(
+            Kifu::parse(&csa, KifuFormat::Csa),
+            Err(KifuError::SyntaxError { line: 3, .. })
+        ));
+        let kif = "先手の持駒：歩十八　歩十八　歩十八\n手数----指手---------消費時間--\n";
+        assert!(matches!(
+            Kifu::parse(kif, KifuFormat::Kif),
+            Err(KifuError::SyntaxError { line: 1, .. })
+        ));
+    }
+}
@@ -20,6 +20,7 @@
 //! # use shogai::board::Board;
 //! # use shogai::ai::{greedy, get_move_from_human};
 //!let mut b = Board::new();
+//!let mut history = Vec::new();
 //!
 //!loop {
 //!    println!("");
@@ -33,6 +34,15 @@
 //!        mv = greedy(&b);
 //!    }
 //!
+//!    //the human may also take back his last move (or resign with "withdraw")
+//!    if mv == "undo" {
+//!        b = history.pop().unwrap_or(b);
+//!        continue;
+//!    }
+//!    if b.get_turn() {
+//!        history.push(b.clone());
+//!    }
+//!
 //!    println!("{:?} has chosen the move: {}", b.get_color(), mv);
 //!    b = b.play_move_unchecked(&mv); //because checks are done within greedy and get_human_move
 //!    if b.game_over() {
@@ -75,7 +85,10 @@
 //! println!("{}", b);
 //!
 //!
-//! //equivalent to play_move()
+//! //equivalent to play_move(), but returns an error instead of panicking
+//! assert!(b.try_play_move("Pzz-1").is_err());
+//!
+//! //also equivalent to play_move()
 //! assert!(b.check_move(&second_player_move).is_ok());
 //! b.play_move_unchecked(&second_player_move);
 //!
@@ -89,9 +102,25 @@
 pub mod ai;
 /// Manage and manipulate information about the shogi board (shogiban) state
 pub mod board;
+/// Opening book: weighted candidate moves for known positions, built from game records
+pub mod book;
+/// Client of the CSA protocol, to let any player enter computer shogi servers such as floodgate
+pub mod csa;
+/// A game in progress: the current board, the moves played so far and their history
+pub mod game;
+/// JSON API over HTTP to create, play and analyse games, behind the "http" feature
+#[cfg(feature = "http")]
+pub mod httpapi;
 /// Check a move against a board, to ensure legality. Different functions are used to check
 /// differents types of incorrect moves.
 pub mod invalidmoveerror;
+/// Read and write game records (KIF, CSA, USI, or lists of moves)
+pub mod kifu;
+/// Play matches between two players, with time controls and adjudication, and estimate their
+/// Elo difference
+pub mod matchrunner;
+/// Staged move generation (captures, promotions, quiet moves, drops) for the searches
+pub mod movegen;
 /// Handles the conversion from string literal to computer-understandable movement structure
 ///respect the [standard notation](https://en.wikipedia.org/wiki/Shogi_notation#Piece)
 ///(see: Western notation);
@@ -102,7 +131,10 @@ pub mod invalidmoveerror;
 ///
 ///drop        [Piece type]*[end]
 ///
-///However, origin must always be written! (implicit start position not allowed)
+///The origin must always be written here. Moves in the short notation, without origin ("P-7f",
+///"Gx5h", "S4i-4h" when two silvers can go to 4h), can be converted with Board::resolve_move, and
+///Board::to_short_notation does the opposite. The japanese notation ("▲７六歩", "△同　銀") is
+///read and written by Board::from_kanji and Board::to_kanji
 ///
 ///Additional moves are (without the quotes): "withdraw" and "restart", although you will have to
 ///enable the later in the board's rules
@@ -113,7 +145,8 @@ pub mod invalidmoveerror;
 ///
 ///If the piece is to be promoted, the move should be written "P4d-4c+" ('+' at the end of the
 ///move).
-///The promotion status may be provided anytime but will trigger the check (and panic) if promotion is
+///The promotion status may be provided anytime but will trigger the check (and an error, or a
+///panic with play_move) if promotion is
 ///requested but the piece does not fulfill conditions to be promoted, or if promotion is
 ///mandatory but the promotion was not requested.
 /// No extra + is required to move a promoted pawn after the promotion. No extra '=' must
@@ -130,7 +163,29 @@ pub mod invalidmoveerror;
 ///Also taking an opponent pawn with a Lance: "L9a-9f"
 ///
 pub mod movement;
+/// Read and write moves in other notations than the one of the movement module
+pub mod notation;
 /// Represent shogi pieces
 pub mod piece;
+/// Players (human, bots, external engines) choosing moves during a game
+pub mod player;
 /// Represent square of the shogiban
 pub mod position;
+/// Draw the board in the terminal or in logs, with letters or kanji
+pub mod render;
+/// Serde support, behind the "serde" feature: squares and moves are written as in the notation
+/// ("7g", "P7g-7f"), boards as their SFEN and rules
+#[cfg(feature = "serde")]
+pub mod serialization;
+/// Game server pairing players over TCP, checking their moves and keeping their clocks
+pub mod server;
+/// Read and write boards in the SFEN notation used by USI engines
+pub mod sfen;
+/// Draw the board as a SVG diagram, for study material
+pub mod svg;
+/// Full-screen terminal interface to play games, moves being entered with the cursor
+pub mod tui;
+/// Drive external engines speaking the USI protocol, and use them as players
+pub mod usi;
+/// Shogi variants (minishogi, judkins shogi...): board size, pieces, moves and promotions
+pub mod variant;
@@ -1,33 +1,236 @@
-mod ai;
-mod board;
-mod invalidmoveerror;
-mod movement;
-mod piece;
-mod position;
+use shogai::ai;
+use shogai::board::Board;
+use shogai::book::Book;
+use shogai::kifu::*;
+use shogai::player::*;
+use shogai::render::*;
+use shogai::svg::SvgOptions;
+use shogai::tui;
+use shogai::variant::Variant;
+use std::env;
+use std::process;
+use std::time::Instant;
 
-fn main() {
-    // example of using the program
-    let mut b = board::Board::new();
+const USAGE: &str = "usage: shogai [COMMAND] [ARGS]
+commands:
+  play [--kanji] [--no-color] [WHITE] [BLACK]  play a game (the default command), players are
+                                               human, greedy, random or usi:COMMAND
+                                               (human against greedy by default)
+       [--book BOOK]                           the bots play from the opening BOOK first
+       [--sfen SFEN]                           start from another position or variant
+  perft DEPTH [--sfen SFEN]                    count the positions reached after DEPTH moves
+  analyse SFEN [--depth N] [--book BOOK]       search the best move of a position, playing
+                                               from the opening BOOK first if given
+  convert IN OUT                               convert a game record, the formats being
+                                               guessed from the extensions (kif, csa, usi, txt)
+  validate KIFU                                replay a game record, checking every move
+  show SFEN [--kanji] [--no-color] [--svg]     draw a position
+SFEN may be \"startpos\" for the usual starting position, or the name of a variant to start
+(minishogi, judkins)";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n{}", message, USAGE);
+    process::exit(1);
+}
+
+///remove a flag from the arguments, and tell whether it was there
+fn flag(args: &mut Vec<String>, name: &str) -> bool {
+    let found = args.iter().any(|arg| arg == name);
+    args.retain(|arg| arg != name);
+    found
+}
+
+///remove an option and its value from the arguments
+fn option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|arg| arg == name)?;
+    if i + 1 >= args.len() {
+        fail(&format!("expected a value after {}", name));
+    }
+    args.remove(i);
+    Some(args.remove(i))
+}
+
+fn number(value: &str) -> u32 {
+    value
+        .parse()
+        .unwrap_or_else(|_| fail(&format!("expected a number: {}", value)))
+}
 
-    loop {
-        println!("");
-        println!("{:?} turn", b.get_color());
-        println!("{}", b);
+fn board(sfen: &str) -> Board {
+    if sfen == "startpos" {
+        return Board::new();
+    }
+    if let Some(variant) = Variant::by_name(sfen) {
+        return Board::start(variant);
+    }
+    Board::from_sfen(sfen).unwrap_or_else(|e| fail(&format!("invalid sfen: {}", e)))
+}
+
+fn load(path: &str) -> Kifu {
+    Kifu::load(path).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path, e);
+        process::exit(1);
+    })
+}
 
-        let mv;
-        if b.get_turn() {
-            mv = ai::get_move_from_human(&b);
+fn load_book(path: &str) -> Book {
+    Book::load(path).unwrap_or_else(|e| fail(&format!("cannot read {}: {}", path, e)))
+}
+
+fn render_options(args: &mut Vec<String>) -> RenderOptions {
+    RenderOptions {
+        style: if flag(args, "--kanji") {
+            PieceStyle::Kanji
         } else {
-            mv = ai::greedy(&b);
+            PieceStyle::Letters
+        },
+        color: !flag(args, "--no-color") && env::var_os("NO_COLOR").is_none(),
+        highlight_check: true,
+        ..RenderOptions::default()
+    }
+}
+
+fn play(mut args: Vec<String>) {
+    let options = render_options(&mut args);
+    let start = board(&option(&mut args, "--sfen").unwrap_or_else(|| String::from("startpos")));
+    let book = option(&mut args, "--book").map(|path| load_book(&path));
+    let mut players: Vec<Box<dyn Player>> = Vec::new();
+    for (i, default) in ["human", "greedy"].iter().enumerate() {
+        let name = args.get(i).map(|n| n.as_str()).unwrap_or(default);
+        match player_by_name(name, book.clone()) {
+            Some(p) => players.push(p),
+            None => fail(&format!("unknown player {}", name)),
         }
+    }
 
-        println!("{:?} has chosen the move: {}", b.get_color(), mv);
-        b = b.play_move_unchecked(&mv);
-        if b.game_over() {
-            println!("{:?} has lost the game!", b.get_color());
-            println!("final disposition of the board is \n{}", b);
+    let black = players.pop().unwrap();
+    let white = players.pop().unwrap();
+    if let Err(e) = tui::run([white, black], options, start) {
+        eprintln!("terminal error: {}", e);
+        process::exit(1);
+    }
+}
 
-            break;
-        }
+fn perft(mut args: Vec<String>) {
+    let b = board(&option(&mut args, "--sfen").unwrap_or_else(|| String::from("startpos")));
+    let depth = match args.as_slice() {
+        [depth] => number(depth),
+        _ => fail("perft needs a depth"),
+    };
+    let start = Instant::now();
+    let count = b.perft(depth);
+    println!(
+        "perft {}: {} ({:.2}s)",
+        depth,
+        count,
+        start.elapsed().as_secs_f64()
+    );
+}
+
+fn analyse(mut args: Vec<String>) {
+    let depth = option(&mut args, "--depth").map_or(2, |depth| number(&depth));
+    let book = option(&mut args, "--book").map(|path| load_book(&path));
+    let b = match args.as_slice() {
+        [sfen] => board(sfen),
+        _ => fail("analyse needs a sfen"),
+    };
+    let (score, pv) = match &book {
+        Some(book) => ai::alphabeta_with_book(&b, depth, book),
+        None => ai::alphabeta(&b, depth),
+    };
+    if pv.is_empty() {
+        println!("no legal move, {:?} has lost", b.get_color());
+        return;
+    }
+
+    // mate scores decrease by one per move played before the mate
+    let score = if score.abs() > ai::MATE / 2 {
+        let plies = ai::MATE - score.abs();
+        format!("mate in {}", (plies + 1) / 2 * score.signum())
+    } else {
+        score.to_string()
+    };
+    let mut kanji = Vec::new();
+    let mut position = b.clone();
+    let mut previous: Option<&str> = None;
+    for mv in &pv {
+        kanji.push(
+            position
+                .to_kanji(mv, previous)
+                .unwrap_or_else(|_| mv.clone()),
+        );
+        position = position.play_move_unchecked(mv);
+        previous = Some(mv);
+    }
+    println!("best move: {} ({})", pv[0], kanji[0]);
+    println!("score: {}", score);
+    println!("principal variation: {}", pv.join(" "));
+    println!("                     {}", kanji.join(" "));
+}
+
+fn convert(args: Vec<String>) {
+    let (input, output) = match args.as_slice() {
+        [input, output] => (input, output),
+        _ => fail("convert needs an input and an output file"),
+    };
+    if let Err(e) = load(input).save(output) {
+        eprintln!("{}: {}", output, e);
+        process::exit(1);
+    }
+}
+
+fn validate(args: Vec<String>) {
+    let path = match args.as_slice() {
+        [path] => path,
+        _ => fail("validate needs a game record"),
+    };
+    // loading replays every move through check_move
+    let kifu = load(path);
+    println!(
+        "{}: {} legal moves{}",
+        path,
+        kifu.game.moves().len(),
+        kifu.result
+            .map(|result| format!(", then {}", result))
+            .unwrap_or_default()
+    );
+}
+
+fn show(mut args: Vec<String>) {
+    let svg = flag(&mut args, "--svg");
+    let options = render_options(&mut args);
+    let b = match args.as_slice() {
+        [sfen] => board(sfen),
+        _ => fail("show needs a sfen"),
+    };
+    if svg {
+        let options = SvgOptions {
+            style: options.style,
+            ..SvgOptions::default()
+        };
+        print!("{}", b.to_svg(&options));
+    } else {
+        print!("{}", b.render(&options));
     }
 }
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let command = args.first().cloned().unwrap_or_default();
+    let run = match command.as_str() {
+        "play" => play,
+        "perft" => perft,
+        "analyse" | "analyze" => analyse,
+        "convert" => convert,
+        "validate" => validate,
+        "show" => show,
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            return;
+        }
+        // players and flags alone still start a game
+        _ => return play(args),
+    };
+    args.remove(0);
+    run(args);
+}
@@ -0,0 +1,376 @@
+use crate::board::*;
+use crate::game::*;
+use crate::piece::*;
+use crate::player::*;
+use crate::sfen::*;
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSettings {
+    /// number of games to play
+    pub games: usize,
+    /// SFENs of the starting positions, each one is played twice (colors swapped)
+    pub openings: Vec<String>,
+    /// time available for the whole game (per player), None for unlimited
+    pub time_per_game: Option<Duration>,
+    /// time added after each move
+    pub increment: Duration,
+    /// number of moves after which the game is declared a draw
+    pub max_moves: usize,
+    /// number of occurrences of the same position after which the game is declared a draw
+    pub repetitions: usize,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        MatchSettings {
+            games: 2,
+            openings: vec![START_SFEN.to_string()],
+            time_per_game: None,
+            increment: Duration::from_secs(0),
+            max_moves: 256,
+            repetitions: 4,
+        }
+    }
+}
+
+/// result of a single game, and the number of moves played
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameOutcome {
+    pub result: GameResult,
+    pub moves: usize,
+}
+
+/// wins, draws and losses from the point of view of the first player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MatchResult {
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+}
+
+impl MatchResult {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// score of the first player, between 0 and 1
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.) / self.games() as f64
+    }
+
+    /// return the Elo difference between the first and the second player, and the half-width of
+    /// its 95% confidence interval. Both are infinite if one of the players won every game, and
+    /// the interval is infinite before the first game
+    pub fn elo(&self) -> (f64, f64) {
+        if self.games() == 0 {
+            return (0., f64::INFINITY);
+        }
+        let n = self.games() as f64;
+        let score = self.score();
+        let variance = (self.wins as f64 * (1. - score).powi(2)
+            + self.draws as f64 * (0.5 - score).powi(2)
+            + self.losses as f64 * score.powi(2))
+            / n;
+        let margin = 1.96 * (variance / n).sqrt();
+        let low = elo_from_score(score - margin);
+        let high = elo_from_score(score + margin);
+        (elo_from_score(score), (high - low) / 2.)
+    }
+}
+
+impl fmt::Display for MatchResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (elo, error) = self.elo();
+        write!(
+            f,
+            "+{} ={} -{} ({} games), Elo difference: {:.1} +/- {:.1}",
+            self.wins,
+            self.draws,
+            self.losses,
+            self.games(),
+            elo,
+            error
+        )
+    }
+}
+
+fn elo_from_score(score: f64) -> f64 {
+    if score <= 0. {
+        f64::NEG_INFINITY
+    } else if score >= 1. {
+        f64::INFINITY
+    } else {
+        -400. * (1. / score - 1.).log10()
+    }
+}
+
+/// play a game from the given board, `white` and `black` choose the moves of each color
+pub fn play_game<'a>(
+    start: &Board,
+    white: &mut (dyn Player + 'a),
+    black: &mut (dyn Player + 'a),
+    settings: &MatchSettings,
+) -> GameOutcome {
+    let mut game = Game::from_board(start.clone());
+    let mut clocks = [settings.time_per_game, settings.time_per_game];
+
+    for moves in 0..settings.max_moves {
+        let color = game.board().get_color();
+        let mut opponent = color;
+        opponent.invert();
+        let outcome = |winner, reason| GameOutcome {
+            result: GameResult { winner, reason },
+            moves,
+        };
+
+        if let Some(condition) = game.board().lost_by() {
+            return outcome(Some(opponent), condition.into());
+        }
+
+        let (player, other) = if color == Color::White {
+            (&mut *white, &mut *black)
+        } else {
+            (&mut *black, &mut *white)
+        };
+        player.set_clocks(clocks, settings.increment);
+        let start_time = Instant::now();
+        let mut action = player.choose_move(&game);
+        if action == PlayerAction::OfferDraw {
+            if other.accept_draw(&game) {
+                return outcome(None, Termination::DrawAgreement);
+            }
+            // the offer was declined, the player has to move (offering again is illegal)
+            action = player.choose_move(&game);
+        }
+        if let Some(remaining) = clocks[color as usize] {
+            match remaining.checked_sub(start_time.elapsed()) {
+                Some(left) => clocks[color as usize] = Some(left + settings.increment),
+                None => return outcome(Some(opponent), Termination::TimeForfeit),
+            }
+        }
+
+        let mv = match action {
+            PlayerAction::Move(mv) => mv,
+            PlayerAction::Resign => return outcome(Some(opponent), Termination::Resignation),
+            PlayerAction::OfferDraw => return outcome(Some(opponent), Termination::IllegalMove),
+            PlayerAction::DeclareImpasse => {
+                let result = game.declare_impasse();
+                return outcome(result.winner, result.reason);
+            }
+        };
+        if mv == "withdraw" {
+            return outcome(Some(opponent), Termination::Resignation);
+        }
+        if mv == "restart" || game.play(&mv).is_err() {
+            return outcome(Some(opponent), Termination::IllegalMove);
+        }
+
+        if game.repetitions() >= settings.repetitions {
+            return GameOutcome {
+                result: game.sennichite(),
+                moves: moves + 1,
+            };
+        }
+    }
+    GameOutcome {
+        result: GameResult::draw(Termination::MaxMoves),
+        moves: settings.max_moves,
+    }
+}
+
+/// play `settings.games` games between two players, alternating colors, and return the result
+/// from the point of view of `first`. `report` is called after each game
+pub fn run_match<'a>(
+    first: &mut (dyn Player + 'a),
+    second: &mut (dyn Player + 'a),
+    settings: &MatchSettings,
+    report: &mut dyn FnMut(usize, &GameOutcome),
+) -> Result<MatchResult, String> {
+    let openings = settings
+        .openings
+        .iter()
+        .map(|sfen| Board::from_sfen(sfen))
+        .collect::<Result<Vec<Board>, String>>()?;
+    if openings.is_empty() {
+        return Err(String::from("at least one opening is needed"));
+    }
+
+    let mut result = MatchResult::default();
+    for game in 0..settings.games {
+        let start = &openings[(game / 2) % openings.len()];
+        let first_plays = if game % 2 == 0 {
+            start.get_color()
+        } else {
+            let mut c = start.get_color();
+            c.invert();
+            c
+        };
+        let outcome = if first_plays == Color::White {
+            play_game(start, first, second, settings)
+        } else {
+            play_game(start, second, first, settings)
+        };
+        match outcome.result.winner {
+            None => result.draws += 1,
+            Some(c) if c == first_plays => result.wins += 1,
+            Some(_) => result.losses += 1,
+        }
+        report(game, &outcome);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::matchrunner::*;
+
+    #[test]
+    fn elo() {
+        let even = MatchResult {
+            wins: 10,
+            draws: 5,
+            losses: 10,
+        };
+        assert_eq!(even.elo().0, 0.);
+        assert!(even.elo().1 > 100.);
+
+        let better = MatchResult {
+            wins: 75,
+            draws: 0,
+            losses: 25,
+        };
+        let (elo, error) = better.elo();
+        assert!((elo - 190.8).abs() < 0.1);
+        assert!(error > 50. && error < 100.);
+        assert_eq!(
+            MatchResult {
+                wins: 3,
+                draws: 0,
+                losses: 0
+            }
+            .elo()
+            .0,
+            f64::INFINITY
+        );
+        assert_eq!(MatchResult::default().elo(), (0., f64::INFINITY));
+    }
+
+    /// always offers a draw, and accepts them
+    struct DrawOffer;
+
+    impl Player for DrawOffer {
+        fn choose_move(&mut self, _game: &Game) -> PlayerAction {
+            PlayerAction::OfferDraw
+        }
+
+        fn accept_draw(&mut self, _game: &Game) -> bool {
+            true
+        }
+    }
+
+    /// always declares the win by impasse
+    struct ImpasseClaim;
+
+    impl Player for ImpasseClaim {
+        fn choose_move(&mut self, _game: &Game) -> PlayerAction {
+            PlayerAction::DeclareImpasse
+        }
+    }
+
+    /// play `forth` if it is legal, else `back`
+    fn king_shuffle(b: &Board, forth: &str, back: &str) -> String {
+        if b.check_move(forth).is_ok() {
+            forth.to_string()
+        } else {
+            back.to_string()
+        }
+    }
+
+    #[test]
+    fn adjudication() {
+        let settings = MatchSettings::default();
+        let b = Board::new();
+
+        let outcome = play_game(
+            &b,
+            &mut |_: &Board| String::from("withdraw"),
+            &mut |_: &Board| String::from("withdraw"),
+            &settings,
+        );
+        assert_eq!(outcome.result.winner, Some(Color::Black));
+        assert_eq!(outcome.result.reason, Termination::Resignation);
+
+        let outcome = play_game(
+            &b,
+            &mut |b: &Board| king_shuffle(b, "K5a-5b", "K5b-5a"),
+            &mut |b: &Board| king_shuffle(b, "K5i-5h", "K5h-5i"),
+            &settings,
+        );
+        assert_eq!(outcome.result.winner, None);
+        assert_eq!(outcome.result.reason, Termination::Repetition);
+        assert_eq!(outcome.moves, 12);
+
+        let outcome = play_game(
+            &b,
+            &mut |_: &Board| String::from("P9c-9e"),
+            &mut |_: &Board| String::new(),
+            &settings,
+        );
+        assert_eq!(outcome.result.reason, Termination::IllegalMove);
+
+        // declaring the impasse at the start is a wrong claim, and loses
+        let outcome = play_game(
+            &b,
+            &mut |_: &Board| String::from("P3c-3d"),
+            &mut ImpasseClaim,
+            &settings,
+        );
+        assert_eq!(outcome.result.winner, Some(Color::White));
+        assert_eq!(outcome.result.reason, Termination::IllegalMove);
+
+        let outcome = play_game(&b, &mut DrawOffer, &mut DrawOffer, &settings);
+        assert_eq!(outcome.result.winner, None);
+        assert_eq!(outcome.result.reason, Termination::DrawAgreement);
+        // greedy declines the draw when it is not behind
+        let outcome = play_game(&b, &mut DrawOffer, &mut Greedy, &settings);
+        assert_eq!(outcome.result.winner, Some(Color::Black));
+        assert_eq!(outcome.result.reason, Termination::IllegalMove);
+
+        // in checkshogi, the rook taking the pawn in front of the king wins at once
+        let mut b = Board::minishogi();
+        b.rules.win_condition = WinCondition::FirstCheck;
+        let outcome = play_game(
+            &b,
+            &mut |_: &Board| String::from("R5a-5d"),
+            &mut |_: &Board| String::new(),
+            &settings,
+        );
+        assert_eq!(outcome.result.winner, Some(Color::White));
+        assert_eq!(outcome.result.reason, Termination::Check);
+        assert_eq!(outcome.moves, 1);
+    }
+
+    #[test]
+    fn alternate_colors() {
+        let settings = MatchSettings {
+            games: 4,
+            ..MatchSettings::default()
+        };
+        let mut always_resign = |_: &Board| String::from("withdraw");
+        let mut colors = Vec::new();
+        let result = run_match(
+            &mut always_resign.clone(),
+            &mut always_resign,
+            &settings,
+            &mut |_, outcome| colors.push(outcome.result.winner),
+        )
+        .unwrap();
+        // the first player always resigns: each player wins with the second color
+        assert_eq!(result.wins, 2);
+        assert_eq!(result.losses, 2);
+        assert_eq!(colors, vec![Some(Color::Black); 4]);
+    }
+}
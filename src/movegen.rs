@@ -0,0 +1,217 @@
+use crate::board::*;
+use crate::movement::*;
+use crate::piece::*;
+use std::mem;
+
+/// The stages of the staged move generator (see Board::staged_moves), in the order in which
+/// their moves are given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    /// the captures, the most valuable victims first and, for the same victim, the least
+    /// valuable attackers first (MVV-LVA)
+    Captures,
+    /// the promotions capturing nothing
+    Promotions,
+    /// the other moves of the pieces on the board
+    Quiets,
+    Drops,
+}
+
+/// Iterator over the legal moves of a board and their stage, the moves most likely to be good
+/// coming first, as alpha-beta and quiescence searches like them. Each stage is only generated
+/// once the previous one is exhausted: after a cutoff in the captures, the drops (most of the
+/// moves once pieces were taken) are never generated
+pub struct StagedMoves<'a> {
+    board: &'a Board,
+    stage: Option<Stage>,
+    moves: std::vec::IntoIter<String>,
+    promotions: Vec<String>,
+    quiets: Vec<String>,
+    /// the moves out of check, computed once for every stage (see Board::keep_legal_with)
+    evasions: Option<Vec<String>>,
+}
+
+impl<'a> StagedMoves<'a> {
+    /// generate the moves of the stage
+    fn generate(&mut self, stage: Stage) -> Vec<String> {
+        let b = self.board;
+        match stage {
+            Stage::Captures => {
+                // the moves on the board are generated at once, and sorted in their stages
+                let mut captures = vec![];
+                for mv in b.keep_legal_with(b.board_candidates().into_iter(), &mut self.evasions) {
+                    let movement: Movement = mv.parse().unwrap();
+                    if let Some(victim) = b.is_occupied_by(movement.end) {
+                        let attacker = b.is_occupied_by(movement.start.unwrap()).unwrap();
+                        captures.push(((-victim.value(), attacker.value()), mv));
+                    } else if movement.promotion {
+                        self.promotions.push(mv);
+                    } else {
+                        self.quiets.push(mv);
+                    }
+                }
+                captures.sort_by_key(|(key, _)| *key);
+                captures.into_iter().map(|(_, mv)| mv).collect()
+            }
+            Stage::Promotions => mem::take(&mut self.promotions),
+            Stage::Quiets => mem::take(&mut self.quiets),
+            Stage::Drops => b.keep_legal_with(b.drop_candidates().into_iter(), &mut self.evasions),
+        }
+    }
+}
+
+impl<'a> Iterator for StagedMoves<'a> {
+    type Item = (Stage, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(mv) = self.moves.next() {
+                return Some((self.stage?, mv));
+            }
+            let stage = match self.stage {
+                None => Stage::Captures,
+                Some(Stage::Captures) => Stage::Promotions,
+                Some(Stage::Promotions) => Stage::Quiets,
+                Some(Stage::Quiets) => Stage::Drops,
+                Some(Stage::Drops) => return None,
+            };
+            self.stage = Some(stage);
+            self.moves = self.generate(stage).into_iter();
+        }
+    }
+}
+
+impl Board {
+    /// iter over the legal moves stage by stage: captures, promotions, quiet moves and drops
+    /// (see StagedMoves)
+    pub fn staged_moves(&self) -> StagedMoves<'_> {
+        StagedMoves {
+            board: self,
+            stage: None,
+            moves: Vec::new().into_iter(),
+            promotions: Vec::new(),
+            quiets: Vec::new(),
+            evasions: None,
+        }
+    }
+
+    /// iter over the legal moves giving check. Only the candidates are looked at: the moves and
+    /// drops to the squares from which the piece attacks the king of the opponent, and the moves
+    /// of the pieces standing between it and one of our sliding pieces, off that line
+    pub fn iter_checks(&self) -> impl Iterator<Item = String> + '_ {
+        let color = self.get_color();
+        let mut opponent = color;
+        opponent.invert();
+        let king = self.king_position(opponent);
+        let blockers = self.line_blockers(opponent, color);
+        let candidates = self
+            .board_candidates()
+            .into_iter()
+            .chain(self.drop_candidates())
+            .filter(move |mv| {
+                let king = match king {
+                    Some(king) => king,
+                    None => return false,
+                };
+                let movement: Movement = mv.parse().unwrap();
+                let piece = Piece {
+                    color,
+                    piecetype: movement.piecetype,
+                    promoted: movement.promotion
+                        || movement
+                            .start
+                            .and_then(|start| self.is_occupied_by(start))
+                            .is_some_and(|piece| piece.promoted),
+                    position: Some(movement.end),
+                };
+                let discovered = movement.start.is_some_and(|start| {
+                    blockers
+                        .iter()
+                        .any(|(blocker, line)| *blocker == start && !line.contains(&movement.end))
+                });
+                discovered || self.reaches(&piece, movement.end, king, movement.start)
+            });
+        self.keep_legal(candidates).into_iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::movegen::*;
+
+    #[test]
+    fn stages() {
+        let b = Board::new().play_move("P3c-3d").play_move("P7g-7f");
+        let staged: Vec<(Stage, String)> = b.staged_moves().collect();
+        assert_eq!(staged[0], (Stage::Captures, "B2b-8h+".to_string()));
+        assert!(staged.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        let mut moves: Vec<String> = staged.into_iter().map(|(_, mv)| mv).collect();
+        let mut expected: Vec<String> = b.iter_moves().collect();
+        moves.sort();
+        expected.sort();
+        assert_eq!(moves, expected);
+    }
+
+    #[test]
+    fn most_valuable_victims_first() {
+        let piece = |color, piecetype, square: &str| Piece {
+            color,
+            piecetype,
+            promoted: false,
+            position: Some(square.parse().unwrap()),
+        };
+        let mut b = Board::empty();
+        b.add_piece(piece(Color::White, PieceType::King, "9a"));
+        b.add_piece(piece(Color::Black, PieceType::King, "9i"));
+        b.add_piece(piece(Color::White, PieceType::Rook, "1e"));
+        b.add_piece(piece(Color::White, PieceType::Pawn, "5d"));
+        b.add_piece(piece(Color::Black, PieceType::Silver, "5e"));
+        b.add_piece(piece(Color::Black, PieceType::Bishop, "1g"));
+        let captures: Vec<String> = b
+            .staged_moves()
+            .take_while(|(stage, _)| *stage == Stage::Captures)
+            .map(|(_, mv)| mv)
+            .collect();
+        assert_eq!(
+            captures[..3],
+            [
+                "R1e-1g+".to_string(),
+                "R1e-1g".to_string(),
+                "P5d-5e".to_string()
+            ]
+        );
+        assert_eq!(captures[3], "R1e-5e");
+    }
+
+    #[test]
+    fn checks() {
+        let b = Board::new()
+            .play_move("P3c-3d")
+            .play_move("P7g-7f")
+            .play_move("B2b-8h+")
+            .play_move("S7i-8h");
+        let same_as_filtering = |b: &Board| {
+            let mut checks: Vec<String> = b.iter_checks().collect();
+            let mut expected: Vec<String> = b
+                .iter_moves()
+                .filter(|mv| b.play_move(mv).is_check())
+                .collect();
+            checks.sort();
+            expected.sort();
+            assert_eq!(checks, expected, "{}", b.to_sfen(1));
+            checks.len()
+        };
+        assert!(same_as_filtering(&b) > 0);
+        assert!(same_as_filtering(&Board::minishogi().play_move("P1b-1c")) > 0);
+        // a discovered check: the silver steps off the line of the rook, and a drop in the middle
+        let b = Board::from_sfen("4k4/9/9/9/4S4/9/9/9/4RK3 b P 1").unwrap();
+        let checks: Vec<String> = b.iter_checks().collect();
+        assert!(checks.contains(&"S5e-4d".to_string()));
+        assert!(!checks.contains(&"S5e-5d".to_string()));
+        assert_eq!(same_as_filtering(&b), 5);
+        // in check, the checks among the evasions
+        assert!(
+            same_as_filtering(&Board::from_sfen("4k4/9/9/9/4r3R/9/9/9/4K4 b - 1").unwrap()) > 0
+        );
+    }
+}
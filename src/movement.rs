@@ -1,3 +1,4 @@
+use crate::board::*;
 use crate::invalidmoveerror::*;
 use crate::piece::*;
 use crate::position::*;
@@ -87,8 +88,7 @@ impl fmt::Display for Movement {
 }
 
 impl FromStr for Movement {
-    // probably panic if given a wrong format, you better use check_move, or a part of it
-    // before parsing. check_move is a bit heavy though
+    // only the syntax is checked here, use check_move to know whether the move is legal
     type Err = String;
     fn from_str(s: &str) -> Result<Movement, String> {
         //special cases
@@ -116,51 +116,171 @@ impl FromStr for Movement {
         }
 
         //parsing reals moves
-        let piecetype: PieceType = s.chars().next().unwrap().to_string().parse().unwrap();
-        let mut pr = false;
-        let fs: Vec<char> = s
-            .chars()
-            .filter(|&c| !(c >= 'A' && c <= 'Z') && c != '-' && c != 'x')
-            .collect();
-        //
-        // moving a piece across the board, not a drop
-        if fs[0] != '*' {
-            if *fs.last().unwrap() == '+' {
-                pr = true;
-            }
-            let fc = match s.as_bytes()[3] {
-                b'-' => false,
-                b'x' => true,
-                _ => panic!("{}", InvalidMoveError::MoveSyntaxError),
-            };
-
-            let s1: String = fs[0..2].iter().collect();
-            let s2: String = fs[2..4].iter().collect();
-            let p1: Position = s1.parse().unwrap();
-            let p2: Position = s2.parse().unwrap();
-
-            Ok(Movement {
-                piecetype: piecetype,
-                start: Some(p1),
-                end: p2,
-                promotion: pr,
-                force_capture: fc,
-                withdraw: false,
-                restart: false,
-            })
-        } else {
+        let syntax_error = || InvalidMoveError::MoveSyntaxError(s.to_string()).to_string();
+        let mut chars = s.chars();
+        let piecetype: PieceType = chars.next().ok_or_else(syntax_error)?.to_string().parse()?;
+        let rest = chars.as_str();
+
+        if let Some(end) = rest.strip_prefix('*') {
             // drop movement
-            let s1: String = fs[1..3].iter().collect();
-            let p1: Position = s1.parse().unwrap();
-            Ok(Movement {
-                piecetype: piecetype,
+            return Ok(Movement {
+                piecetype,
                 start: None,
-                end: p1,
-                promotion: pr,
+                end: end.parse()?,
+                promotion: false,
                 force_capture: false,
                 withdraw: false,
                 restart: false,
-            })
+            });
+        }
+
+        // moving a piece across the board, not a drop
+        let (rest, promotion) = match rest.strip_suffix('+') {
+            Some(rest) => (rest, true),
+            None => (rest, false),
+        };
+        let force_capture = match rest.get(2..3) {
+            Some("-") => false,
+            Some("x") => true,
+            _ => return Err(syntax_error()),
+        };
+        let start: Position = rest[0..2].parse()?;
+        let end: Position = rest[3..].parse()?;
+
+        Ok(Movement {
+            piecetype,
+            start: Some(start),
+            end,
+            promotion,
+            force_capture,
+            withdraw: false,
+            restart: false,
+        })
+    }
+}
+
+///origin codes above the 81 squares: the drops (one per piece type) and the special moves
+const DROP: u16 = 81;
+const WITHDRAW: u16 = 126;
+const RESTART: u16 = 127;
+
+///A move packed in 16 bits, for transposition tables, opening books and game databases:
+///the destination (bits 0 to 6), the origin square or the dropped piece (bits 7 to 13), the
+///promotion (bit 14) and the capture mark "x" (bit 15). The piece moved is not stored: it is the
+///one on the origin square, so that a board is needed to get the Movement back (see Move32
+///otherwise)
+///```
+///use shogai::board::*;
+///use shogai::movement::*;
+///
+///let b = Board::new();
+///let mv: Movement = "P7g-7f".parse().unwrap();
+///let packed = Move16::from(mv);
+///assert_eq!(packed.to_movement(&b), Some(mv));
+///```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move16(pub u16);
+
+impl Move16 {
+    ///return the destination of the move
+    pub fn end(self) -> Position {
+        Position(self.0 & 0x7f)
+    }
+
+    ///return the square the piece moves from, None for a drop (or a special move)
+    pub fn start(self) -> Option<Position> {
+        Some(Position(self.origin())).filter(|p| p.0 < DROP)
+    }
+
+    ///return the dropped piece type, None if the move is not a drop
+    pub fn dropped(self) -> Option<PieceType> {
+        let origin = self.origin();
+        origin
+            .checked_sub(DROP)
+            .and_then(|i| PieceType::from_index(i as usize))
+    }
+
+    ///return whether the piece is promoted
+    pub fn promotion(self) -> bool {
+        self.0 & (1 << 14) != 0
+    }
+
+    fn origin(self) -> u16 {
+        (self.0 >> 7) & 0x7f
+    }
+
+    ///unpack the move, the piece moved being read on the board (None if its origin is empty)
+    pub fn to_movement(self, b: &Board) -> Option<Movement> {
+        let piecetype = match (self.start(), self.dropped()) {
+            (Some(start), _) => b.is_occupied_by(start)?.piecetype,
+            (None, Some(piecetype)) => piecetype,
+            (None, None) => PieceType::Pawn,
+        };
+        Some(Move32::with_piecetype(self, piecetype).into())
+    }
+}
+
+impl From<Movement> for Move16 {
+    fn from(mv: Movement) -> Self {
+        let origin = if mv.restart {
+            RESTART
+        } else if mv.withdraw {
+            WITHDRAW
+        } else {
+            mv.start.map_or(DROP + mv.piecetype as u16, |start| start.0)
+        };
+        Move16(
+            mv.end.0 | origin << 7 | (mv.promotion as u16) << 14 | (mv.force_capture as u16) << 15,
+        )
+    }
+}
+
+///A move packed in 32 bits: a Move16 (the lower bits) and the type of the piece moved (bits 16
+///to 18), from which the Movement is rebuilt without the board
+///```
+///use shogai::movement::*;
+///
+///let mv: Movement = "B2bx8h+".parse().unwrap();
+///assert_eq!(Movement::from(Move32::from(mv)), mv);
+///```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move32(pub u32);
+
+impl Move32 {
+    fn with_piecetype(mv: Move16, piecetype: PieceType) -> Self {
+        Move32(mv.0 as u32 | (piecetype as u32) << 16)
+    }
+
+    ///return the type of the piece moved (or dropped)
+    pub fn piecetype(self) -> PieceType {
+        PieceType::from_index((self.0 >> 16) as usize & 7).unwrap()
+    }
+}
+
+impl From<Movement> for Move32 {
+    fn from(mv: Movement) -> Self {
+        Move32::with_piecetype(mv.into(), mv.piecetype)
+    }
+}
+
+impl From<Move32> for Move16 {
+    fn from(mv: Move32) -> Self {
+        Move16(mv.0 as u16)
+    }
+}
+
+impl From<Move32> for Movement {
+    fn from(mv: Move32) -> Self {
+        let packed = Move16::from(mv);
+        let origin = packed.origin();
+        Movement {
+            piecetype: mv.piecetype(),
+            start: packed.start(),
+            end: packed.end(),
+            promotion: packed.promotion(),
+            force_capture: packed.0 & (1 << 15) != 0,
+            withdraw: origin == WITHDRAW,
+            restart: origin == RESTART,
         }
     }
 }
@@ -202,9 +322,48 @@ mod test {
     #[test]
     fn testrestart() {
         let s = "restart";
-        let mv: Movement = s.clone().parse().unwrap();
+        let mv: Movement = s.parse().unwrap();
         assert!(mv.restart);
         let s2 = mv.to_string();
         assert_eq!(s, s2);
     }
+
+    #[test]
+    fn malformed() {
+        for s in &[
+            "", "P", "Pzz-1", "P*", "P*0a", "X1a-1b", "P1a_1b", "P1a-1b-", "P1é-1b", "P1a-1b++",
+        ] {
+            assert!(s.parse::<Movement>().is_err());
+        }
+    }
+
+    #[test]
+    fn packed_moves() {
+        let mut b = Board::new();
+        for _ in 0..60 {
+            let moves: Vec<String> = b.iter_moves().collect();
+            if moves.is_empty() {
+                break;
+            }
+            for s in moves
+                .iter()
+                .chain(&["withdraw".to_string(), "restart".to_string()])
+            {
+                let mv: Movement = s.parse().unwrap();
+                assert_eq!(Movement::from(Move32::from(mv)), mv);
+                assert_eq!(Move16::from(mv).to_movement(&b), Some(mv));
+            }
+            b = b.play_move_unchecked(&moves[moves.len() / 2]);
+        }
+        let mv: Movement = "B2bx8h+".parse().unwrap();
+        let packed = Move16::from(mv);
+        assert_eq!(packed.start(), "2b".parse().ok());
+        assert_eq!(packed.end(), "8h".parse().unwrap());
+        assert!(packed.promotion());
+        assert_eq!(
+            Move16::from("G*5e".parse::<Movement>().unwrap()).dropped(),
+            Some(PieceType::Gold)
+        );
+        assert_eq!(Move16::from(mv).to_movement(&Board::empty()), None);
+    }
 }
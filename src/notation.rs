@@ -0,0 +1,717 @@
+use crate::board::*;
+use crate::invalidmoveerror::*;
+use crate::movement::*;
+use crate::piece::*;
+use crate::position::*;
+
+/// a move in the short Western notation, before it is resolved against a board
+struct ShortMove {
+    piecetype: PieceType,
+    /// Some(true) if the piece was written as promoted ("+R"), None if not specified
+    promoted: Option<bool>,
+    start: Option<Position>,
+    capture: bool,
+    end: Position,
+    /// Some(true) for '+', Some(false) for '=', None if not specified
+    promotion: Option<bool>,
+}
+
+fn parse_short(s: &str) -> Option<ShortMove> {
+    let mut rest = s;
+    let promoted = match rest.strip_prefix('+') {
+        Some(r) => {
+            rest = r;
+            Some(true)
+        }
+        None => None,
+    };
+    let piecetype: PieceType = rest.get(0..1)?.parse().ok()?;
+    rest = &rest[1..];
+    let promotion = if let Some(r) = rest.strip_suffix('+') {
+        rest = r;
+        Some(true)
+    } else if let Some(r) = rest.strip_suffix('=') {
+        rest = r;
+        Some(false)
+    } else {
+        None
+    };
+    let (start, separator, end) = match rest.len() {
+        3 => (None, rest.get(0..1)?, rest.get(1..3)?),
+        5 => (
+            Some(rest.get(0..2)?.parse().ok()?),
+            rest.get(2..3)?,
+            rest.get(3..5)?,
+        ),
+        _ => return None,
+    };
+    if separator != "-" && separator != "x" {
+        return None;
+    }
+    Some(ShortMove {
+        piecetype,
+        promoted,
+        start,
+        capture: separator == "x",
+        end: end.parse().ok()?,
+        promotion,
+    })
+}
+
+/// files and ranks of the japanese notation, from 1 to 9 (as seen by the first player)
+const KANJI_FILES: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+const KANJI_RANKS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+/// disambiguators tried by Board::to_kanji, from the most to the least usual
+const KANJI_TAGS: [&str; 13] = [
+    "", "上", "引", "寄", "直", "右", "左", "右上", "右引", "右寄", "左上", "左引", "左寄",
+];
+
+///squares of a board of the given size, as seen by the first player
+pub(crate) fn kanji_square(p: Position, size: u16) -> String {
+    format!(
+        "{}{}",
+        KANJI_FILES[(size - 1 - p.0 % 9) as usize],
+        KANJI_RANKS[(size - 1 - p.0 / 9) as usize]
+    )
+}
+
+///files may also be written with ascii digits, and ranks with digits (as in kifu origins)
+pub(crate) fn kanji_coordinate(c: char) -> Option<u16> {
+    KANJI_FILES
+        .iter()
+        .chain(KANJI_RANKS.iter())
+        .position(|&k| k == c)
+        .map(|i| (i % 9) as u16 + 1)
+        .or_else(|| c.to_digit(10).filter(|&d| d >= 1).map(|d| d as u16))
+}
+
+pub(crate) fn kanji_position(file: char, rank: char, size: u16) -> Option<Position> {
+    let file = kanji_coordinate(file).filter(|&file| file <= size)?;
+    let rank = kanji_coordinate(rank).filter(|&rank| rank <= size)?;
+    Some(Position((size - rank) * 9 + (size - file)))
+}
+
+/// a move in the japanese notation, before it is resolved against a board
+struct KanjiMove {
+    /// None for 同 (same square as the previous move)
+    end: Option<Position>,
+    piecetype: PieceType,
+    promoted: bool,
+    /// disambiguators among 右, 左, 上, 引, 寄, 直
+    tags: Vec<char>,
+    drop: bool,
+    /// Some(true) for 成, Some(false) for 不成, None if not specified
+    promotion: Option<bool>,
+    /// origin written in kifu files, as in "７六歩(77)"
+    start: Option<Position>,
+}
+
+fn parse_kanji(s: &str, size: u16) -> Option<KanjiMove> {
+    let mut rest = s.trim();
+    for mark in &['▲', '△', '☗', '☖'] {
+        rest = rest.strip_prefix(*mark).unwrap_or(rest);
+    }
+    let end = match rest.strip_prefix('同') {
+        Some(r) => {
+            rest = r.trim_start_matches([' ', '　']);
+            None
+        }
+        None => {
+            let mut chars = rest.chars();
+            let end = kanji_position(chars.next()?, chars.next()?, size)?;
+            rest = chars.as_str();
+            Some(end)
+        }
+    };
+
+    // names have one or two characters ("銀", "成銀")
+    let mut chars = rest.chars();
+    let (first, second) = (chars.next()?, chars.next());
+    let ((piecetype, promoted), name_len) =
+        match second.and_then(|second| Piece::from_kanji(&format!("{}{}", first, second))) {
+            Some(name) => (name, first.len_utf8() + second.unwrap().len_utf8()),
+            None => (Piece::from_kanji(&first.to_string())?, first.len_utf8()),
+        };
+    rest = &rest[name_len..];
+
+    let mut tags = Vec::new();
+    while let Some(c) = rest.chars().next().filter(|c| "右左上引寄直".contains(*c)) {
+        tags.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    let drop = match rest.strip_prefix('打') {
+        Some(r) => {
+            rest = r;
+            true
+        }
+        None => false,
+    };
+    let promotion = if let Some(r) = rest
+        .strip_prefix("不成")
+        .or_else(|| rest.strip_prefix('生'))
+    {
+        rest = r;
+        Some(false)
+    } else if let Some(r) = rest.strip_prefix('成') {
+        rest = r;
+        Some(true)
+    } else {
+        None
+    };
+    let start = match rest.strip_prefix('(').and_then(|r| r.strip_suffix(')')) {
+        Some(origin) => {
+            let mut chars = origin.chars();
+            let start = kanji_position(chars.next()?, chars.next()?, size)?;
+            if chars.next().is_some() {
+                return None;
+            }
+            rest = "";
+            Some(start)
+        }
+        None => None,
+    };
+    if !rest.is_empty() || (drop && (promoted || promotion.is_some() || start.is_some())) {
+        return None;
+    }
+    Some(KanjiMove {
+        end,
+        piecetype,
+        promoted,
+        tags,
+        drop,
+        promotion,
+        start,
+    })
+}
+
+impl Board {
+    ///legal moves (in the full notation) matching a short move
+    fn short_move_candidates(&self, short: &ShortMove) -> (Vec<String>, Option<InvalidMoveError>) {
+        let mut candidates = Vec::new();
+        let mut first_error = None;
+        let separator = if short.capture { "x" } else { "-" };
+        for piece in self.iter().filter(|p| {
+            p.color == self.get_color()
+                && p.piecetype == short.piecetype
+                && p.position.is_some()
+                && (short.start.is_none() || p.position == short.start)
+                && (short.promoted.is_none() || short.promoted == Some(p.promoted))
+        }) {
+            let suffixes: &[&str] = match short.promotion {
+                Some(true) => &["+"],
+                Some(false) => &[""],
+                // promoted pieces do not promote again
+                None if piece.promoted => &[""],
+                None => &["", "+"],
+            };
+            for suffix in suffixes {
+                let mv = format!(
+                    "{}{}{}{}{}",
+                    short.piecetype,
+                    piece.position.unwrap().to_string(),
+                    separator,
+                    short.end.to_string(),
+                    suffix
+                );
+                // without '+' or '=', the promotion and the plain move may both be legal, and
+                // are both candidates
+                match self.check_move(&mv) {
+                    Ok(_) => candidates.push(mv),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+        (candidates, first_error)
+    }
+
+    /// Resolve a move written in the short Western notation ("P-7f", "Gx5h", "S4i-4h", "+B-5e")
+    /// against the board, and return it in the full notation ("P7g-7f"). The origin is only
+    /// needed when several pieces of the same type can reach the destination, and the promotion
+    /// ('+' or '=') when the piece may promote or not. Moves already in the full notation (and
+    /// drops) are checked and returned as is
+    pub fn resolve_move(&self, s: &str) -> Result<String, InvalidMoveError> {
+        if check_syntax(s).is_ok() || s == "withdraw" || s == "restart" {
+            return self.check_move(s).map(|mv| mv.to_string());
+        }
+        let short = match parse_short(s) {
+            Some(short) => short,
+            None => return Err(InvalidMoveError::MoveSyntaxError(s.to_string())),
+        };
+        let (mut candidates, first_error) = self.short_move_candidates(&short);
+        match candidates.len() {
+            1 => Ok(candidates.remove(0)),
+            0 => Err(
+                first_error.unwrap_or(InvalidMoveError::NoPieceAtPositionError {
+                    square: short.start,
+                    found: short.start.and_then(|start| self.is_occupied_by(start)),
+                }),
+            ),
+            _ => Err(InvalidMoveError::AmbiguousMoveError(candidates)),
+        }
+    }
+
+    /// Write a legal move (given in the full notation) in the shortest unambiguous Western
+    /// notation: the origin is only written when needed, '=' marks a declined promotion and a
+    /// leading '+' a promoted piece
+    pub fn to_short_notation(&self, mv: &str) -> Result<String, InvalidMoveError> {
+        self.check_move(mv)?;
+        let movement: Movement = mv.parse().unwrap();
+        let start = match movement.start {
+            None => return Ok(mv.to_string()), // drops, withdraw and restart
+            Some(start) => start,
+        };
+        let piece = self.is_occupied_by(start).unwrap();
+        let capture = self.is_occupied_by(movement.end).is_some();
+        let can_promote = !movement.promotion
+            && !piece.promoted
+            && self
+                .check_move(&format!(
+                    "{}{}-{}+",
+                    piece.piecetype,
+                    start.to_string(),
+                    movement.end.to_string()
+                ))
+                .is_ok();
+
+        let mut short = ShortMove {
+            piecetype: piece.piecetype,
+            promoted: if piece.promoted { Some(true) } else { None },
+            start: None,
+            capture,
+            end: movement.end,
+            promotion: if movement.promotion {
+                Some(true)
+            } else if can_promote {
+                Some(false)
+            } else {
+                None
+            },
+        };
+        if self.short_move_candidates(&short).0.len() > 1 {
+            short.start = Some(start);
+        }
+
+        Ok(format!(
+            "{}{}{}{}{}{}",
+            if piece.promoted { "+" } else { "" },
+            piece.piecetype,
+            short.start.map(|s| s.to_string()).unwrap_or_default(),
+            if capture { "x" } else { "-" },
+            movement.end.to_string(),
+            match short.promotion {
+                Some(true) => "+",
+                Some(false) => "=",
+                None => "",
+            }
+        ))
+    }
+
+    ///legal moves (in the full notation, with their origin) of the pieces named in a japanese
+    ///move, before the disambiguators are applied
+    fn kanji_candidates(
+        &self,
+        k: &KanjiMove,
+        end: Position,
+    ) -> (Vec<(Position, String)>, Option<InvalidMoveError>) {
+        let mut candidates = Vec::new();
+        let mut first_error = None;
+        for piece in self.iter().filter(|p| {
+            p.color == self.get_color()
+                && p.piecetype == k.piecetype
+                && p.promoted == k.promoted
+                && p.position.is_some()
+                && (k.start.is_none() || p.position == k.start)
+        }) {
+            let start = piece.position.unwrap();
+            let suffixes: &[&str] = match k.promotion {
+                Some(true) => &["+"],
+                Some(false) => &[""],
+                None if k.promoted => &[""],
+                None => &["", "+"],
+            };
+            for suffix in suffixes {
+                let mv = format!(
+                    "{}{}-{}{}",
+                    k.piecetype,
+                    start.to_string(),
+                    end.to_string(),
+                    suffix
+                );
+                // without 成 or 不成, the promotion and the plain move may both be legal, and are
+                // both candidates (as in short_move_candidates)
+                match self.check_move(&mv) {
+                    Ok(_) => candidates.push((start, mv)),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+        (candidates, first_error)
+    }
+
+    ///keep the candidates matching the disambiguators: 上 (forward), 引 (backward), 寄
+    ///(sideways), 直 (straight forward), then 右 or 左 (rightmost or leftmost piece, as seen by
+    ///the player)
+    fn filter_kanji_tags(
+        &self,
+        tags: &[char],
+        piecetype: PieceType,
+        end: Position,
+        mut candidates: Vec<(Position, String)>,
+    ) -> Vec<(Position, String)> {
+        let white = self.get_color() == Color::White;
+        let forward = |start: Position| {
+            let rows = (end.0 / 9) as i16 - (start.0 / 9) as i16;
+            if white {
+                rows
+            } else {
+                -rows
+            }
+        };
+        // first player's right is toward the file 1, which is the column 9 for us
+        let right = |start: Position| {
+            if white {
+                start.0 % 9
+            } else {
+                8 - start.0 % 9
+            }
+        };
+        for tag in tags {
+            candidates.retain(|&(start, _)| match tag {
+                '上' => forward(start) > 0,
+                '引' => forward(start) < 0,
+                '寄' => forward(start) == 0,
+                '直' => {
+                    forward(start) > 0
+                        && start.0 % 9 == end.0 % 9
+                        && piecetype != PieceType::Rook
+                        && piecetype != PieceType::Bishop
+                }
+                _ => true,
+            });
+        }
+        for tag in tags {
+            let sides = candidates.iter().map(|&(start, _)| right(start));
+            let side = match tag {
+                '右' => sides.max(),
+                '左' => sides.min(),
+                _ => continue,
+            };
+            candidates.retain(|&(start, _)| Some(right(start)) == side);
+        }
+        candidates
+    }
+
+    /// Resolve a move written in the japanese notation ("▲７六歩", "△同　銀", "５八金右",
+    /// "２二角成", "５五角打", or with the origin of kifu files "７六歩(77)") against the board,
+    /// and return it in the full notation. The previous move (in the full notation) is needed
+    /// for 同
+    pub fn from_kanji(&self, s: &str, previous: Option<&str>) -> Result<String, InvalidMoveError> {
+        let syntax_error = || InvalidMoveError::MoveSyntaxError(s.to_string());
+        if s.trim() == "投了" {
+            return Ok(String::from("withdraw"));
+        }
+        let k = parse_kanji(s, self.rules.variant.size).ok_or_else(syntax_error)?;
+        let end = match k.end {
+            Some(end) => end,
+            None => {
+                previous
+                    .and_then(|mv| mv.parse::<Movement>().ok())
+                    .filter(|mv| !mv.withdraw && !mv.restart)
+                    .ok_or_else(syntax_error)?
+                    .end
+            }
+        };
+        let drop = format!("{}*{}", k.piecetype, end.to_string());
+        if k.drop {
+            return self.check_move(&drop).map(|mv| mv.to_string());
+        }
+        let (candidates, first_error) = self.kanji_candidates(&k, end);
+        let mut candidates: Vec<String> = self
+            .filter_kanji_tags(&k.tags, k.piecetype, end, candidates)
+            .into_iter()
+            .map(|(_, mv)| mv)
+            .collect();
+        match candidates.len() {
+            1 => Ok(candidates.remove(0)),
+            // 打 may be omitted when no piece on the board can reach the square
+            0 if k.tags.is_empty()
+                && !k.promoted
+                && k.promotion.is_none()
+                && k.start.is_none()
+                && self.check_move(&drop).is_ok() =>
+            {
+                Ok(drop)
+            }
+            0 => Err(
+                first_error.unwrap_or(InvalidMoveError::NoPieceAtPositionError {
+                    square: k.start,
+                    found: k.start.and_then(|start| self.is_occupied_by(start)),
+                }),
+            ),
+            _ => Err(InvalidMoveError::AmbiguousMoveError(candidates)),
+        }
+    }
+
+    /// Write a legal move (given in the full notation) in the japanese notation ("▲７六歩"),
+    /// with 同 when it ends where the previous move did, and the disambiguators, 成, 不成 and
+    /// 打 only where they are needed. The king of the second player is written 王, the other one
+    /// 玉
+    pub fn to_kanji(&self, mv: &str, previous: Option<&str>) -> Result<String, InvalidMoveError> {
+        if mv == "withdraw" {
+            return Ok(String::from("投了"));
+        }
+        self.check_move(mv)?;
+        let movement: Movement = mv.parse().unwrap();
+        if movement.restart {
+            return Err(InvalidMoveError::MoveSyntaxError(mv.to_string()));
+        }
+        let color = self.get_color();
+        let piece = match movement.start {
+            Some(start) => self.is_occupied_by(start).unwrap(),
+            None => Piece {
+                color,
+                piecetype: movement.piecetype,
+                promoted: false,
+                position: None,
+            },
+        };
+        let name = match piece.piecetype {
+            PieceType::King if color == Color::Black => "王",
+            _ => piece.kanji(),
+        };
+        let same_square = previous
+            .and_then(|mv| mv.parse::<Movement>().ok())
+            .is_some_and(|previous| {
+                !previous.withdraw && !previous.restart && previous.end == movement.end
+            });
+        let destination = if !same_square {
+            kanji_square(movement.end, self.rules.variant.size)
+        } else if name.chars().count() == 1 {
+            String::from("同　")
+        } else {
+            String::from("同")
+        };
+
+        let mut k = KanjiMove {
+            end: Some(movement.end),
+            piecetype: piece.piecetype,
+            promoted: piece.promoted,
+            tags: Vec::new(),
+            drop: false,
+            promotion: None,
+            start: None,
+        };
+        // the pieces which can reach the square, promoting or not
+        let mut candidates = self.kanji_candidates(&k, movement.end).0;
+        candidates.dedup_by_key(|(start, _)| *start);
+        let suffix = match movement.start {
+            None if candidates.is_empty() => "",
+            None => "打",
+            Some(start) => {
+                let tags = KANJI_TAGS
+                    .iter()
+                    .find(|tags| {
+                        k.tags = tags.chars().collect();
+                        let left = self.filter_kanji_tags(
+                            &k.tags,
+                            piece.piecetype,
+                            movement.end,
+                            candidates.clone(),
+                        );
+                        left.len() == 1 && left[0].0 == start
+                    })
+                    .unwrap_or(&"");
+                k.tags = tags.chars().collect();
+                let can_promote = !movement.promotion
+                    && !piece.promoted
+                    && self
+                        .check_move(&format!(
+                            "{}{}-{}+",
+                            piece.piecetype,
+                            start.to_string(),
+                            movement.end.to_string()
+                        ))
+                        .is_ok();
+                if movement.promotion {
+                    "成"
+                } else if can_promote {
+                    "不成"
+                } else {
+                    ""
+                }
+            }
+        };
+        Ok(format!(
+            "{}{}{}{}{}",
+            if color == Color::White { "▲" } else { "△" },
+            destination,
+            name,
+            k.tags.iter().collect::<String>(),
+            suffix
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::notation::*;
+
+    #[test]
+    fn resolve() {
+        let b = Board::new();
+        assert_eq!(b.resolve_move("P-9d").unwrap(), "P9c-9d");
+        assert_eq!(b.resolve_move("P9c-9d").unwrap(), "P9c-9d");
+        assert!(b.resolve_move("P-9e").is_err());
+        assert!(b.resolve_move("Px9d").is_err());
+        assert!(b.resolve_move("P-").is_err());
+        assert_eq!(
+            b.resolve_move("G-5b"),
+            Err(InvalidMoveError::AmbiguousMoveError(vec![
+                String::from("G4a-5b"),
+                String::from("G6a-5b")
+            ]))
+        );
+        assert_eq!(b.resolve_move("G6a-5b").unwrap(), "G6a-5b");
+        assert_eq!(b.resolve_move("G4a-5b").unwrap(), "G4a-5b");
+        assert!(b.resolve_move("+R-8c").is_err());
+        assert!(b.resolve_move("+R-7b").is_err());
+        assert_eq!(b.resolve_move("R-7b").unwrap(), "R8b-7b");
+    }
+
+    #[test]
+    fn promotion() {
+        let mut b = Board::empty();
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Pawn,
+            promoted: false,
+            position: Some("5f".parse().unwrap()),
+        });
+        assert_eq!(b.resolve_move("P-5g+").unwrap(), "P5f-5g+");
+        assert_eq!(b.resolve_move("P-5g=").unwrap(), "P5f-5g");
+        assert_eq!(
+            b.resolve_move("P-5g"),
+            Err(InvalidMoveError::AmbiguousMoveError(vec![
+                String::from("P5f-5g"),
+                String::from("P5f-5g+")
+            ]))
+        );
+        assert_eq!(b.to_short_notation("P5f-5g").unwrap(), "P-5g=");
+        assert_eq!(b.to_short_notation("P5f-5g+").unwrap(), "P-5g+");
+
+        let mut b = Board::empty();
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Pawn,
+            promoted: true,
+            position: Some("5g".parse().unwrap()),
+        });
+        assert_eq!(b.to_short_notation("P5g-5h").unwrap(), "+P-5h");
+        assert_eq!(b.resolve_move("+P-5h").unwrap(), "P5g-5h");
+    }
+
+    #[test]
+    fn shortest_form() {
+        let b = Board::new();
+        assert_eq!(b.to_short_notation("P9c-9d").unwrap(), "P-9d");
+        assert_eq!(b.to_short_notation("G6a-5b").unwrap(), "G6a-5b");
+        assert_eq!(b.to_short_notation("K5a-5b").unwrap(), "K-5b");
+        assert!(b.to_short_notation("P9c-9e").is_err());
+        for mv in b.iter_moves() {
+            let short = b.to_short_notation(&mv).unwrap();
+            assert_eq!(b.resolve_move(&short).unwrap(), mv);
+        }
+    }
+
+    #[test]
+    fn kanji() {
+        let b = Board::new();
+        assert_eq!(b.to_kanji("P3c-3d", None).unwrap(), "▲７六歩");
+        assert_eq!(b.from_kanji("▲７六歩", None).unwrap(), "P3c-3d");
+        assert_eq!(b.from_kanji("７六歩(77)", None).unwrap(), "P3c-3d");
+        assert_eq!(b.to_kanji("G6a-5b", None).unwrap(), "▲５八金右");
+        assert_eq!(b.to_kanji("G4a-5b", None).unwrap(), "▲５八金左");
+        assert_eq!(b.from_kanji("５八金右", None).unwrap(), "G6a-5b");
+        assert_eq!(b.to_kanji("G6a-6b", None).unwrap(), "▲４八金");
+        assert!(b.from_kanji("５八金", None).is_err());
+        assert!(b.from_kanji("７五歩", None).is_err());
+        assert!(b.from_kanji("７六", None).is_err());
+        assert_eq!(b.from_kanji("投了", None).unwrap(), "withdraw");
+
+        // 玉 for the king of the first player, 王 for the other one
+        assert_eq!(b.to_kanji("K5a-5b", None).unwrap(), "▲５八玉");
+        let after = b.play_move("P3c-3d");
+        assert_eq!(after.to_kanji("K5i-5h", None).unwrap(), "△５二王");
+        assert_eq!(after.from_kanji("△５二王", None).unwrap(), "K5i-5h");
+        assert_eq!(after.from_kanji("△５二玉", None).unwrap(), "K5i-5h");
+
+        let mut golds = Board::empty();
+        for square in &["4a", "5a", "6a"] {
+            golds.add_piece(Piece {
+                color: Color::White,
+                piecetype: PieceType::Gold,
+                promoted: false,
+                position: Some(square.parse().unwrap()),
+            });
+        }
+        assert_eq!(golds.to_kanji("G5a-5b", None).unwrap(), "▲５八金直");
+        assert_eq!(golds.to_kanji("G4a-5b", None).unwrap(), "▲５八金左");
+        assert_eq!(golds.from_kanji("５八金右", None).unwrap(), "G6a-5b");
+
+        // 同 refers to the square of the previous move
+        let b = b
+            .play_move("P3c-3d")
+            .play_move("P7g-7f")
+            .play_move("B2b-8h+");
+        assert_eq!(b.to_kanji("S7i-8h", Some("B2b-8h+")).unwrap(), "△同　銀");
+        assert_eq!(b.from_kanji("△同　銀", Some("B2b-8h+")).unwrap(), "S7i-8h");
+        assert_eq!(b.from_kanji("同銀", Some("B2b-8h+")).unwrap(), "S7i-8h");
+        assert!(b.from_kanji("同銀", None).is_err());
+        for mv in b.iter_moves() {
+            let kanji = b.to_kanji(&mv, Some("B2b-8h+")).unwrap();
+            assert_eq!(b.from_kanji(&kanji, Some("B2b-8h+")).unwrap(), mv);
+        }
+    }
+
+    #[test]
+    fn kanji_drops_and_promotion() {
+        let mut b = Board::empty();
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Silver,
+            promoted: false,
+            position: Some("5f".parse().unwrap()),
+        });
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Silver,
+            promoted: false,
+            position: None,
+        });
+        assert_eq!(b.to_kanji("S5f-5g+", None).unwrap(), "▲５三銀成");
+        assert_eq!(b.to_kanji("S5f-5g", None).unwrap(), "▲５三銀不成");
+        assert_eq!(b.to_kanji("S*5g", None).unwrap(), "▲５三銀打");
+        assert_eq!(b.to_kanji("S*1a", None).unwrap(), "▲９九銀");
+        assert_eq!(b.from_kanji("５三銀成", None).unwrap(), "S5f-5g+");
+        assert_eq!(b.from_kanji("５三銀不成", None).unwrap(), "S5f-5g");
+        assert_eq!(b.from_kanji("５三銀打", None).unwrap(), "S*5g");
+        assert_eq!(b.from_kanji("９九銀", None).unwrap(), "S*1a");
+        // the promotion is optional, and neither 成 nor 不成 is written
+        assert_eq!(
+            b.from_kanji("５三銀", None),
+            Err(InvalidMoveError::AmbiguousMoveError(vec![
+                "S5f-5g".to_string(),
+                "S5f-5g+".to_string()
+            ]))
+        );
+        for mv in b.iter_moves() {
+            let kanji = b.to_kanji(&mv, None).unwrap();
+            assert_eq!(b.from_kanji(&kanji, None).unwrap(), mv);
+        }
+    }
+}
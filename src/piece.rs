@@ -1,4 +1,5 @@
 use crate::position::Position;
+use crate::variant::*;
 use std::fmt;
 use std::str::FromStr;
 
@@ -6,6 +7,7 @@ use rand::seq::SliceRandom;
 use rand::thread_rng;
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Piece {
     pub color: Color,
     pub piecetype: PieceType,
@@ -14,6 +16,7 @@ pub struct Piece {
 }
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 ///though there is not really "color" in shogi, it is simpler
 ///to think and visualize with this
 pub enum Color {
@@ -31,6 +34,7 @@ impl Color {
 }
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceType {
     Pawn,
     King,
@@ -42,6 +46,23 @@ pub enum PieceType {
     Lance,
 }
 
+impl PieceType {
+    ///return the piece type of the given discriminant (`piecetype as usize`), None above 7
+    pub fn from_index(i: usize) -> Option<PieceType> {
+        Some(match i {
+            0 => PieceType::Pawn,
+            1 => PieceType::King,
+            2 => PieceType::Rook,
+            3 => PieceType::Bishop,
+            4 => PieceType::Gold,
+            5 => PieceType::Silver,
+            6 => PieceType::Knight,
+            7 => PieceType::Lance,
+            _ => return None,
+        })
+    }
+}
+
 impl Piece {
     ///I use the piece valuation from YSS 7.0 (1997), but scaled x100 to be integers.
     pub fn value(&self) -> i32 {
@@ -111,121 +132,16 @@ impl Piece {
         }
     }
 
-    ///get relatives moves of a piece: but do not check if piece have to 'jump' over other pieces
-    pub fn get_relative_moves(&self) -> Vec<(i16, i16)> {
-        //beware to check that moves  not within a column make not the piece's "wrap around" the board in case the move is next to the border
-        //
-
+    ///get relatives moves of a piece on a board of the variant: but do not check if piece have
+    ///to 'jump' over other pieces
+    pub fn get_relative_moves(&self, variant: &Variant) -> Vec<(i16, i16)> {
         //also, whether the piece has to jump over other piece is not checked!
-        let mut possibles_moves: Vec<(i16, i16)> = match &self.piecetype {
-            PieceType::Pawn => vec![(0, 1)],
-            PieceType::King => vec![
-                (1, 0),
-                (-1, 0),
-                (1, 1),
-                (-1, 1),
-                (0, 1),
-                (0, -1),
-                (1, -1),
-                (-1, -1),
-            ],
-            PieceType::Rook => vec![
-                (-8, 0),
-                (-7, 0),
-                (-6, 0),
-                (-5, 0),
-                (-4, 0),
-                (-3, 0),
-                (-2, 0),
-                (-1, 0),
-                (1, 0),
-                (2, 0),
-                (3, 0),
-                (4, 0),
-                (5, 0),
-                (6, 0),
-                (7, 0),
-                (8, 0),
-                (0, 1),
-                (0, -1),
-                (0, 2),
-                (0, -2),
-                (0, 3),
-                (0, -3),
-                (0, 4),
-                (0, -4),
-                (0, 5),
-                (0, -5),
-                (0, 6),
-                (0, -6),
-                (0, 7),
-                (0, -7),
-                (0, 8),
-                (0, -8),
-            ],
-            PieceType::Bishop => vec![
-                (1, 1),
-                (2, 2),
-                (3, 3),
-                (4, 4),
-                (5, 5),
-                (6, 6),
-                (7, 7),
-                (8, 8),
-                (-1, 1),
-                (-2, 2),
-                (-3, 3),
-                (-4, 4),
-                (-5, 5),
-                (-6, 6),
-                (-7, 7),
-                (-8, 8),
-                (1, -1),
-                (2, -2),
-                (3, -3),
-                (4, -4),
-                (5, -5),
-                (6, -6),
-                (7, -7),
-                (8, -8),
-                (-1, -1),
-                (-2, -2),
-                (-3, -3),
-                (-4, -4),
-                (-5, -5),
-                (-6, -6),
-                (-7, -7),
-                (-8, -8),
-            ],
-            PieceType::Gold => vec![(1, 0), (-1, 0), (-1, 1), (0, 1), (1, 1), (0, -1)],
-            PieceType::Silver => vec![(-1, 1), (0, 1), (1, 1), (1, -1), (-1, -1)],
-            PieceType::Knight => vec![(-1, 2), (1, 2)],
-            PieceType::Lance => vec![
-                (0, 1),
-                (0, 2),
-                (0, 3),
-                (0, 4),
-                (0, 5),
-                (0, 6),
-                (0, 7),
-                (0, 8),
-            ],
-        };
-
-        //manage promotion
-        if self.promoted {
-            if self.piecetype == PieceType::Pawn
-                || self.piecetype == PieceType::Knight
-                || self.piecetype == PieceType::Lance
-                || self.piecetype == PieceType::Silver
-            {
-                possibles_moves = vec![(1, 0), (-1, 0), (-1, 1), (0, 1), (1, 1), (0, -1)];
-            } else if self.piecetype == PieceType::Rook {
-                possibles_moves.append(&mut vec![(1, 1), (-1, 1), (1, -1), (-1, -1)]);
-            } else {
-                // bishop only one left, beacuse gold and king cannot be promoted
-                possibles_moves.append(&mut vec![(1, 0), (-1, 0), (0, 1), (0, -1)]);
-            }
+        let moves = variant
+            .piece_moves(self.piecetype, self.promoted)
+            .unwrap_or(variant.moves[self.piecetype as usize]);
+        let mut possibles_moves: Vec<(i16, i16)> = moves.steps.to_vec();
+        for &(x, y) in moves.slides {
+            possibles_moves.extend((1..variant.size as i16).map(|n| (n * x, n * y)));
         }
 
         let mut possibles_moves_colored;
@@ -240,17 +156,57 @@ impl Piece {
 
         possibles_moves_colored.shuffle(&mut thread_rng());
 
-        //make those board-aware (see first comment)
+        //make those board-aware: the piece must not leave the board (nor "wrap around" it)
+        let size = variant.size as i16;
+        let column = (self.position.unwrap().0 % 9) as i16;
+        let row = (self.position.unwrap().0 / 9) as i16;
         return possibles_moves_colored
             .into_iter()
-            .filter(|&mv| {
-                (self.position.unwrap().0 as i32 + mv.0 as i32) / 9
-                    == self.position.unwrap().0 as i32 / 9
-                    && (self.position.unwrap().0 as i32 + (mv.0 + 9 * mv.1) as i32) >= 0
-                    && (self.position.unwrap().0 as i32 + (mv.0 + 9 * mv.1) as i32) <= 80
-            })
+            .filter(|&(x, y)| (0..size).contains(&(column + x)) && (0..size).contains(&(row + y)))
             .collect();
     }
+
+    ///return the kanji used for the piece in japanese notation (歩, と, 成香...)
+    pub fn kanji(&self) -> &'static str {
+        match (self.piecetype, self.promoted) {
+            (PieceType::Pawn, false) => "歩",
+            (PieceType::Pawn, true) => "と",
+            (PieceType::Lance, false) => "香",
+            (PieceType::Lance, true) => "成香",
+            (PieceType::Knight, false) => "桂",
+            (PieceType::Knight, true) => "成桂",
+            (PieceType::Silver, false) => "銀",
+            (PieceType::Silver, true) => "成銀",
+            (PieceType::Gold, _) => "金",
+            (PieceType::Bishop, false) => "角",
+            (PieceType::Bishop, true) => "馬",
+            (PieceType::Rook, false) => "飛",
+            (PieceType::Rook, true) => "龍",
+            (PieceType::King, _) => "玉",
+        }
+    }
+
+    ///return the piece type and promotion of a kanji name (the reverse of Piece::kanji, also
+    ///accepting the usual variants: 王, 竜, 杏, 圭, 全)
+    pub fn from_kanji(s: &str) -> Option<(PieceType, bool)> {
+        Some(match s {
+            "歩" => (PieceType::Pawn, false),
+            "と" => (PieceType::Pawn, true),
+            "香" => (PieceType::Lance, false),
+            "成香" | "杏" => (PieceType::Lance, true),
+            "桂" => (PieceType::Knight, false),
+            "成桂" | "圭" => (PieceType::Knight, true),
+            "銀" => (PieceType::Silver, false),
+            "成銀" | "全" => (PieceType::Silver, true),
+            "金" => (PieceType::Gold, false),
+            "角" => (PieceType::Bishop, false),
+            "馬" => (PieceType::Bishop, true),
+            "飛" => (PieceType::Rook, false),
+            "龍" | "竜" => (PieceType::Rook, true),
+            "玉" | "王" => (PieceType::King, false),
+            _ => return None,
+        })
+    }
 }
 
 impl fmt::Display for PieceType {
@@ -320,6 +276,14 @@ mod test {
         p.position = None;
     }
 
+    #[test]
+    fn piecetype_index() {
+        for i in 0..8 {
+            assert_eq!(PieceType::from_index(i).unwrap() as usize, i);
+        }
+        assert_eq!(PieceType::from_index(8), None);
+    }
+
     #[test]
     fn move_check() {
         for i in 0..80 {
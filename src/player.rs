@@ -0,0 +1,211 @@
+use crate::ai;
+use crate::board::*;
+use crate::book::*;
+use crate::game::*;
+use crate::usi::*;
+use std::time::Duration;
+
+/// what a player can do when it is his turn
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PlayerAction {
+    Move(String),
+    Resign,
+    OfferDraw,
+    /// claim the win by impasse (see Game::declare_impasse), lost if the claim is wrong
+    DeclareImpasse,
+}
+
+/// Anything able to play a game: a human at the terminal, a bot, an external engine...
+///
+/// Any `FnMut(&Board) -> String` (such as ai::greedy) is a player, the "withdraw" move being
+/// understood as a resignation
+pub trait Player {
+    /// choose what to do in the current position of the game
+    fn choose_move(&mut self, game: &Game) -> PlayerAction;
+
+    /// told the time left to both players (indexed by color, None when unlimited) and the
+    /// increment before choosing a move, ignored by default
+    fn set_clocks(&mut self, _clocks: [Option<Duration>; 2], _increment: Duration) {}
+
+    /// answer a draw offer from the opponent, declined by default
+    fn accept_draw(&mut self, _game: &Game) -> bool {
+        false
+    }
+
+    /// name of the player, as shown in the results
+    fn name(&self) -> String {
+        String::from("player")
+    }
+
+    /// whether a human decides, whom an interface may let enter the moves itself (see tui::run)
+    fn is_human(&self) -> bool {
+        false
+    }
+}
+
+impl<F: FnMut(&Board) -> String> Player for F {
+    fn choose_move(&mut self, game: &Game) -> PlayerAction {
+        action_from_move(self(game.board()))
+    }
+}
+
+fn action_from_move(mv: String) -> PlayerAction {
+    if mv == "withdraw" {
+        PlayerAction::Resign
+    } else {
+        PlayerAction::Move(mv)
+    }
+}
+
+/// player typing his moves in the terminal (see ai::read_human_move)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Human;
+
+impl Player for Human {
+    fn choose_move(&mut self, game: &Game) -> PlayerAction {
+        // the game belongs to the caller, who may not allow taking back moves
+        match ai::read_human_move(game.board(), game.last_move()) {
+            ai::HumanInput::Move(mv) => PlayerAction::Move(mv),
+            ai::HumanInput::Resign => PlayerAction::Resign,
+            ai::HumanInput::Undo => unreachable!("read_human_move does not accept undo"),
+        }
+    }
+
+    fn name(&self) -> String {
+        String::from("human")
+    }
+
+    fn is_human(&self) -> bool {
+        true
+    }
+}
+
+/// the ai::greedy bot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Greedy;
+
+impl Player for Greedy {
+    fn choose_move(&mut self, game: &Game) -> PlayerAction {
+        PlayerAction::Move(ai::greedy(game.board()))
+    }
+
+    /// the bot accepts a draw when it is behind in material
+    fn accept_draw(&mut self, game: &Game) -> bool {
+        game.board().value() < 0
+    }
+
+    fn name(&self) -> String {
+        String::from("greedy")
+    }
+}
+
+/// the ai::random bot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Random;
+
+impl Player for Random {
+    fn choose_move(&mut self, game: &Game) -> PlayerAction {
+        action_from_move(ai::random(game.board()))
+    }
+
+    fn name(&self) -> String {
+        String::from("random")
+    }
+}
+
+/// play from an opening book as long as the position is known, then let another player decide
+pub struct BookPlayer<P: Player> {
+    pub book: Book,
+    pub fallback: P,
+}
+
+impl<P: Player> Player for BookPlayer<P> {
+    fn choose_move(&mut self, game: &Game) -> PlayerAction {
+        match self.book.pick(game.board()) {
+            Some(mv) => PlayerAction::Move(mv),
+            None => self.fallback.choose_move(game),
+        }
+    }
+
+    fn accept_draw(&mut self, game: &Game) -> bool {
+        self.fallback.accept_draw(game)
+    }
+
+    fn name(&self) -> String {
+        format!("{}+book", self.fallback.name())
+    }
+
+    fn is_human(&self) -> bool {
+        self.fallback.is_human()
+    }
+}
+
+/// return the player with the given name: "human", "greedy", "random", or "usi:COMMAND" to
+/// start an external USI engine (COMMAND being the program followed by its arguments). The bots
+/// and engines play from the book first if there is one (see BookPlayer), humans ignore it
+pub fn player_by_name(name: &str, book: Option<Book>) -> Option<Box<dyn Player>> {
+    match name {
+        "human" => Some(Box::new(Human)),
+        "greedy" => Some(with_book(Greedy, book)),
+        "random" => Some(with_book(Random, book)),
+        _ => {
+            let mut command = name.strip_prefix("usi:")?.split_whitespace();
+            let program = command.next()?;
+            let args: Vec<&str> = command.collect();
+            match UsiEngine::spawn(program, &args, &[]) {
+                Ok(engine) => Some(with_book(engine, book)),
+                Err(_) => None,
+            }
+        }
+    }
+}
+
+fn with_book<P: Player + 'static>(player: P, book: Option<Book>) -> Box<dyn Player> {
+    match book {
+        Some(book) => Box::new(BookPlayer {
+            book,
+            fallback: player,
+        }),
+        None => Box::new(player),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::player::*;
+
+    #[test]
+    fn closures_are_players() {
+        let g = Game::new();
+        let mut resign = |_: &Board| String::from("withdraw");
+        assert_eq!(resign.choose_move(&g), PlayerAction::Resign);
+        assert!(!resign.accept_draw(&g));
+
+        let mut book = Book::new();
+        book.add_game(&["P9c-9d"], 1);
+        let mut with_book = BookPlayer {
+            book,
+            fallback: Greedy,
+        };
+        assert_eq!(
+            with_book.choose_move(&g),
+            PlayerAction::Move(String::from("P9c-9d"))
+        );
+        assert_eq!(with_book.name(), "greedy+book");
+        assert!(player_by_name("greedy", None).is_some());
+        assert!(player_by_name("nobody", None).is_none());
+
+        let mut book = Book::new();
+        book.add_game(&["P9c-9d"], 1);
+        let mut random = player_by_name("random", Some(book.clone())).unwrap();
+        assert_eq!(random.name(), "random+book");
+        assert_eq!(
+            random.choose_move(&g),
+            PlayerAction::Move(String::from("P9c-9d"))
+        );
+        assert!(!random.is_human());
+        let human = player_by_name("human", Some(book)).unwrap();
+        assert_eq!(human.name(), "human");
+        assert!(human.is_human());
+    }
+}
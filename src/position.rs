@@ -2,7 +2,8 @@ use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub struct Position(pub u16); //but must range between 0 and 80
-                              //(81 position for 9*9 grid)
+                              //(81 position for 9*9 grid, smaller boards keep the same
+                              //numbering and only use the squares of their first rows and columns)
 
 ///Simple methods on Position
 impl Position {
@@ -12,6 +13,10 @@ impl Position {
     pub fn column(&self) -> char {
         return ((self.0 % 9) as u8 + 1 + '1' as u8) as char;
     }
+    ///whether the square is on a board of size*size squares
+    pub fn in_board(&self, size: u16) -> bool {
+        self.0 % 9 < size && self.0 / 9 < size
+    }
 }
 
 impl ToString for Position {
@@ -25,14 +30,15 @@ impl ToString for Position {
 impl FromStr for Position {
     type Err = String;
     fn from_str(s: &str) -> Result<Self, String> {
-        if s.len() != 2 {
+        let v: Vec<char> = s.chars().collect();
+        if v.len() != 2 {
             return Err(String::from("Invalid number of characters in string"));
         }
-        let v: Vec<&str> = s.split("").filter(|&m| m != "").take(2).collect();
         let (x, y) = (v[0], v[1]);
-        let p = (x.parse::<u8>().unwrap() as u8 - 1)
-            + (y.parse::<char>().unwrap() as u8 - 'a' as u8) * 9;
-        assert!(p <= 80);
+        if !('1'..='9').contains(&x) || !('a'..='i').contains(&y) {
+            return Err(format!("{} is not a square of the board", s));
+        }
+        let p = (x as u8 - b'1') + (y as u8 - b'a') * 9;
         Ok(Position(p as u16))
     }
 }
@@ -63,4 +69,19 @@ mod test {
             assert_eq!(p1, p0);
         }
     }
+
+    #[test]
+    fn invalid() {
+        for s in &["", "1", "1a1", "0a", "1j", "a1", "Pzz", "é1", "1é"] {
+            assert!(Position::from_str(s).is_err());
+        }
+    }
+
+    #[test]
+    fn smaller_boards() {
+        assert!(Position(80).in_board(9));
+        assert!(Position::from_str("5e").unwrap().in_board(5));
+        assert!(!Position::from_str("6a").unwrap().in_board(5));
+        assert!(!Position::from_str("1f").unwrap().in_board(5));
+    }
 }
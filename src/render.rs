@@ -0,0 +1,338 @@
+use crate::board::*;
+use crate::movement::*;
+use crate::piece::*;
+use crate::position::*;
+
+use std::fmt::Write;
+
+/// how pieces are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PieceStyle {
+    /// the letters of the western notation ("P", "+R"), with the coordinates used by moves
+    Letters,
+    /// one kanji per piece (歩, 龍...), drawn as in japanese books: first player at the bottom,
+    /// files from ９ to １ and ranks from 一 to 九
+    Kanji,
+}
+
+/// how the pieces of the second player (Black) are told apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpponentStyle {
+    /// written in red
+    Colored,
+    /// written in reverse video
+    Inverted,
+    /// preceded by a 'v', as in kifu diagrams (also used for the other styles without colors)
+    Marked,
+}
+
+/// options of Board::render
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderOptions {
+    pub style: PieceStyle,
+    pub opponent: OpponentStyle,
+    /// use ANSI escape codes, disable for logs and files
+    pub color: bool,
+    /// move (in the full notation) whose squares are highlighted
+    pub last_move: Option<String>,
+    /// highlight the king of the current player if it is in check
+    pub highlight_check: bool,
+    /// squares to point out, such as the destinations of a selected piece
+    pub targets: Vec<Position>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            style: PieceStyle::Letters,
+            opponent: OpponentStyle::Colored,
+            color: true,
+            last_move: None,
+            highlight_check: false,
+            targets: Vec::new(),
+        }
+    }
+}
+
+const RED: &str = "\x1b[0;31m";
+const REVERSE: &str = "\x1b[7m";
+const LAST_MOVE: &str = "\x1b[43m";
+const CHECK: &str = "\x1b[41m";
+const TARGET: &str = "\x1b[46m";
+const RESET: &str = "\x1b[0m";
+
+const KANJI_FILES: [&str; 9] = ["９", "８", "７", "６", "５", "４", "３", "２", "１"];
+const KANJI_NUMBERS: [&str; 10] = ["", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+
+///the piece written on 2 columns (kanji are twice as wide as letters)
+fn symbol(piece: &Piece, style: PieceStyle) -> String {
+    match style {
+        PieceStyle::Letters => format!(
+            "{}{}",
+            if piece.promoted { "+" } else { " " },
+            piece.piecetype
+        ),
+        PieceStyle::Kanji => kanji_symbol(piece).to_string(),
+    }
+}
+
+///the kanji of the piece on one character: promoted lance, knight and silver use their short
+///form (杏, 圭, 全) in diagrams
+pub(crate) fn kanji_symbol(piece: &Piece) -> &'static str {
+    match piece.kanji() {
+        "成香" => "杏",
+        "成桂" => "圭",
+        "成銀" => "全",
+        kanji => kanji,
+    }
+}
+
+///counts of pieces in hand, in kanji ("十二")
+pub(crate) fn kanji_number(n: usize) -> String {
+    if n < 10 {
+        KANJI_NUMBERS[n].to_string()
+    } else {
+        format!("十{}", KANJI_NUMBERS[n % 10])
+    }
+}
+
+///the pieces in hand of a player, as written in japanese diagrams ("飛　角　歩十二", or "なし")
+pub(crate) fn kanji_hand(b: &Board, color: Color) -> String {
+    let mut hand = String::new();
+    for piecetype in &[
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Gold,
+        PieceType::Silver,
+        PieceType::Knight,
+        PieceType::Lance,
+        PieceType::Pawn,
+    ] {
+        let pieces: Vec<&Piece> = b
+            .iter()
+            .filter(|p| p.position.is_none() && p.color == color && p.piecetype == *piecetype)
+            .collect();
+        if let Some(piece) = pieces.first() {
+            let count = match pieces.len() {
+                1 => String::new(),
+                n => kanji_number(n),
+            };
+            write!(hand, "{}{}　", piece.kanji(), count).unwrap();
+        }
+    }
+    if hand.is_empty() {
+        String::from("なし")
+    } else {
+        hand.trim_end_matches('　').to_string()
+    }
+}
+
+impl Board {
+    /// Draw the board according to the options (see RenderOptions)
+    pub fn render(&self, options: &RenderOptions) -> String {
+        // the last highlight of a square wins
+        let mut highlighted: Vec<(Position, &str, char)> = Vec::new();
+        if let Some(mv) = options
+            .last_move
+            .as_ref()
+            .and_then(|mv| mv.parse::<Movement>().ok())
+            .filter(|mv| !mv.withdraw && !mv.restart)
+        {
+            highlighted.extend(mv.start.map(|start| (start, LAST_MOVE, '*')));
+            highlighted.push((mv.end, LAST_MOVE, '*'));
+        }
+        if options.highlight_check && self.is_check() {
+            if let Some(king) = self
+                .iter_pieces(self.get_color())
+                .find(|p| p.piecetype == PieceType::King && p.position.is_some())
+            {
+                highlighted.push((king.position.unwrap(), CHECK, '!'));
+            }
+        }
+        highlighted.extend(options.targets.iter().map(|&target| (target, TARGET, '+')));
+        let opponent = if options.color {
+            options.opponent
+        } else {
+            OpponentStyle::Marked
+        };
+
+        let cell = |position: Position| -> String {
+            // without colors, highlighted squares are marked in the first column
+            let highlight = highlighted.iter().rev().find(|h| h.0 == position);
+            let mut cell = match highlight {
+                Some(&(_, _, mark)) if !options.color => mark.to_string(),
+                _ => String::from(" "),
+            };
+            match self.is_occupied_by(position) {
+                None => cell.push_str(match options.style {
+                    PieceStyle::Letters => "   ",
+                    PieceStyle::Kanji => " ・",
+                }),
+                Some(piece) if piece.color == Color::White => {
+                    cell.push(' ');
+                    cell.push_str(&symbol(&piece, options.style));
+                }
+                Some(piece) => match opponent {
+                    OpponentStyle::Marked => {
+                        cell.push('v');
+                        cell.push_str(&symbol(&piece, options.style));
+                    }
+                    OpponentStyle::Colored | OpponentStyle::Inverted => {
+                        let code = if opponent == OpponentStyle::Colored {
+                            RED
+                        } else {
+                            REVERSE
+                        };
+                        // the background of the highlight must be restored after the reset
+                        let background = highlight.map_or("", |h| h.1);
+                        write!(
+                            cell,
+                            " {}{}{}{}",
+                            code,
+                            symbol(&piece, options.style),
+                            RESET,
+                            background
+                        )
+                        .unwrap();
+                    }
+                },
+            }
+            match highlight {
+                Some(&(_, background, _)) if options.color => {
+                    format!("{}{}{}", background, cell, RESET)
+                }
+                _ => cell,
+            }
+        };
+
+        let size = self.rules.variant.size;
+        let border = format!("+{}+\n", "-".repeat(4 * size as usize));
+        let mut s = String::new();
+        match options.style {
+            PieceStyle::Letters => {
+                for column in (1..=size).rev() {
+                    write!(s, "   {}", column).unwrap();
+                }
+                s.push('\n');
+                s.push_str(&border);
+                for row in 0..size {
+                    s.push('|');
+                    for column in (0..size).rev() {
+                        s.push_str(&cell(Position(row * 9 + column)));
+                    }
+                    writeln!(s, "|{}", (b'a' + row as u8) as char).unwrap();
+                }
+                s.push_str(&border);
+                for color in &[Color::White, Color::Black] {
+                    let mut hand: Vec<(PieceType, usize)> = Vec::new();
+                    for piece in self
+                        .iter()
+                        .filter(|p| p.position.is_none() && p.color == *color)
+                    {
+                        match hand.iter_mut().find(|(t, _)| *t == piece.piecetype) {
+                            Some((_, count)) => *count += 1,
+                            None => hand.push((piece.piecetype, 1)),
+                        }
+                    }
+                    let hand: Vec<String> = hand
+                        .iter()
+                        .map(|(t, count)| match count {
+                            1 => t.to_string(),
+                            _ => format!("{} x{}", t, count),
+                        })
+                        .collect();
+                    writeln!(s, "{:?} reserve: {}", color, hand.join(", ")).unwrap();
+                }
+            }
+            PieceStyle::Kanji => {
+                writeln!(s, "後手の持駒：{}", kanji_hand(self, Color::Black)).unwrap();
+                let files = &KANJI_FILES[9 - size as usize..];
+                writeln!(s, "   {}", files.join("  ")).unwrap();
+                s.push_str(&border);
+                // first player at the bottom: the rank 一 is our row i, the file ９ our column 1
+                for rank in 1..=size {
+                    s.push('|');
+                    for column in 0..size {
+                        s.push_str(&cell(Position((size - rank) * 9 + column)));
+                    }
+                    writeln!(s, "|{}", KANJI_NUMBERS[rank as usize]).unwrap();
+                }
+                s.push_str(&border);
+                writeln!(s, "先手の持駒：{}", kanji_hand(self, Color::White)).unwrap();
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::render::*;
+
+    fn no_color(style: PieceStyle) -> RenderOptions {
+        RenderOptions {
+            style,
+            color: false,
+            ..RenderOptions::default()
+        }
+    }
+
+    #[test]
+    fn letters() {
+        let b = Board::new().play_move("P3c-3d");
+        let mut options = no_color(PieceStyle::Letters);
+        let s = b.render(&options);
+        assert!(!s.contains('\x1b'));
+        assert!(s.contains("| v L v N v S v G v K v G v S v N v L|i"));
+        assert!(s.contains("White reserve: \n"));
+
+        options.last_move = Some(String::from("P3c-3d"));
+        let s = b.render(&options);
+        assert!(s.contains("|   P   P   P   P   P   P*      P   P|c"));
+        options.targets = vec!["3e".parse().unwrap()];
+        assert!(b
+            .render(&options)
+            .contains("|                        +           |e"));
+        assert!(s.contains("*  P"));
+
+        let s = b.render(&RenderOptions::default());
+        assert!(s.contains("\x1b[0;31m L\x1b[0m"));
+    }
+
+    #[test]
+    fn kanji() {
+        let b = Board::new()
+            .play_move("P3c-3d")
+            .play_move("P7g-7f")
+            .play_move("B2b-8h+");
+        let s = b.render(&no_color(PieceStyle::Kanji));
+        assert!(s.starts_with("後手の持駒：なし\n"));
+        assert!(s.contains("| v香 v桂 v銀 v金 v玉 v金 v銀 v桂 v香|一"));
+        assert!(s.contains("|  ・ v飛  ・  ・  ・  ・  ・  馬  ・|二"));
+        assert!(s.ends_with("先手の持駒：角\n"));
+        assert_eq!(kanji_number(12), "十二");
+    }
+
+    #[test]
+    fn check() {
+        let mut b = Board::empty();
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::King,
+            promoted: false,
+            position: Some("5a".parse().unwrap()),
+        });
+        b.add_piece(Piece {
+            color: Color::Black,
+            piecetype: PieceType::Rook,
+            promoted: true,
+            position: Some("5e".parse().unwrap()),
+        });
+        let mut options = no_color(PieceStyle::Kanji);
+        assert!(!b.render(&options).contains('!'));
+        options.highlight_check = true;
+        assert!(b.render(&options).contains("! 玉"));
+        options.color = true;
+        assert!(b.render(&options).contains("\x1b[41m  玉\x1b[0m"));
+    }
+}
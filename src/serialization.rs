@@ -0,0 +1,167 @@
+use crate::board::*;
+use crate::movement::*;
+use crate::position::*;
+use crate::variant::*;
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+
+// pieces, colors, rules and errors derive their implementations, the types below are written as
+// strings instead, as they would be typed by a player
+
+/// squares are written as in moves: "7g"
+impl Serialize for Position {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Position {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// moves are written in the full notation: "P7g-7f", "B*5e", "withdraw"...
+impl Serialize for Movement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Movement {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// variants are written as their name: "minishogi". Only the variants known by the crate (see
+/// Variant::ALL) can be read back
+impl Serialize for Variant {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name)
+    }
+}
+
+impl<'de> Deserialize<'de> for &'static Variant {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Variant::by_name(&s).ok_or_else(|| de::Error::custom(format!("unknown variant {}", s)))
+    }
+}
+
+/// what a board is written as: its SFEN (without the move number) and its rules
+#[derive(Serialize, Deserialize)]
+struct BoardFields {
+    sfen: String,
+    #[serde(default)]
+    rules: Option<Rules>,
+}
+
+/// boards are written as {"sfen": "lnsgkgsnl/1r5b1/... b -", "rules": {...}}
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let sfen = self.to_sfen(1);
+        BoardFields {
+            sfen: sfen.strip_suffix(" 1").unwrap_or(&sfen).to_string(),
+            rules: Some(self.rules),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let fields = BoardFields::deserialize(deserializer)?;
+        let mut b = Board::from_sfen(&fields.sfen).map_err(de::Error::custom)?;
+        // the size of the board comes from the SFEN, rules written without a variant (or with
+        // one of another size) keep the variant found from the SFEN
+        if let Some(rules) = fields.rules {
+            b.rules = if rules.variant.size == b.rules.variant.size {
+                rules
+            } else {
+                Rules {
+                    variant: b.rules.variant,
+                    ..rules
+                }
+            };
+        }
+        Ok(b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::invalidmoveerror::*;
+    use crate::piece::*;
+    use crate::serialization::*;
+
+    #[test]
+    fn compact_forms() {
+        let square: Position = "7g".parse().unwrap();
+        assert_eq!(serde_json::to_string(&square).unwrap(), "\"7g\"");
+        assert_eq!(serde_json::from_str::<Position>("\"7g\"").unwrap(), square);
+        assert!(serde_json::from_str::<Position>("\"0z\"").is_err());
+
+        let moves: Vec<Movement> = vec!["P7g-7f".parse().unwrap(), "B*5e".parse().unwrap()];
+        let json = serde_json::to_string(&moves).unwrap();
+        assert_eq!(json, "[\"P7g-7f\",\"B*5e\"]");
+        assert_eq!(serde_json::from_str::<Vec<Movement>>(&json).unwrap(), moves);
+
+        let piece = Piece {
+            color: Color::Black,
+            piecetype: PieceType::Rook,
+            promoted: true,
+            position: Some(square),
+        };
+        let json = serde_json::to_string(&piece).unwrap();
+        assert_eq!(
+            json,
+            "{\"color\":\"Black\",\"piecetype\":\"Rook\",\"promoted\":true,\"position\":\"7g\"}"
+        );
+        assert_eq!(serde_json::from_str::<Piece>(&json).unwrap(), piece);
+
+        let error = Board::new().check_move("P7c-7e").unwrap_err();
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(
+            serde_json::from_str::<InvalidMoveError>(&json).unwrap(),
+            error
+        );
+    }
+
+    #[test]
+    fn boards() {
+        let mut b = Board::new().play_move("P3c-3d").play_move("P7g-7f");
+        b.rules.can_restart = true;
+        let json = serde_json::to_string(&b).unwrap();
+        assert_eq!(
+            json,
+            "{\"sfen\":\"lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b -\",\
+             \"rules\":{\"can_uncover_check\":false,\"can_restart\":true,\"variant\":\"shogi\",\
+             \"win_condition\":\"Checkmate\"}}"
+        );
+        let back: Board = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.to_sfen(3), b.to_sfen(3));
+        assert_eq!(back.rules, b.rules);
+        assert_eq!(back.get_color(), b.get_color());
+
+        // the rules may be left out
+        let b: Board =
+            serde_json::from_str(&format!("{{\"sfen\":\"{}\"}}", crate::sfen::START_SFEN)).unwrap();
+        assert_eq!(b.rules, Rules::default());
+        let b: Board = serde_json::from_str(
+            "{\"sfen\":\"rbsgk/4p/5/P4/KGSBR b -\",\"rules\":{\"can_restart\":true}}",
+        )
+        .unwrap();
+        assert_eq!(b.rules.variant, &Variant::MINISHOGI);
+        assert!(serde_json::from_str::<Board>(
+            "{\"sfen\":\"9/9/9/9/9/9/9/9/9 b -\",\"rules\":{\"variant\":\"chess\"}}"
+        )
+        .is_err());
+        assert!(b.rules.can_restart);
+        assert!(serde_json::from_str::<Board>("{\"sfen\":\"9/9 b -\"}").is_err());
+    }
+}
@@ -0,0 +1,478 @@
+use crate::game::*;
+use crate::kifu::*;
+use crate::matchrunner::*;
+use crate::piece::*;
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// time given to a client to send its name after connecting
+const JOIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerSettings {
+    /// time available for the whole game (per player), None for unlimited
+    pub time_per_game: Option<Duration>,
+    /// time added after each move
+    pub increment: Duration,
+    /// number of moves after which the game is declared a draw
+    pub max_moves: usize,
+    /// number of occurrences of the same position after which the game is declared a draw
+    pub repetitions: usize,
+    /// directory where the finished games are written (as KIF), None to keep no record
+    pub kifu_dir: Option<PathBuf>,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            time_per_game: Some(Duration::from_secs(15 * 60)),
+            increment: Duration::from_secs(0),
+            max_moves: 256,
+            repetitions: 4,
+            kifu_dir: None,
+        }
+    }
+}
+
+/// a client that has given its name and waits for (or plays) a game
+#[derive(Debug)]
+pub struct Seat {
+    pub name: String,
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Seat {
+    /// read the "NAME <name>" line the clients start with
+    pub fn join(stream: TcpStream) -> io::Result<Seat> {
+        stream.set_read_timeout(Some(JOIN_TIMEOUT))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        stream.set_read_timeout(None)?;
+        let mut seat = Seat {
+            name: String::new(),
+            stream,
+            reader,
+        };
+        match line.trim().strip_prefix("NAME ") {
+            Some(name) if !name.trim().is_empty() => {
+                seat.name = name.trim().to_string();
+                send(&seat.stream, "WAITING");
+                Ok(seat)
+            }
+            _ => {
+                send(&seat.stream, "ERROR expected NAME <name>");
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the client did not give its name",
+                ))
+            }
+        }
+    }
+
+    ///whether the client is still there, without consuming what it sent
+    fn is_connected(&self) -> bool {
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let connected = match self.stream.peek(&mut [0]) {
+            Ok(0) => false,
+            Ok(_) => true,
+            Err(e) => e.kind() == io::ErrorKind::WouldBlock,
+        };
+        connected && self.stream.set_nonblocking(false).is_ok()
+    }
+}
+
+///send a line to a client, a failure being noticed by its reader as a disconnection
+fn send(mut stream: &TcpStream, line: &str) {
+    let _ = writeln!(stream, "{}", line);
+}
+
+///milliseconds left on a clock, 0 when unlimited
+fn millis(clock: Option<Duration>) -> u128 {
+    clock.map_or(0, |clock| clock.as_millis())
+}
+
+/// Play a game between two clients, White being the first one, and return its outcome along
+/// with the record of the game.
+///
+/// The server sends to both clients:
+/// - "START <color> <opponent> <time ms> <increment ms>" when the game starts (time 0 when
+///   unlimited)
+/// - "TURN <color> <white ms> <black ms>" when a player has to move, with the clocks
+/// - "MOVED <move>" after each move, in the full notation
+/// - "END <winner or Draw> <termination>" at the end ("END Black Resignation")
+///
+/// and to the player concerned "ILLEGAL <reason>" for an illegal move (the clock keeps running
+/// until a legal one), "ERROR <message>" for anything else. Clients send "MOVE <move>" (in any
+/// notation Board::resolve_move understands) and "RESIGN", the latter at any time
+pub fn host_game(seats: [Seat; 2], settings: &ServerSettings) -> (GameOutcome, Kifu) {
+    let [white, black] = seats;
+    let mut kifu = Kifu::new(Game::new());
+    kifu.white = Some(white.name.clone());
+    kifu.black = Some(black.name.clone());
+    for (seat, color, opponent) in [(&white, "White", &black), (&black, "Black", &white)] {
+        send(
+            &seat.stream,
+            &format!(
+                "START {} {} {} {}",
+                color,
+                opponent.name,
+                millis(settings.time_per_game),
+                settings.increment.as_millis()
+            ),
+        );
+    }
+
+    // one thread per client reads its lines, None meaning a disconnection
+    let (sender, receiver) = mpsc::channel();
+    // the streams are indexed by color, as the clocks
+    let mut streams = Vec::new();
+    for (color, seat) in [(Color::Black, black), (Color::White, white)] {
+        let sender = sender.clone();
+        let mut reader = seat.reader;
+        thread::spawn(move || loop {
+            let mut line = String::new();
+            let event = match reader.read_line(&mut line) {
+                Ok(n) if n > 0 => Some(line.trim().to_string()),
+                _ => None,
+            };
+            let disconnected = event.is_none();
+            if sender.send((color, event)).is_err() || disconnected {
+                break;
+            }
+        });
+        streams.push(seat.stream);
+    }
+    let send_both = |line: &str| streams.iter().for_each(|stream| send(stream, line));
+
+    let mut clocks = [settings.time_per_game, settings.time_per_game];
+    let outcome = 'game: loop {
+        let game = &mut kifu.game;
+        let moves = game.moves().len();
+        let color = game.board().get_color();
+        let mut opponent = color;
+        opponent.invert();
+        let outcome = |winner, reason| GameOutcome {
+            result: GameResult { winner, reason },
+            moves,
+        };
+        if let Some(condition) = game.board().lost_by() {
+            break outcome(Some(opponent), condition.into());
+        }
+        if moves >= settings.max_moves {
+            break outcome(None, Termination::MaxMoves);
+        }
+        if game.repetitions() >= settings.repetitions {
+            break GameOutcome {
+                result: game.sennichite(),
+                moves,
+            };
+        }
+        send_both(&format!(
+            "TURN {:?} {} {}",
+            color,
+            millis(clocks[Color::White as usize]),
+            millis(clocks[Color::Black as usize])
+        ));
+
+        let start_time = Instant::now();
+        let mv = loop {
+            let (from, line) = match clocks[color as usize] {
+                Some(clock) => {
+                    let left = clock.checked_sub(start_time.elapsed()).unwrap_or_default();
+                    match receiver.recv_timeout(left) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => {
+                            break 'game outcome(Some(opponent), Termination::TimeForfeit)
+                        }
+                        Err(RecvTimeoutError::Disconnected) => unreachable!(),
+                    }
+                }
+                None => receiver.recv().unwrap(),
+            };
+            let stream = &streams[from as usize];
+            let mut winner = from;
+            winner.invert();
+            let line = match line {
+                Some(line) => line,
+                None => break 'game outcome(Some(winner), Termination::Disconnection),
+            };
+            match line.split_once(' ').unwrap_or((line.as_str(), "")) {
+                ("RESIGN", _) => break 'game outcome(Some(winner), Termination::Resignation),
+                ("MOVE", _) if from != color => send(stream, "ERROR not your turn"),
+                ("MOVE", mv) => match game.board().resolve_move(mv.trim()) {
+                    Ok(mv) => break mv,
+                    Err(error) => send(stream, &format!("ILLEGAL {}", error)),
+                },
+                _ => send(stream, &format!("ERROR unknown command: {}", line)),
+            }
+        };
+
+        if let Some(clock) = clocks[color as usize] {
+            match clock.checked_sub(start_time.elapsed()) {
+                Some(left) => clocks[color as usize] = Some(left + settings.increment),
+                None => break outcome(Some(opponent), Termination::TimeForfeit),
+            }
+        }
+        // "withdraw" and "restart" are not moves here, RESIGN ends the game
+        if mv == "withdraw" {
+            break outcome(Some(opponent), Termination::Resignation);
+        }
+        // resolve_move only returns legal moves, Game::play checks it again anyway
+        if mv == "restart" || game.play(&mv).is_err() {
+            break outcome(Some(opponent), Termination::IllegalMove);
+        }
+        send_both(&format!("MOVED {}", mv));
+    };
+
+    kifu.result = Some(outcome.result);
+    send_both(&format!(
+        "END {} {:?}",
+        outcome
+            .result
+            .winner
+            .map_or(String::from("Draw"), |winner| format!("{:?}", winner)),
+        outcome.result.reason
+    ));
+    for stream in &streams {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+    (outcome, kifu)
+}
+
+///a name usable in a file name
+fn file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// write the record of a finished game in the directory of the settings, and return its path
+pub fn save_game(kifu: &Kifu, settings: &ServerSettings) -> Result<Option<PathBuf>, KifuError> {
+    let dir = match &settings.kifu_dir {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = dir.join(format!(
+        "{}-{}-{}.kif",
+        time,
+        file_name(kifu.white.as_deref().unwrap_or("white")),
+        file_name(kifu.black.as_deref().unwrap_or("black"))
+    ));
+    kifu.save(&path)?;
+    Ok(Some(path))
+}
+
+/// Accept clients and pair them in the order they arrive, the first one of a pair playing White.
+/// Each game runs in its own thread (see host_game), and its record is written when it ends
+/// (see save_game). `report` is called with the names of the players and the outcome
+pub fn serve(
+    listener: TcpListener,
+    settings: ServerSettings,
+    report: fn(&str, &str, &GameOutcome),
+) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel::<Seat>();
+    thread::spawn(move || {
+        let mut waiting: Option<Seat> = None;
+        for seat in receiver {
+            match waiting.take() {
+                Some(first) if first.is_connected() => {
+                    let settings = settings.clone();
+                    thread::spawn(move || {
+                        let (outcome, kifu) = host_game([first, seat], &settings);
+                        report(
+                            kifu.white.as_deref().unwrap_or_default(),
+                            kifu.black.as_deref().unwrap_or_default(),
+                            &outcome,
+                        );
+                        if let Err(e) = save_game(&kifu, &settings) {
+                            eprintln!("cannot write the record of the game: {}", e);
+                        }
+                    });
+                }
+                _ => waiting = Some(seat),
+            }
+        }
+    });
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let sender = sender.clone();
+        thread::spawn(move || {
+            if let Ok(seat) = Seat::join(stream) {
+                let _ = sender.send(seat);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::server::*;
+    use std::fs;
+
+    struct Client {
+        stream: TcpStream,
+        reader: BufReader<TcpStream>,
+    }
+
+    impl Client {
+        fn connect(address: std::net::SocketAddr, name: &str) -> Client {
+            let stream = TcpStream::connect(address).unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            let mut client = Client { stream, reader };
+            client.send(&format!("NAME {}", name));
+            client
+        }
+
+        fn send(&mut self, line: &str) {
+            writeln!(self.stream, "{}", line).unwrap();
+        }
+
+        fn read(&mut self) -> String {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            line.trim().to_string()
+        }
+
+        ///read lines until one starts with the prefix
+        fn expect(&mut self, prefix: &str) -> String {
+            loop {
+                let line = self.read();
+                assert!(!line.is_empty(), "connection closed waiting for {}", prefix);
+                if line.starts_with(prefix) {
+                    return line;
+                }
+            }
+        }
+    }
+
+    ///two seats, as accepted by the server, and their clients
+    fn pair() -> ([Seat; 2], Client, Client) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let white = Client::connect(address, "alice");
+        let first = Seat::join(listener.accept().unwrap().0).unwrap();
+        let black = Client::connect(address, "bob");
+        let second = Seat::join(listener.accept().unwrap().0).unwrap();
+        ([first, second], white, black)
+    }
+
+    #[test]
+    fn relay_and_record() {
+        let dir = std::env::temp_dir().join(format!("shogai-server-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let settings = ServerSettings {
+            kifu_dir: Some(dir.clone()),
+            ..ServerSettings::default()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || serve(listener, settings, |_, _, _| {}));
+
+        let mut white = Client::connect(address, "alice");
+        assert_eq!(white.read(), "WAITING");
+        let mut black = Client::connect(address, "bob smith");
+        assert_eq!(white.expect("START"), "START White bob smith 900000 0");
+        assert_eq!(black.expect("START"), "START Black alice 900000 0");
+        assert_eq!(white.expect("TURN"), "TURN White 900000 900000");
+
+        black.send("MOVE P7g-7f");
+        assert_eq!(black.expect("ERROR"), "ERROR not your turn");
+        white.send("MOVE P-3d");
+        assert_eq!(black.expect("MOVED"), "MOVED P3c-3d");
+        assert_eq!(white.expect("MOVED"), "MOVED P3c-3d");
+        black.send("MOVE P7g-7e");
+        assert!(black.expect("ILLEGAL").starts_with("ILLEGAL "));
+        black.send("MOVE P7g-7f");
+        assert_eq!(white.expect("MOVED"), "MOVED P7g-7f");
+        white.send("RESIGN");
+        assert_eq!(white.expect("END"), "END Black Resignation");
+        assert_eq!(black.expect("END"), "END Black Resignation");
+
+        // the record is written once the game is over
+        let mut record = None;
+        for _ in 0..100 {
+            let path = fs::read_dir(&dir)
+                .unwrap()
+                .next()
+                .map(|f| f.unwrap().path());
+            if let Some(kifu) = path.as_ref().and_then(|path| Kifu::load(path).ok()) {
+                record = Some((path.unwrap(), kifu));
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        let (path, kifu) = record.unwrap();
+        assert!(path.to_string_lossy().ends_with("-alice-bob_smith.kif"));
+        assert_eq!(kifu.game.moves(), ["P3c-3d", "P7g-7f"]);
+        assert_eq!(kifu.black.as_deref(), Some("bob smith"));
+        assert_eq!(
+            kifu.result,
+            Some(GameResult::win(Color::Black, Termination::Resignation))
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn clocks_and_disconnections() {
+        let settings = ServerSettings {
+            time_per_game: Some(Duration::from_millis(200)),
+            ..ServerSettings::default()
+        };
+        let (seats, mut white, _black) = pair();
+        let game = thread::spawn(move || host_game(seats, &settings));
+        white.send("MOVE P3c-3d");
+        let (outcome, kifu) = game.join().unwrap();
+        assert_eq!(outcome.result.winner, Some(Color::White));
+        assert_eq!(outcome.result.reason, Termination::TimeForfeit);
+        assert_eq!(kifu.game.moves().len(), 1);
+        assert_eq!(kifu.result, Some(outcome.result));
+        assert_eq!(white.expect("END"), "END White TimeForfeit");
+
+        let (seats, white, mut black) = pair();
+        let game = thread::spawn(move || host_game(seats, &ServerSettings::default()));
+        black.expect("TURN");
+        drop(white);
+        let (outcome, _) = game.join().unwrap();
+        assert_eq!(outcome.result.winner, Some(Color::Black));
+        assert_eq!(outcome.result.reason, Termination::Disconnection);
+        assert_eq!(black.expect("END"), "END Black Disconnection");
+    }
+
+    #[test]
+    fn special_moves() {
+        let (seats, mut white, _black) = pair();
+        let game = thread::spawn(move || host_game(seats, &ServerSettings::default()));
+        white.send("MOVE withdraw");
+        let (outcome, kifu) = game.join().unwrap();
+        assert_eq!(
+            outcome.result,
+            GameResult::win(Color::Black, Termination::Resignation)
+        );
+        assert!(kifu.game.moves().is_empty());
+        assert_eq!(white.expect("END"), "END Black Resignation");
+
+        let (seats, mut white, _black) = pair();
+        let game = thread::spawn(move || host_game(seats, &ServerSettings::default()));
+        // restarting is not allowed by the rules of the server
+        white.send("MOVE restart");
+        assert!(white.expect("ILLEGAL").starts_with("ILLEGAL "));
+        white.send("RESIGN");
+        let (outcome, _) = game.join().unwrap();
+        assert_eq!(outcome.result.reason, Termination::Resignation);
+    }
+}
@@ -0,0 +1,253 @@
+use crate::board::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::variant::*;
+
+/// SFEN of the regular starting position (what Board::new() returns)
+pub const START_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+/// SFEN of the minishogi starting position (what Board::minishogi() returns)
+pub const MINISHOGI_SFEN: &str = "rbsgk/4p/5/P4/KGSBR b - 1";
+
+/// order in which the reserves are written
+const RESERVE_ORDER: [PieceType; 7] = [
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+];
+
+///the first player (White here) is the one called "sente" in SFEN, and it sits at the bottom of
+///the diagram: squares have to be rotated to go from one convention to the other
+fn square_of(sfen_rank: u16, sfen_file: u16, size: u16) -> Position {
+    Position((size - 1 - sfen_rank) * 9 + (size - sfen_file))
+}
+
+fn piece_letter(piece: &Piece) -> String {
+    let letter = piece.piecetype.to_string();
+    if piece.color == Color::White {
+        letter
+    } else {
+        letter.to_lowercase()
+    }
+}
+
+impl Board {
+    /// return the SFEN (as used by USI engines) of the board, with the given move number
+    pub fn to_sfen(&self, move_number: u32) -> String {
+        let size = self.rules.variant.size;
+        let mut ranks: Vec<String> = Vec::new();
+        for rank in 0..size {
+            let mut line = String::new();
+            let mut empty = 0;
+            for file in (1..=size).rev() {
+                if let Some(piece) = self.is_occupied_by(square_of(rank, file, size)) {
+                    if empty > 0 {
+                        line.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    if piece.promoted {
+                        line.push('+');
+                    }
+                    line.push_str(&piece_letter(&piece));
+                } else {
+                    empty += 1;
+                }
+            }
+            if empty > 0 {
+                line.push_str(&empty.to_string());
+            }
+            ranks.push(line);
+        }
+
+        let mut reserves = String::new();
+        for &color in &[Color::White, Color::Black] {
+            for &piecetype in RESERVE_ORDER.iter() {
+                let count = self
+                    .iter()
+                    .filter(|p| {
+                        p.position.is_none() && p.color == color && p.piecetype == piecetype
+                    })
+                    .count();
+                if count > 1 {
+                    reserves.push_str(&count.to_string());
+                }
+                if count > 0 {
+                    reserves.push_str(&piece_letter(&Piece {
+                        color,
+                        piecetype,
+                        promoted: false,
+                        position: None,
+                    }));
+                }
+            }
+        }
+        if reserves.is_empty() {
+            reserves.push('-');
+        }
+
+        let turn = if self.get_color() == Color::White {
+            "b"
+        } else {
+            "w"
+        };
+        format!("{} {} {} {}", ranks.join("/"), turn, reserves, move_number)
+    }
+
+    /// create a board from a SFEN. The move number is optional and ignored. The variant is the
+    /// first known one (see Variant::ALL) of the board's size: minishogi for 5 ranks...
+    pub fn from_sfen(sfen: &str) -> Result<Board, String> {
+        let fields: Vec<&str> = sfen.split_whitespace().collect();
+        if fields.len() != 3 && fields.len() != 4 {
+            return Err(format!("a SFEN has 3 or 4 fields: {}", sfen));
+        }
+        let mut b = Board::empty();
+
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        b.rules.variant = match Variant::ALL.iter().find(|v| v.size as usize == ranks.len()) {
+            Some(variant) => variant,
+            None => {
+                return Err(format!(
+                    "no variant has {} ranks: {}",
+                    ranks.len(),
+                    fields[0]
+                ))
+            }
+        };
+        let size = b.rules.variant.size as u32;
+        for (rank, line) in ranks.iter().enumerate() {
+            let mut file = size;
+            let mut promoted = false;
+            for c in line.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    if promoted || empty == 0 || empty > file {
+                        return Err(format!("invalid rank in SFEN: {}", line));
+                    }
+                    file -= empty;
+                } else if c == '+' {
+                    promoted = true;
+                } else {
+                    if file == 0 {
+                        return Err(format!("too many squares in SFEN rank: {}", line));
+                    }
+                    let mut piece = parse_piece(c, b.rules.variant)?;
+                    piece.promoted = promoted;
+                    piece.position = Some(square_of(rank as u16, file as u16, size as u16));
+                    b.try_add_piece(piece)?;
+                    file -= 1;
+                    promoted = false;
+                }
+            }
+            if file != 0 || promoted {
+                return Err(format!("invalid rank in SFEN: {}", line));
+            }
+        }
+
+        b.turn = match fields[1] {
+            "b" => Color::White,
+            "w" => Color::Black,
+            other => return Err(format!("invalid side to move in SFEN: {}", other)),
+        };
+
+        if fields[2] != "-" {
+            let mut count: u32 = 0;
+            let invalid = || format!("invalid reserves in SFEN: {}", fields[2]);
+            for c in fields[2].chars() {
+                if let Some(digit) = c.to_digit(10) {
+                    count = count
+                        .checked_mul(10)
+                        .and_then(|count| count.checked_add(digit))
+                        .ok_or_else(invalid)?;
+                } else {
+                    let piece = parse_piece(c, b.rules.variant)?;
+                    for _ in 0..count.max(1) {
+                        b.try_add_piece(piece)?;
+                    }
+                    count = 0;
+                }
+            }
+            if count != 0 {
+                return Err(invalid());
+            }
+        }
+        Ok(b)
+    }
+}
+
+fn parse_piece(c: char, variant: &Variant) -> Result<Piece, String> {
+    let piecetype: PieceType = c.to_ascii_uppercase().to_string().parse()?;
+    if !variant.pieces.contains(&piecetype) {
+        return Err(format!("there is no {:?} in {}", piecetype, variant.name));
+    }
+    let color = if c.is_ascii_uppercase() {
+        Color::White
+    } else {
+        Color::Black
+    };
+    Ok(Piece {
+        color,
+        piecetype,
+        promoted: false,
+        position: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sfen::*;
+
+    #[test]
+    fn start_position() {
+        assert_eq!(Board::new().to_sfen(1), START_SFEN);
+        assert_eq!(
+            Board::from_sfen(START_SFEN).unwrap().hash_key(),
+            Board::new().hash_key()
+        );
+    }
+
+    #[test]
+    fn convertback() {
+        let b = Board::new()
+            .play_move("P3c-3d")
+            .play_move("P7g-7f")
+            .play_move("B2b-8h+")
+            .play_move("S7i-8h");
+        let sfen = b.to_sfen(5);
+        assert_eq!(
+            sfen,
+            "lnsgkg1nl/1r5s1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL b Bb 5"
+        );
+        let b2 = Board::from_sfen(&sfen).unwrap();
+        assert_eq!(b2.hash_key(), b.hash_key());
+        assert_eq!(b2.to_sfen(5), sfen);
+    }
+
+    #[test]
+    fn minishogi() {
+        assert_eq!(Board::minishogi().to_sfen(1), MINISHOGI_SFEN);
+        let b = Board::from_sfen(MINISHOGI_SFEN).unwrap();
+        assert_eq!(b.rules.variant, &Variant::MINISHOGI);
+        assert_eq!(b.hash_key(), Board::minishogi().hash_key());
+        assert!(Board::from_sfen("5/5/5/5/6 b - 1").is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(Board::from_sfen("").is_err());
+        assert!(Board::from_sfen("9/9/9/9/9/9/9/9 b - 1").is_err());
+        assert!(Board::from_sfen("9/9/9/9/9/9/9/9/10 b - 1").is_err());
+        assert!(Board::from_sfen("9/9/9/9/9/9/9/9/8X b - 1").is_err());
+        assert!(Board::from_sfen("9/9/9/9/9/9/9/9/9 x - 1").is_err());
+        assert!(Board::from_sfen("9/9/9/9/9/9/9/9/9 b 2 1").is_err());
+        // more pieces than a board holds, or than a number holds
+        assert!(Board::from_sfen("9/9/9/9/9/9/9/9/9 b 40P 1").is_err());
+        assert!(Board::from_sfen("9/9/9/9/9/9/9/9/9 b 20P20p 1").is_ok());
+        assert!(Board::from_sfen("9/9/9/9/9/9/9/9/9 b 99999999999P 1").is_err());
+        assert!(
+            Board::from_sfen("GGGGGGGGG/GGGGGGGGG/GGGGGGGGG/GGGGGGGGG/9/9/9/9/9 b - 1").is_err()
+        );
+    }
+}
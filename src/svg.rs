@@ -0,0 +1,370 @@
+use crate::board::*;
+use crate::piece::*;
+use crate::position::*;
+use crate::render::*;
+
+use std::fmt::Write;
+
+/// options of Board::to_svg
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SvgOptions {
+    /// letters with our coordinates, or kanji drawn as in japanese books (see PieceStyle)
+    pub style: PieceStyle,
+    /// size of a square, in pixels
+    pub square_size: u32,
+    /// squares filled with a light color, under the pieces
+    pub highlighted: Vec<Position>,
+    /// arrows drawn from a square to another one, over the pieces
+    pub arrows: Vec<(Position, Position)>,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            style: PieceStyle::Letters,
+            square_size: 40,
+            highlighted: Vec::new(),
+            arrows: Vec::new(),
+        }
+    }
+}
+
+const HAND_ORDER: [PieceType; 7] = [
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Gold,
+    PieceType::Silver,
+    PieceType::Knight,
+    PieceType::Lance,
+    PieceType::Pawn,
+];
+
+fn label(piece: &Piece, style: PieceStyle) -> String {
+    match style {
+        PieceStyle::Letters if piece.promoted => format!("+{}", piece.piecetype),
+        PieceStyle::Letters => piece.piecetype.to_string(),
+        PieceStyle::Kanji => kanji_symbol(piece).to_string(),
+    }
+}
+
+/// geometry of the diagram, in pixels
+struct Layout {
+    style: PieceStyle,
+    size: f64,
+    left: f64,
+    top: f64,
+    /// number of columns and rows of the board
+    squares: u16,
+}
+
+impl Layout {
+    ///column and row of the square on the diagram
+    fn cell(&self, p: Position) -> (u16, u16) {
+        match self.style {
+            // our coordinates: column 9 on the left, row a at the top
+            PieceStyle::Letters => (self.squares - 1 - p.0 % 9, p.0 / 9),
+            // first player at the bottom, file ９ (our column 1) on the left
+            PieceStyle::Kanji => (p.0 % 9, self.squares - 1 - p.0 / 9),
+        }
+    }
+
+    fn center(&self, p: Position) -> (f64, f64) {
+        let (x, y) = self.cell(p);
+        (
+            self.left + (x as f64 + 0.5) * self.size,
+            self.top + (y as f64 + 0.5) * self.size,
+        )
+    }
+
+    ///the player whose pieces point up
+    fn bottom(&self) -> Color {
+        match self.style {
+            PieceStyle::Letters => Color::Black,
+            PieceStyle::Kanji => Color::White,
+        }
+    }
+
+    ///a piece shaped as a pentagon pointing to the opponent, with its label
+    fn piece(&self, s: &mut String, piece: &Piece, (cx, cy): (f64, f64), style: PieceStyle) {
+        let size = self.size;
+        let points: Vec<String> = [
+            (0.0, -0.42),
+            (0.3, -0.3),
+            (0.37, 0.42),
+            (-0.37, 0.42),
+            (-0.3, -0.3),
+        ]
+        .iter()
+        .map(|(x, y)| format!("{:.1},{:.1}", cx + x * size, cy + y * size))
+        .collect();
+        let rotation = if piece.color == self.bottom() {
+            String::new()
+        } else {
+            format!(" transform=\"rotate(180 {:.1} {:.1})\"", cx, cy)
+        };
+        let text = label(piece, style);
+        let font = if text.chars().count() > 1 {
+            0.38 * size
+        } else {
+            0.5 * size
+        };
+        writeln!(
+            s,
+            "<g{}><polygon points=\"{}\" fill=\"#f5deb3\" stroke=\"#000\"/>\
+             <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{:.1}\" text-anchor=\"middle\" \
+             dominant-baseline=\"central\" fill=\"{}\">{}</text></g>",
+            rotation,
+            points.join(" "),
+            cx,
+            cy + 0.05 * size,
+            font,
+            if piece.promoted { "#c00" } else { "#000" },
+            text
+        )
+        .unwrap();
+    }
+}
+
+impl Board {
+    /// Draw the board as a standalone SVG diagram: the grid with its coordinates, the pieces,
+    /// both hands with their counts, and the highlighted squares and arrows of the options
+    pub fn to_svg(&self, options: &SvgOptions) -> String {
+        let size = options.square_size as f64;
+        let layout = Layout {
+            style: options.style,
+            size,
+            left: 0.5 * size,
+            top: 1.6 * size,
+            squares: self.rules.variant.size,
+        };
+        let squares = layout.squares as f64;
+        let width = layout.left + (squares + 1.0) * size;
+        let height = layout.top + (squares + 1.6) * size;
+        let board = squares * size;
+
+        let mut s = String::new();
+        writeln!(
+            s,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" \
+             viewBox=\"0 0 {:.0} {:.0}\" font-family=\"sans-serif\">",
+            width, height, width, height
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "<defs><marker id=\"arrowhead\" viewBox=\"0 0 10 10\" refX=\"7\" refY=\"5\" \
+             markerWidth=\"4\" markerHeight=\"4\" orient=\"auto\">\
+             <path d=\"M0,0 L10,5 L0,10 z\" fill=\"#d33\"/></marker></defs>"
+        )
+        .unwrap();
+        writeln!(
+            s,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#f0c878\"/>",
+            layout.left, layout.top, board, board
+        )
+        .unwrap();
+        for &square in &options.highlighted {
+            let (x, y) = layout.cell(square);
+            writeln!(
+                s,
+                "<rect class=\"highlight\" x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" \
+                 height=\"{:.1}\" fill=\"#f6e27f\"/>",
+                layout.left + x as f64 * size,
+                layout.top + y as f64 * size,
+                size,
+                size
+            )
+            .unwrap();
+        }
+
+        // grid, star points and coordinates
+        for i in 0..=layout.squares {
+            let offset = i as f64 * size;
+            writeln!(
+                s,
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#000\"/>\
+                 <line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#000\"/>",
+                layout.left + offset,
+                layout.top,
+                layout.left + offset,
+                layout.top + board,
+                layout.left,
+                layout.top + offset,
+                layout.left + board,
+                layout.top + offset
+            )
+            .unwrap();
+        }
+        let stars: &[(f64, f64)] = if layout.squares == 9 {
+            &[(3.0, 3.0), (6.0, 3.0), (3.0, 6.0), (6.0, 6.0)]
+        } else {
+            &[]
+        };
+        for &(x, y) in stars {
+            writeln!(
+                s,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"{:.1}\"/>",
+                layout.left + x * size,
+                layout.top + y * size,
+                0.06 * size
+            )
+            .unwrap();
+        }
+        for i in 0..layout.squares as usize {
+            let rank = match options.style {
+                PieceStyle::Letters => ((b'a' + i as u8) as char).to_string(),
+                PieceStyle::Kanji => {
+                    ["一", "二", "三", "四", "五", "六", "七", "八", "九"][i].to_string()
+                }
+            };
+            writeln!(
+                s,
+                "<text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{:.1}\" text-anchor=\"middle\">{}</text>\
+                 <text x=\"{:.1}\" y=\"{:.1}\" font-size=\"{:.1}\" text-anchor=\"middle\" \
+                 dominant-baseline=\"central\">{}</text>",
+                layout.left + (i as f64 + 0.5) * size,
+                layout.top - 0.15 * size,
+                0.35 * size,
+                layout.squares as usize - i,
+                layout.left + board + 0.3 * size,
+                layout.top + (i as f64 + 0.5) * size,
+                0.35 * size,
+                rank
+            )
+            .unwrap();
+        }
+
+        for piece in self.iter() {
+            if let Some(position) = piece.position {
+                layout.piece(&mut s, piece, layout.center(position), options.style);
+            }
+        }
+
+        // hands: the one of the top player above the board, the other one below
+        for &color in &[Color::White, Color::Black] {
+            let y = if color == layout.bottom() {
+                layout.top + board + 0.6 * size
+            } else {
+                0.5 * size
+            };
+            let mut x = layout.left + 0.5 * size;
+            for piecetype in &HAND_ORDER {
+                let count = self
+                    .iter()
+                    .filter(|p| {
+                        p.position.is_none() && p.color == color && p.piecetype == *piecetype
+                    })
+                    .count();
+                if count == 0 {
+                    continue;
+                }
+                let piece = Piece {
+                    color,
+                    piecetype: *piecetype,
+                    promoted: false,
+                    position: None,
+                };
+                layout.piece(&mut s, &piece, (x, y), options.style);
+                if count > 1 {
+                    writeln!(
+                        s,
+                        "<text class=\"count\" x=\"{:.1}\" y=\"{:.1}\" font-size=\"{:.1}\" \
+                         dominant-baseline=\"central\">×{}</text>",
+                        x + 0.4 * size,
+                        y,
+                        0.35 * size,
+                        count
+                    )
+                    .unwrap();
+                }
+                x += 1.2 * size;
+            }
+        }
+
+        for &(from, to) in &options.arrows {
+            let (x1, y1) = layout.center(from);
+            let (x2, y2) = layout.center(to);
+            writeln!(
+                s,
+                "<line class=\"arrow\" x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" \
+                 stroke=\"#d33\" stroke-width=\"{:.1}\" stroke-opacity=\"0.8\" \
+                 marker-end=\"url(#arrowhead)\"/>",
+                x1,
+                y1,
+                x2,
+                y2,
+                0.12 * size
+            )
+            .unwrap();
+        }
+        s.push_str("</svg>\n");
+        s
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::svg::*;
+
+    #[test]
+    fn diagram() {
+        let b = Board::new();
+        let svg = b.to_svg(&SvgOptions::default());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(svg.matches("<polygon").count(), 40);
+        assert_eq!(svg.matches("rotate(180").count(), 20);
+        assert_eq!(svg.matches("class=\"highlight\"").count(), 0);
+        assert_eq!(svg.matches("class=\"arrow\"").count(), 0);
+    }
+
+    #[test]
+    fn hands_and_marks() {
+        let b = Board::new()
+            .play_move("P3c-3d")
+            .play_move("P7g-7f")
+            .play_move("B2b-8h+")
+            .play_move("S7i-8h");
+        let options = SvgOptions {
+            style: PieceStyle::Kanji,
+            highlighted: vec!["8h".parse().unwrap()],
+            arrows: vec![("7i".parse().unwrap(), "8h".parse().unwrap())],
+            ..SvgOptions::default()
+        };
+        let svg = b.to_svg(&options);
+        assert_eq!(svg.matches(">角</text>").count(), 2);
+        assert!(svg.contains(">九</text>"));
+        assert_eq!(svg.matches("class=\"highlight\"").count(), 1);
+        assert_eq!(svg.matches("class=\"arrow\"").count(), 1);
+        assert!(!svg.contains("class=\"count\""));
+
+        let mut b = Board::empty();
+        for _ in 0..3 {
+            b.add_piece(Piece {
+                color: Color::Black,
+                piecetype: PieceType::Pawn,
+                promoted: false,
+                position: None,
+            });
+        }
+        assert!(b.to_svg(&options).contains("×3</text>"));
+    }
+
+    #[test]
+    fn variant_size() {
+        let options = SvgOptions {
+            style: PieceStyle::Kanji,
+            ..SvgOptions::default()
+        };
+        let svg = Board::minishogi().to_svg(&options);
+        assert!(svg.contains("width=\"260\" height=\"328\""));
+        assert_eq!(svg.matches("<polygon").count(), 12);
+        assert_eq!(svg.matches("<line").count(), 12);
+        assert_eq!(svg.matches("<circle").count(), 0);
+        assert!(svg.contains(">五</text>"));
+        assert!(!svg.contains(">六</text>"));
+        // the rook of the second player in the top left corner
+        assert!(svg.contains("rotate(180 40.0 84.0)\"><polygon"));
+        assert!(svg.contains(">飛</text>"));
+    }
+}
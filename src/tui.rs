@@ -0,0 +1,540 @@
+use crate::board::*;
+use crate::game::*;
+use crate::movement::*;
+use crate::piece::*;
+use crate::player::*;
+use crate::position::*;
+use crate::render::*;
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute, queue, style, terminal};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// keys understood by the move entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Up,
+    Down,
+    Left,
+    Right,
+    Enter,
+    Escape,
+    Tab,
+    Char(char),
+}
+
+/// what is about to be moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Square(Position),
+    Hand(PieceType),
+}
+
+/// Entering a move with the cursor: select a piece (or, with Tab, a piece in hand), then one of
+/// its legal destinations, then answer the promotion prompt if the piece may promote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveEntry {
+    pub cursor: Position,
+    pub selection: Option<Selection>,
+    /// the promoting and the non promoting moves, while the player is asked to choose
+    pub promotion: Option<(String, String)>,
+    /// the arrow keys follow the orientation of the board on the screen
+    pub style: PieceStyle,
+}
+
+impl MoveEntry {
+    pub fn new(style: PieceStyle) -> Self {
+        MoveEntry {
+            cursor: Position(40),
+            selection: None,
+            promotion: None,
+            style,
+        }
+    }
+
+    /// legal moves of the selected piece
+    pub fn moves(&self, b: &Board) -> Vec<String> {
+        let selection = match self.selection {
+            Some(selection) => selection,
+            None => return Vec::new(),
+        };
+        b.iter_moves()
+            .filter(|mv| match (selection, mv.parse::<Movement>()) {
+                (Selection::Square(start), Ok(mv)) => mv.start == Some(start),
+                (Selection::Hand(piecetype), Ok(mv)) => {
+                    mv.start.is_none() && !mv.withdraw && !mv.restart && mv.piecetype == piecetype
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// squares the selected piece can go to
+    pub fn destinations(&self, b: &Board) -> Vec<Position> {
+        let mut destinations: Vec<Position> = self
+            .moves(b)
+            .iter()
+            .map(|mv| mv.parse::<Movement>().unwrap().end)
+            .collect();
+        destinations.dedup();
+        destinations
+    }
+
+    /// handle a key, and return the move once it is complete
+    pub fn press(&mut self, b: &Board, key: Key) -> Option<String> {
+        if let Some((promoting, not_promoting)) = self.promotion.clone() {
+            match key {
+                Key::Char('y') | Key::Enter => {
+                    self.promotion = None;
+                    return Some(promoting);
+                }
+                Key::Char('n') => {
+                    self.promotion = None;
+                    return Some(not_promoting);
+                }
+                Key::Escape => self.promotion = None,
+                _ => {}
+            }
+            return None;
+        }
+
+        // on the screen, our column 9 is on the left and row a at the top with letters, the
+        // board is rotated with kanji
+        let key = match (self.style, key) {
+            (PieceStyle::Kanji, Key::Up) => Key::Down,
+            (PieceStyle::Kanji, Key::Down) => Key::Up,
+            (PieceStyle::Kanji, Key::Left) => Key::Right,
+            (PieceStyle::Kanji, Key::Right) => Key::Left,
+            (_, key) => key,
+        };
+        let p = self.cursor.0;
+        let size = b.rules.variant.size;
+        match key {
+            Key::Up if p >= 9 => self.cursor = Position(p - 9),
+            Key::Down if p / 9 < size - 1 => self.cursor = Position(p + 9),
+            Key::Left if p % 9 < size - 1 => self.cursor = Position(p + 1),
+            Key::Right if p % 9 >= 1 => self.cursor = Position(p - 1),
+            Key::Escape => self.selection = None,
+            Key::Tab => self.selection = self.next_in_hand(b),
+            Key::Enter | Key::Char(' ') => return self.validate(b),
+            _ => {}
+        }
+        None
+    }
+
+    ///play the selected piece at the cursor, or select the piece under the cursor
+    fn validate(&mut self, b: &Board) -> Option<String> {
+        let cursor = self.cursor;
+        let mut moves: Vec<String> = self
+            .moves(b)
+            .into_iter()
+            .filter(|mv| mv.parse::<Movement>().unwrap().end == cursor)
+            .collect();
+        match moves.len() {
+            0 => {
+                self.selection = match b.is_occupied_by(cursor) {
+                    Some(piece) if piece.color == b.get_color() => Some(Selection::Square(cursor)),
+                    _ => None,
+                };
+                None
+            }
+            1 => {
+                self.selection = None;
+                moves.pop()
+            }
+            _ => {
+                self.selection = None;
+                moves.sort_by_key(|mv| !mv.ends_with('+'));
+                self.promotion = Some((moves[0].clone(), moves[1].clone()));
+                None
+            }
+        }
+    }
+
+    ///the next type of piece in the hand of the current player
+    fn next_in_hand(&self, b: &Board) -> Option<Selection> {
+        let mut hand: Vec<PieceType> = Vec::new();
+        for piece in b
+            .iter()
+            .filter(|p| p.position.is_none() && p.color == b.get_color())
+        {
+            if !hand.contains(&piece.piecetype) {
+                hand.push(piece.piecetype);
+            }
+        }
+        let next = match self.selection {
+            Some(Selection::Hand(current)) => hand.iter().position(|&t| t == current)? + 1,
+            _ => 0,
+        };
+        hand.get(next).map(|&piecetype| Selection::Hand(piecetype))
+    }
+}
+
+///restore the terminal when the interface stops, even on a panic
+struct RawScreen;
+
+impl RawScreen {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen)?;
+        Ok(RawScreen)
+    }
+}
+
+impl Drop for RawScreen {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn read_key() -> io::Result<Option<Key>> {
+    if !event::poll(Duration::from_millis(500))? {
+        return Ok(None);
+    }
+    Ok(match event::read()? {
+        Event::Key(key) if key.kind != KeyEventKind::Release => match key.code {
+            KeyCode::Up => Some(Key::Up),
+            KeyCode::Down => Some(Key::Down),
+            KeyCode::Left => Some(Key::Left),
+            KeyCode::Right => Some(Key::Right),
+            KeyCode::Enter => Some(Key::Enter),
+            KeyCode::Esc => Some(Key::Escape),
+            KeyCode::Tab => Some(Key::Tab),
+            KeyCode::Char(c) => Some(Key::Char(c)),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn clock(d: Duration) -> String {
+    format!("{}:{:02}", d.as_secs() / 60, d.as_secs() % 60)
+}
+
+/// state of the interface, besides the move being entered
+struct Screen {
+    names: [String; 2],
+    options: RenderOptions,
+    /// moves as shown in the move list
+    notations: Vec<String>,
+    clocks: [Duration; 2],
+    turn_start: Instant,
+    message: String,
+}
+
+impl Screen {
+    fn draw(&self, game: &Game, entry: &MoveEntry, human: bool) -> io::Result<()> {
+        let b = game.board();
+        let mut options = self.options.clone();
+        options.last_move = game.last_move().map(|mv| mv.to_string());
+        if let Some(Selection::Square(start)) = entry.selection {
+            options.targets.push(start);
+        }
+        options.targets.extend(entry.destinations(b));
+
+        let mut clocks = self.clocks;
+        clocks[if b.get_turn() { 0 } else { 1 }] += self.turn_start.elapsed();
+        let turn = |i: usize| if b.get_turn() == (i == 0) { ">" } else { " " };
+        let mut lines = vec![
+            format!(
+                "{} White ({}) {}    {} Black ({}) {}",
+                turn(0),
+                self.names[0],
+                clock(clocks[0]),
+                turn(1),
+                self.names[1],
+                clock(clocks[1])
+            ),
+            String::new(),
+        ];
+        let top = lines.len() as u16;
+        lines.extend(b.render(&options).lines().map(|line| line.to_string()));
+
+        let status = if entry.promotion.is_some() {
+            String::from("Promote? (y/n, esc to cancel)")
+        } else {
+            match entry.selection {
+                Some(Selection::Hand(piecetype)) => format!("Drop {} where?", piecetype),
+                Some(Selection::Square(_)) => String::from("Move where?"),
+                None if human => String::from("Select a piece"),
+                None => String::from("Thinking..."),
+            }
+        };
+        lines.push(String::new());
+        lines.push(status);
+        lines.push(self.message.clone());
+        lines.push(String::from(
+            "arrows: cursor  enter: select/play  tab: piece in hand  esc: cancel  u: undo  q: quit",
+        ));
+
+        // raw mode: every line is placed explicitly
+        let mut out = io::stdout();
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        for (i, line) in lines.iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, i as u16), style::Print(line))?;
+        }
+
+        // move list, next to the board
+        let first = self.notations.len().saturating_sub(20) / 2 * 2;
+        for (i, pair) in self.notations[first..].chunks(2).enumerate() {
+            queue!(
+                out,
+                cursor::MoveTo(44, top + i as u16),
+                style::Print(format!(
+                    "{:>3}. {:<12} {}",
+                    (first / 2) + i + 1,
+                    pair[0],
+                    pair.get(1).map(|s| s.as_str()).unwrap_or("")
+                ))
+            )?;
+        }
+
+        // the terminal cursor shows the square under the cursor of the move entry
+        let last = b.rules.variant.size - 1;
+        let (row, column) = match entry.style {
+            PieceStyle::Letters => (entry.cursor.0 / 9, last - entry.cursor.0 % 9),
+            PieceStyle::Kanji => (last - entry.cursor.0 / 9, entry.cursor.0 % 9),
+        };
+        let header = match entry.style {
+            PieceStyle::Letters => 2,
+            PieceStyle::Kanji => 3,
+        };
+        if human {
+            queue!(
+                out,
+                cursor::MoveTo(3 + column * 4, top + header + row),
+                cursor::Show
+            )?;
+        } else {
+            queue!(out, cursor::Hide)?;
+        }
+        out.flush()
+    }
+
+    ///record the move of the current player (before it is played)
+    fn record(&mut self, game: &Game, mv: &str) {
+        let b = game.board();
+        let notation = match self.options.style {
+            PieceStyle::Letters => b.to_short_notation(mv),
+            PieceStyle::Kanji => b.to_kanji(mv, game.last_move()),
+        };
+        self.notations
+            .push(notation.unwrap_or_else(|_| mv.to_string()));
+        self.clocks[if b.get_turn() { 0 } else { 1 }] += self.turn_start.elapsed();
+        self.turn_start = Instant::now();
+    }
+}
+
+/// Play a game from the start position in a full-screen terminal interface. Human players (see
+/// Player::is_human) enter their moves with the cursor, the other players are asked for theirs.
+/// As in matchrunner, a bot loses by playing an illegal move or offering a draw again after its
+/// offer was declined
+pub fn run(
+    mut players: [Box<dyn Player>; 2],
+    options: RenderOptions,
+    start: Board,
+) -> io::Result<()> {
+    let human = [players[0].is_human(), players[1].is_human()];
+    let mut screen = Screen {
+        names: [players[0].name(), players[1].name()],
+        options: options.clone(),
+        notations: Vec::new(),
+        clocks: [Duration::from_secs(0); 2],
+        turn_start: Instant::now(),
+        message: String::new(),
+    };
+    // the cursor starts in the middle of the board
+    let middle = start.rules.variant.size / 2;
+    let mut entry = MoveEntry {
+        cursor: Position(middle * 9 + middle),
+        ..MoveEntry::new(options.style)
+    };
+    let mut game = Game::from_board(start);
+    // whether the current player already had a draw offer declined
+    let mut declined = false;
+    let _raw = RawScreen::enter()?;
+
+    loop {
+        let b = game.board().clone();
+        let color = b.get_color();
+        let current = if b.get_turn() { 0 } else { 1 };
+        if let Some(result) = game.result() {
+            screen.message = format!("{}! (press a key)", result);
+            break;
+        }
+        screen.draw(&game, &entry, human[current])?;
+
+        let mv = if human[current] {
+            let key = match read_key()? {
+                Some(key) => key,
+                None => continue, // refresh the clocks
+            };
+            match key {
+                Key::Char('q') => {
+                    screen.message = format!("{:?} resigns! (press a key)", color);
+                    break;
+                }
+                Key::Char('u') if entry.promotion.is_none() => {
+                    // take back the moves of the bots too, to be back to a human turn
+                    loop {
+                        if game.undo().is_none() {
+                            break;
+                        }
+                        screen.notations.pop();
+                        if human[if game.board().get_turn() { 0 } else { 1 }] {
+                            break;
+                        }
+                    }
+                    entry.selection = None;
+                    declined = false;
+                    screen.message.clear();
+                    continue;
+                }
+                key => match entry.press(&b, key) {
+                    Some(mv) => mv,
+                    None => continue,
+                },
+            }
+        } else {
+            match players[current].choose_move(&game) {
+                PlayerAction::Move(mv) => mv,
+                PlayerAction::Resign => {
+                    screen.message = format!("{:?} resigns! (press a key)", color);
+                    break;
+                }
+                PlayerAction::DeclareImpasse => {
+                    screen.message = format!("{} (press a key)", game.declare_impasse());
+                    break;
+                }
+                PlayerAction::OfferDraw if declined => {
+                    let result = GameResult::loss(color, Termination::IllegalMove);
+                    screen.message = format!("{}! (press a key)", result);
+                    break;
+                }
+                PlayerAction::OfferDraw => {
+                    let accepted = if human[1 - current] {
+                        screen.message = String::from("Draw offered, accept? (y/n)");
+                        screen.draw(&game, &entry, true)?;
+                        loop {
+                            match read_key()? {
+                                Some(Key::Char('y')) => break true,
+                                Some(Key::Char('n')) => break false,
+                                _ => {}
+                            }
+                        }
+                    } else {
+                        players[1 - current].accept_draw(&game)
+                    };
+                    if accepted {
+                        screen.message = String::from("Draw agreed (press a key)");
+                        break;
+                    }
+                    screen.message = String::from("Draw declined");
+                    declined = true;
+                    continue;
+                }
+            }
+        };
+
+        screen.message.clear();
+        screen.record(&game, &mv);
+        if let Err(e) = game.play(&mv) {
+            screen.notations.pop();
+            if !human[current] {
+                let result = GameResult::loss(color, Termination::IllegalMove);
+                screen.message = format!("{} ({}: {})! (press a key)", result, mv, e);
+                break;
+            }
+            screen.message = format!("Not a valid move: {}", e);
+        } else {
+            declined = false;
+        }
+    }
+
+    screen.draw(&game, &MoveEntry::new(options.style), false)?;
+    while read_key()?.is_none() {}
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tui::*;
+
+    #[test]
+    fn select_and_move() {
+        let b = Board::new();
+        let mut entry = MoveEntry::new(PieceStyle::Letters);
+        entry.cursor = "3c".parse().unwrap();
+        assert_eq!(entry.press(&b, Key::Enter), None);
+        assert_eq!(
+            entry.selection,
+            Some(Selection::Square("3c".parse().unwrap()))
+        );
+        assert_eq!(entry.destinations(&b), vec!["3d".parse().unwrap()]);
+        assert_eq!(entry.press(&b, Key::Down), None);
+        assert_eq!(entry.press(&b, Key::Enter), Some(String::from("P3c-3d")));
+        assert_eq!(entry.selection, None);
+
+        // opponent pieces cannot be selected
+        entry.cursor = "3g".parse().unwrap();
+        entry.press(&b, Key::Enter);
+        assert_eq!(entry.selection, None);
+
+        // the cursor stays on the board
+        entry.cursor = Position(0);
+        entry.press(&b, Key::Up);
+        entry.press(&b, Key::Right);
+        assert_eq!(entry.cursor, Position(0));
+        entry.press(&b, Key::Left);
+        assert_eq!(entry.cursor, Position(1));
+
+        // also on a smaller board
+        let b = Board::minishogi();
+        entry.cursor = "5e".parse().unwrap();
+        entry.press(&b, Key::Down);
+        entry.press(&b, Key::Left);
+        assert_eq!(entry.cursor, "5e".parse().unwrap());
+        entry.cursor = "1b".parse().unwrap();
+        entry.press(&b, Key::Enter);
+        assert_eq!(entry.destinations(&b), vec!["1c".parse().unwrap()]);
+    }
+
+    #[test]
+    fn promotion_and_drops() {
+        let mut b = Board::empty();
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Silver,
+            promoted: false,
+            position: Some("5f".parse().unwrap()),
+        });
+        b.add_piece(Piece {
+            color: Color::White,
+            piecetype: PieceType::Pawn,
+            promoted: false,
+            position: None,
+        });
+        let mut entry = MoveEntry::new(PieceStyle::Kanji);
+        entry.cursor = "5f".parse().unwrap();
+        entry.press(&b, Key::Enter);
+        // the board is rotated with kanji: up goes toward the row i
+        entry.press(&b, Key::Up);
+        assert_eq!(entry.cursor, "5g".parse().unwrap());
+        assert_eq!(entry.press(&b, Key::Enter), None);
+        assert!(entry.promotion.is_some());
+        assert_eq!(
+            entry.press(&b, Key::Char('n')),
+            Some(String::from("S5f-5g"))
+        );
+
+        entry.press(&b, Key::Tab);
+        assert_eq!(entry.selection, Some(Selection::Hand(PieceType::Pawn)));
+        assert_eq!(entry.destinations(&b).len(), 71);
+        assert_eq!(entry.press(&b, Key::Enter), Some(String::from("P*5g")));
+        entry.press(&b, Key::Tab);
+        entry.press(&b, Key::Tab);
+        assert_eq!(entry.selection, None);
+    }
+}
@@ -0,0 +1,509 @@
+use crate::board::*;
+use crate::game::*;
+use crate::invalidmoveerror::*;
+use crate::movement::*;
+use crate::piece::*;
+use crate::player::*;
+use crate::position::*;
+use crate::sfen::*;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum UsiError {
+    IoError(io::Error),
+    ProtocolError(String),
+    IllegalMoveError(String, InvalidMoveError),
+    /// the engine did not answer in time
+    Timeout,
+}
+
+impl fmt::Display for UsiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UsiError::IoError(e) => write!(f, "Communication with the engine failed: {}", e),
+            UsiError::ProtocolError(line) => {
+                write!(f, "Unexpected answer from the engine: {}", line)
+            }
+            UsiError::IllegalMoveError(mv, e) => write!(f, "The engine played {}: {}", mv, e),
+            UsiError::Timeout => write!(f, "The engine did not answer in time"),
+        }
+    }
+}
+
+impl error::Error for UsiError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            UsiError::IoError(e) => Some(e),
+            UsiError::IllegalMoveError(_, e) => Some(e),
+            UsiError::ProtocolError(_) | UsiError::Timeout => None,
+        }
+    }
+}
+
+impl From<io::Error> for UsiError {
+    fn from(e: io::Error) -> Self {
+        UsiError::IoError(e)
+    }
+}
+
+///USI squares of a board of the given size are seen from the first player (see the sfen
+///module), ours are rotated
+fn square_from_usi(s: &str, size: u16) -> Option<Position> {
+    let bytes = s.as_bytes();
+    if bytes.len() != 2 {
+        return None;
+    }
+    let file = bytes[0].wrapping_sub(b'0') as u16;
+    let rank = bytes[1].wrapping_sub(b'a') as u16;
+    if !(1..=size).contains(&file) || rank >= size {
+        return None;
+    }
+    Some(Position((size - 1 - rank) * 9 + (size - file)))
+}
+
+fn square_to_usi(p: Position, size: u16) -> String {
+    let file = size - p.0 % 9;
+    let rank = (b'a' + (size - 1 - p.0 / 9) as u8) as char;
+    format!("{}{}", file, rank)
+}
+
+/// convert a movement on a board of the given size (see Variant) to the USI notation ("7g7f",
+/// "8h2b+", "P*5e")
+pub fn to_usi(mv: &Movement, size: u16) -> String {
+    match mv.start {
+        None => format!("{}*{}", mv.piecetype, square_to_usi(mv.end, size)),
+        Some(start) => format!(
+            "{}{}{}",
+            square_to_usi(start, size),
+            square_to_usi(mv.end, size),
+            if mv.promotion { "+" } else { "" }
+        ),
+    }
+}
+
+/// convert a USI move to our notation, and check that it is legal on the board
+pub fn from_usi(b: &Board, usi: &str) -> Result<String, UsiError> {
+    let protocol_error = || UsiError::ProtocolError(format!("invalid move {}", usi));
+    let size = b.rules.variant.size;
+    // the slices below need one byte per character
+    if !usi.is_ascii() {
+        return Err(protocol_error());
+    }
+    let mv = if usi.len() == 4 && &usi[1..2] == "*" {
+        let end = square_from_usi(&usi[2..], size).ok_or_else(protocol_error)?;
+        format!("{}*{}", &usi[0..1], end.to_string())
+    } else if usi.len() == 4 || (usi.len() == 5 && usi.ends_with('+')) {
+        let start = square_from_usi(&usi[0..2], size).ok_or_else(protocol_error)?;
+        let end = square_from_usi(&usi[2..4], size).ok_or_else(protocol_error)?;
+        let piece = b.is_occupied_by(start).ok_or_else(|| {
+            UsiError::IllegalMoveError(
+                usi.to_string(),
+                InvalidMoveError::NoPieceAtPositionError {
+                    square: Some(start),
+                    found: None,
+                },
+            )
+        })?;
+        format!(
+            "{}{}-{}{}",
+            piece.piecetype,
+            start.to_string(),
+            end.to_string(),
+            &usi[4..]
+        )
+    } else {
+        return Err(protocol_error());
+    };
+    match b.check_move(&mv) {
+        Ok(_) => Ok(mv),
+        Err(e) => Err(UsiError::IllegalMoveError(usi.to_string(), e)),
+    }
+}
+
+/// return the USI "position" command for the game
+pub fn position_command(game: &Game) -> String {
+    let start = game.start().to_sfen(1);
+    let mut command = if start == START_SFEN {
+        String::from("position startpos")
+    } else {
+        format!("position sfen {}", start)
+    };
+    let size = game.start().rules.variant.size;
+    let moves: Vec<String> = game
+        .moves()
+        .iter()
+        // USI has no such moves, and the game does not go on after them
+        .take_while(|mv| *mv != "withdraw" && *mv != "restart")
+        .filter_map(|mv| mv.parse::<Movement>().ok())
+        .map(|movement| to_usi(&movement, size))
+        .collect();
+    if !moves.is_empty() {
+        command.push_str(" moves ");
+        command.push_str(&moves.join(" "));
+    }
+    command
+}
+
+/// what the engine told about its search ("info" lines)
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct UsiInfo {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    /// score in centipawns, from the point of view of the engine
+    pub score_cp: Option<i32>,
+    /// number of moves before mate (negative if the engine is getting mated)
+    pub mate: Option<i32>,
+    /// principal variation, in our notation (stops at the first illegal move)
+    pub pv: Vec<String>,
+}
+
+impl UsiInfo {
+    /// parse the fields of an "info" line, the moves of the pv are played from the given board
+    pub fn parse(b: &Board, line: &str) -> UsiInfo {
+        let mut info = UsiInfo::default();
+        let mut words = line.split_whitespace().skip(1);
+        while let Some(word) = words.next() {
+            match word {
+                "depth" => info.depth = words.next().and_then(|w| w.parse().ok()),
+                "nodes" => info.nodes = words.next().and_then(|w| w.parse().ok()),
+                "score" => match (words.next(), words.next()) {
+                    (Some("cp"), Some(value)) => info.score_cp = value.parse().ok(),
+                    (Some("mate"), Some(value)) => info.mate = value.parse().ok(),
+                    _ => {}
+                },
+                "string" => break,
+                "pv" => {
+                    let mut board = b.clone();
+                    for usi in words.by_ref() {
+                        match from_usi(&board, usi) {
+                            Ok(mv) => {
+                                board = board.play_move_unchecked(&mv);
+                                info.pv.push(mv);
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        info
+    }
+}
+
+/// answer of the engine to the "go" command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BestMove {
+    Move(String),
+    Resign,
+    Win,
+}
+
+/// An external engine speaking USI, running in a child process
+pub struct UsiEngine {
+    child: Child,
+    stdin: ChildStdin,
+    /// lines of the engine, read by a separate thread so that waiting for them can time out
+    lines: Receiver<io::Result<String>>,
+    /// name given by the engine during the handshake
+    pub name: String,
+    /// time to think about each move when the clocks are unlimited
+    pub byoyomi: Duration,
+    /// longest wait for an answer, on top of the time the engine has to think
+    pub timeout: Duration,
+    /// time left to both players (see Player::set_clocks)
+    clocks: [Option<Duration>; 2],
+    increment: Duration,
+    /// "info" lines received during the last search
+    pub infos: Vec<UsiInfo>,
+    /// last error that made the engine resign, as a Player
+    pub last_error: Option<UsiError>,
+}
+
+impl UsiEngine {
+    /// start the engine and perform the handshake (usi, setoption, isready, usinewgame)
+    pub fn spawn(program: &str, args: &[&str], options: &[(&str, &str)]) -> Result<Self, UsiError> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut engine = UsiEngine {
+            child,
+            stdin,
+            lines,
+            name: program.to_string(),
+            byoyomi: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            clocks: [None, None],
+            increment: Duration::from_secs(0),
+            infos: Vec::new(),
+            last_error: None,
+        };
+
+        engine.send("usi")?;
+        loop {
+            let line = engine.receive(Duration::from_secs(0))?;
+            if line == "usiok" {
+                break;
+            }
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.to_string();
+            }
+        }
+        for (name, value) in options {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.send("isready")?;
+        while engine.receive(Duration::from_secs(0))? != "readyok" {}
+        engine.send("usinewgame")?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), UsiError> {
+        writeln!(self.stdin, "{}", command)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    /// wait for the next line of the engine, at most `thinking` + self.timeout
+    fn receive(&mut self, thinking: Duration) -> Result<String, UsiError> {
+        match self.lines.recv_timeout(thinking + self.timeout) {
+            Ok(line) => Ok(line?.trim_end().to_string()),
+            Err(RecvTimeoutError::Timeout) => Err(UsiError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(UsiError::ProtocolError(String::from(
+                "the engine closed its output",
+            ))),
+        }
+    }
+
+    /// the "go" command for the side to move, with the clocks if they are limited, else with
+    /// self.byoyomi for each move. Returns the longest time the engine may think
+    fn go_command(&self, color: Color) -> (String, Duration) {
+        let millis = |time: Option<Duration>| time.unwrap_or_default().as_millis();
+        // USI's black is the first player, our White
+        let (btime, wtime) = (
+            millis(self.clocks[Color::White as usize]),
+            millis(self.clocks[Color::Black as usize]),
+        );
+        match self.clocks[color as usize] {
+            Some(left) => (
+                format!(
+                    "go btime {} wtime {} binc {} winc {}",
+                    btime,
+                    wtime,
+                    self.increment.as_millis(),
+                    self.increment.as_millis()
+                ),
+                left + self.increment,
+            ),
+            None => (
+                format!(
+                    "go btime {} wtime {} byoyomi {}",
+                    btime,
+                    wtime,
+                    self.byoyomi.as_millis()
+                ),
+                self.byoyomi,
+            ),
+        }
+    }
+
+    /// ask the engine for its move in the current position of the game
+    pub fn go(&mut self, game: &Game) -> Result<BestMove, UsiError> {
+        self.infos.clear();
+        self.send(&position_command(game))?;
+        let (go, thinking) = self.go_command(game.board().get_color());
+        self.send(&go)?;
+        loop {
+            let line = self.receive(thinking)?;
+            if line.starts_with("info") {
+                self.infos.push(UsiInfo::parse(game.board(), &line));
+            } else if let Some(rest) = line.strip_prefix("bestmove") {
+                return match rest.split_whitespace().next() {
+                    Some("resign") => Ok(BestMove::Resign),
+                    Some("win") => Ok(BestMove::Win),
+                    Some(usi) => from_usi(game.board(), usi).map(BestMove::Move),
+                    None => Err(UsiError::ProtocolError(line)),
+                };
+            }
+        }
+    }
+
+    /// tell the engine to stop, and wait for it
+    pub fn quit(mut self) -> Result<(), UsiError> {
+        self.send("quit")?;
+        self.child.wait()?;
+        Ok(())
+    }
+}
+
+impl Player for UsiEngine {
+    /// an engine that fails (crash, illegal move...) resigns, the error is kept in last_error
+    fn choose_move(&mut self, game: &Game) -> PlayerAction {
+        match self.go(game) {
+            Ok(BestMove::Move(mv)) => PlayerAction::Move(mv),
+            Ok(BestMove::Resign) => PlayerAction::Resign,
+            Ok(BestMove::Win) => PlayerAction::DeclareImpasse,
+            Err(e) => {
+                self.last_error = Some(e);
+                PlayerAction::Resign
+            }
+        }
+    }
+
+    fn set_clocks(&mut self, clocks: [Option<Duration>; 2], increment: Duration) {
+        self.clocks = clocks;
+        self.increment = increment;
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+impl Drop for UsiEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::usi::*;
+
+    const STUB: &str = r#"
+while read line; do
+  case "$line" in
+    usi) echo "id name stub"; echo "usiok";;
+    isready) echo "readyok";;
+    "position startpos") echo "info depth 2 nodes 30 score cp 15 pv 7g7f 3c3d";
+                         echo "bestmove 7g7f";;
+    "position startpos moves 7g7f 3c3d") echo "bestmove win";;
+    position*) echo "bestmove resign";;
+    quit) exit 0;;
+  esac
+done
+"#;
+
+    #[test]
+    fn conversions() {
+        let b = Board::new();
+        assert_eq!(from_usi(&b, "7g7f").unwrap(), "P3c-3d");
+        assert_eq!(
+            to_usi(&"P3c-3d".parse::<Movement>().unwrap(), 9),
+            String::from("7g7f")
+        );
+        assert_eq!(to_usi(&"P*5e".parse::<Movement>().unwrap(), 9), "P*5e");
+        assert!(from_usi(&b, "7g7e").is_err());
+        assert!(from_usi(&b, "7z7f").is_err());
+        assert!(from_usi(&b, "P*5e").is_err());
+        assert!(from_usi(&b, "７六").is_err());
+        assert!(from_usi(&b, "7g７").is_err());
+
+        // the squares of minishogi go from 1a to 5e
+        let b = Board::minishogi();
+        let mv = from_usi(&b, "5d5c").unwrap();
+        assert_eq!(to_usi(&mv.parse::<Movement>().unwrap(), 5), "5d5c");
+        assert!(from_usi(&b, "5f5e").is_err());
+        let mut g = Game::from_board(b);
+        g.play(&mv).unwrap();
+        assert!(position_command(&g).ends_with(" moves 5d5c"));
+
+        let mut g = Game::new();
+        assert_eq!(position_command(&g), "position startpos");
+        g.play("P3c-3d").unwrap();
+        assert_eq!(position_command(&g), "position startpos moves 7g7f");
+        g.play("withdraw").unwrap();
+        assert_eq!(position_command(&g), "position startpos moves 7g7f");
+        let mut g = Game::new();
+        g.play("withdraw").unwrap();
+        assert_eq!(position_command(&g), "position startpos");
+    }
+
+    #[test]
+    fn info() {
+        let info = UsiInfo::parse(
+            &Board::new(),
+            "info depth 3 nodes 1000 score mate -5 pv 7g7f 3c3d 9z9z",
+        );
+        assert_eq!(info.depth, Some(3));
+        assert_eq!(info.nodes, Some(1000));
+        assert_eq!(info.mate, Some(-5));
+        assert_eq!(info.score_cp, None);
+        assert_eq!(info.pv, vec!["P3c-3d", "P7g-7f"]);
+    }
+
+    #[test]
+    fn stub_engine() {
+        let mut engine = UsiEngine::spawn("sh", &["-c", STUB], &[("USI_Hash", "16")]).unwrap();
+        assert_eq!(engine.name(), "stub");
+        let mut g = Game::new();
+        assert_eq!(
+            engine.choose_move(&g),
+            PlayerAction::Move(String::from("P3c-3d"))
+        );
+        assert_eq!(engine.infos.len(), 1);
+        assert_eq!(engine.infos[0].score_cp, Some(15));
+        g.play("P3c-3d").unwrap();
+        assert_eq!(engine.go(&g).unwrap(), BestMove::Resign);
+        g.play("P7g-7f").unwrap();
+        assert_eq!(engine.choose_move(&g), PlayerAction::DeclareImpasse);
+        engine.quit().unwrap();
+    }
+
+    #[test]
+    fn clocks() {
+        let mut engine = UsiEngine::spawn("sh", &["-c", STUB], &[]).unwrap();
+        assert_eq!(
+            engine.go_command(Color::White).0,
+            "go btime 0 wtime 0 byoyomi 1000"
+        );
+        engine.set_clocks(
+            [Some(Duration::from_secs(60)), Some(Duration::from_secs(30))],
+            Duration::from_secs(2),
+        );
+        assert_eq!(
+            engine.go_command(Color::Black),
+            (
+                String::from("go btime 30000 wtime 60000 binc 2000 winc 2000"),
+                Duration::from_secs(62)
+            )
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        // answers the handshake, but never the searches
+        let silent = "while read line; do
+  case \"$line\" in
+    usi) echo usiok;;
+    isready) echo readyok;;
+    quit) exit 0;;
+  esac
+done";
+        let mut engine = UsiEngine::spawn("sh", &["-c", silent], &[]).unwrap();
+        engine.byoyomi = Duration::from_millis(10);
+        engine.timeout = Duration::from_millis(100);
+        assert!(matches!(engine.go(&Game::new()), Err(UsiError::Timeout)));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::piece::*;
+use std::fmt;
+
+///how a piece moves, for the first player (White, whose pieces go toward the row i): the
+///(column, row) steps it can make, and the directions in which it slides as far as the board
+///and the other pieces let it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PieceMoves {
+    pub steps: &'static [(i16, i16)],
+    pub slides: &'static [(i16, i16)],
+}
+
+const ORTHOGONAL: [(i16, i16); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL: [(i16, i16); 4] = [(1, 1), (-1, 1), (1, -1), (-1, -1)];
+
+pub const PAWN: PieceMoves = PieceMoves {
+    steps: &[(0, 1)],
+    slides: &[],
+};
+pub const KING: PieceMoves = PieceMoves {
+    steps: &[
+        (1, 0),
+        (-1, 0),
+        (1, 1),
+        (-1, 1),
+        (0, 1),
+        (0, -1),
+        (1, -1),
+        (-1, -1),
+    ],
+    slides: &[],
+};
+pub const ROOK: PieceMoves = PieceMoves {
+    steps: &[],
+    slides: &ORTHOGONAL,
+};
+pub const BISHOP: PieceMoves = PieceMoves {
+    steps: &[],
+    slides: &DIAGONAL,
+};
+pub const GOLD: PieceMoves = PieceMoves {
+    steps: &[(1, 0), (-1, 0), (-1, 1), (0, 1), (1, 1), (0, -1)],
+    slides: &[],
+};
+pub const SILVER: PieceMoves = PieceMoves {
+    steps: &[(-1, 1), (0, 1), (1, 1), (1, -1), (-1, -1)],
+    slides: &[],
+};
+pub const KNIGHT: PieceMoves = PieceMoves {
+    steps: &[(-1, 2), (1, 2)],
+    slides: &[],
+};
+pub const LANCE: PieceMoves = PieceMoves {
+    steps: &[],
+    slides: &[(0, 1)],
+};
+///the promoted rook (dragon) also steps diagonally
+pub const DRAGON: PieceMoves = PieceMoves {
+    steps: &DIAGONAL,
+    slides: &ORTHOGONAL,
+};
+///the promoted bishop (horse) also steps orthogonally
+pub const HORSE: PieceMoves = PieceMoves {
+    steps: &ORTHOGONAL,
+    slides: &DIAGONAL,
+};
+
+///moves of the usual pieces, indexed by PieceType (Pawn, King, Rook, Bishop, Gold, Silver,
+///Knight, Lance)
+pub const STANDARD_MOVES: [PieceMoves; 8] = [PAWN, KING, ROOK, BISHOP, GOLD, SILVER, KNIGHT, LANCE];
+///moves of the usual promoted pieces, None for the pieces that cannot promote
+pub const STANDARD_PROMOTED_MOVES: [Option<PieceMoves>; 8] = [
+    Some(GOLD),
+    None,
+    Some(DRAGON),
+    Some(HORSE),
+    None,
+    Some(GOLD),
+    Some(GOLD),
+    Some(GOLD),
+];
+
+///Everything that changes from a shogi variant to another. Boards refer to their variant
+///through their rules: moves are checked and generated with its tables, so a new variant only
+///needs a new (static) Variant, such as:
+///```
+///use shogai::board::*;
+///use shogai::variant::*;
+///
+///// minishogi, without drops
+///static NO_DROPS: Variant = Variant {
+///    name: "minishogi without drops",
+///    drops: false,
+///    ..Variant::MINISHOGI
+///};
+///let b = Board::start(&NO_DROPS);
+///assert_eq!(b.iter_moves().count(), 14);
+///```
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Variant {
+    ///name of the variant, also used to (de)serialize it
+    pub name: &'static str,
+    ///number of rows (and columns) of the board, 9 at most
+    pub size: u16,
+    ///piece types used in this variant
+    pub pieces: &'static [PieceType],
+    ///moves of each piece type, indexed by PieceType
+    pub moves: [PieceMoves; 8],
+    ///moves of each promoted piece type, None for the pieces that cannot promote
+    pub promoted_moves: [Option<PieceMoves>; 8],
+    ///number of rows, on the opponent's side, where the pieces can promote
+    pub promotion_rows: u16,
+    ///whether captured pieces can be dropped back on the board
+    pub drops: bool,
+    ///the starting position, as a SFEN
+    pub start: &'static str,
+}
+
+impl Variant {
+    ///the usual shogi
+    pub const STANDARD: Variant = Variant {
+        name: "shogi",
+        size: 9,
+        pieces: &[
+            PieceType::Pawn,
+            PieceType::King,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Gold,
+            PieceType::Silver,
+            PieceType::Knight,
+            PieceType::Lance,
+        ],
+        moves: STANDARD_MOVES,
+        promoted_moves: STANDARD_PROMOTED_MOVES,
+        promotion_rows: 3,
+        drops: true,
+        start: crate::sfen::START_SFEN,
+    };
+
+    ///minishogi: a 5x5 board without knights nor lances, where pieces promote on the last row
+    pub const MINISHOGI: Variant = Variant {
+        name: "minishogi",
+        size: 5,
+        pieces: &[
+            PieceType::Pawn,
+            PieceType::King,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Gold,
+            PieceType::Silver,
+        ],
+        promotion_rows: 1,
+        start: crate::sfen::MINISHOGI_SFEN,
+        ..Variant::STANDARD
+    };
+
+    ///judkins shogi: a 6x6 board without lances, where pieces promote on the last two rows
+    pub const JUDKINS: Variant = Variant {
+        name: "judkins",
+        size: 6,
+        pieces: &[
+            PieceType::Pawn,
+            PieceType::King,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Gold,
+            PieceType::Silver,
+            PieceType::Knight,
+        ],
+        promotion_rows: 2,
+        start: "rbnsgk/5p/6/6/P5/KGSNBR b - 1",
+        ..Variant::STANDARD
+    };
+
+    ///the variants known by the crate, for instance to read a board from its SFEN or its name
+    pub const ALL: [&'static Variant; 3] =
+        [&Variant::STANDARD, &Variant::MINISHOGI, &Variant::JUDKINS];
+
+    ///return the known variant of that name
+    pub fn by_name(name: &str) -> Option<&'static Variant> {
+        Variant::ALL.iter().copied().find(|v| v.name == name)
+    }
+
+    ///return the moves of a piece (as seen by the first player), None if it cannot be promoted
+    ///but is
+    pub fn piece_moves(&self, piecetype: PieceType, promoted: bool) -> Option<PieceMoves> {
+        if promoted {
+            self.promoted_moves[piecetype as usize]
+        } else {
+            Some(self.moves[piecetype as usize])
+        }
+    }
+
+    ///whether that kind of piece can be promoted
+    pub fn can_promote(&self, piecetype: PieceType) -> bool {
+        self.promoted_moves[piecetype as usize].is_some()
+    }
+}
+
+///variants are long to describe: only their name is shown
+impl fmt::Debug for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Variant({})", self.name)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::*;
+    use crate::invalidmoveerror::*;
+    use crate::variant::*;
+
+    #[test]
+    fn known_variants() {
+        for variant in Variant::ALL.iter() {
+            let b = Board::start(variant);
+            assert_eq!(b.rules.variant, *variant);
+            assert_eq!(b.to_sfen(1), variant.start);
+            assert_eq!(Variant::by_name(variant.name), Some(*variant));
+        }
+        assert_eq!(Variant::by_name("chess"), None);
+        assert!(!Variant::STANDARD.can_promote(PieceType::Gold));
+        assert_eq!(
+            Variant::STANDARD.piece_moves(PieceType::Rook, true),
+            Some(DRAGON)
+        );
+    }
+
+    #[test]
+    fn judkins() {
+        let b = Board::start(&Variant::JUDKINS);
+        assert_eq!(b.perft(1), 20);
+        // the knight is the only piece able to jump, and it promotes on the last two rows
+        assert!(b.check_move("N4a-3c").is_ok());
+        let b = b.play_move("N4a-3c").play_move("P6e-6d");
+        assert!(b.check_move("N3c-2e").is_err());
+        assert!(b
+            .play_move("N3c-2e+")
+            .is_occupied_by("2e".parse().unwrap())
+            .is_some_and(|knight| knight.promoted));
+        assert!(Board::from_sfen("rbnsgk/5p/6/6/P5/KGSNBL b - 1").is_err());
+    }
+
+    #[test]
+    fn without_drops() {
+        static NO_DROPS: Variant = Variant {
+            name: "no drops",
+            drops: false,
+            ..Variant::STANDARD
+        };
+        let b = Board::start(&NO_DROPS)
+            .play_move("P3c-3d")
+            .play_move("P7g-7f")
+            .play_move("B2b-8h+")
+            .play_move("S7i-8h");
+        assert_eq!(b.iter().filter(|p| p.position.is_none()).count(), 2);
+        assert_eq!(
+            b.check_move("B*5e"),
+            Err(InvalidMoveError::DropError {
+                piecetype: PieceType::Bishop
+            })
+        );
+        assert!(b.iter_moves().all(|mv| !mv.contains('*')));
+    }
+}